@@ -0,0 +1,425 @@
+use crate::api_types::*;
+use crate::session::{self, SessionManager};
+use crate::capabilities;
+use crate::connectivity;
+use crate::http_client;
+use crate::inspector::RecordedSend;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// Errors returned by the typed gateway client
+#[derive(Debug)]
+pub enum ApiError {
+    /// No token is available or the backend rejected it with 401
+    Unauthorized,
+    /// The backend answered with a non-success status
+    Status { status: u16, body: String },
+    /// The request could not be sent or the response could not be read
+    Network(String),
+    /// The response body did not match the expected type
+    Decode(String),
+    /// The request was not sent because the backend is offline or incompatible
+    Unavailable(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized => write!(f, "Authentication required"),
+            ApiError::Status { status, body } => write!(f, "API request failed: {} {}", status, body),
+            ApiError::Network(e) => write!(f, "Backend request failed: {}", e),
+            ApiError::Decode(e) => write!(f, "Failed to parse backend response: {}", e),
+            ApiError::Unavailable(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<ApiError> for String {
+    fn from(e: ApiError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Typed client for the Editron gateway API.
+/// It does not depend on an `AppHandle`, so it can be used from Tauri commands,
+/// command-line tools and tests alike.
+#[derive(Clone)]
+pub struct ApiClient {
+    client: Arc<Client>,
+    api_url: String,
+    access_token: String,
+}
+
+impl ApiClient {
+    /// Creates a client for the given API base URL (e.g. `http://localhost:5000/api/v1`)
+    pub fn new(api_url: impl Into<String>, access_token: impl Into<String>) -> Self {
+        Self {
+            client: http_client::get_client(),
+            api_url: api_url.into().trim_end_matches('/').to_string(),
+            access_token: access_token.into(),
+        }
+    }
+
+    /// Creates a client from the configured backend and the logged-in user's token
+    pub fn from_session(session: &SessionManager) -> Result<Self, ApiError> {
+        let token = session.current_access_token().ok_or(ApiError::Unauthorized)?;
+        Ok(Self::new(session.config().backend_api_url(), token))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.api_url, path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", self.access_token))
+    }
+
+    /// Sends an authorized request and maps non-success statuses to `ApiError`
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, ApiError> {
+        let res = self
+            .authorize(request)
+            .send_recorded()
+            .await
+            .map_err(|e| ApiError::Network(e.to_string()))?;
+
+        let status = res.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(ApiError::Unauthorized);
+        }
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(ApiError::Status { status: status.as_u16(), body });
+        }
+        Ok(res)
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ApiError> {
+        self.send(request)
+            .await?
+            .json::<T>()
+            .await
+            .map_err(|e| ApiError::Decode(e.to_string()))
+    }
+
+    async fn empty(&self, request: RequestBuilder) -> Result<(), ApiError> {
+        self.send(request).await.map(|_| ())
+    }
+
+    /// Returns the underlying HTTP client, e.g. for streaming requests
+    pub fn http(&self) -> &Client {
+        &self.client
+    }
+
+    /// Builds a request against an API path such as `/projects`
+    pub fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        self.client.request(method, self.url(path))
+    }
+
+    // Auth
+
+    pub async fn get_profile(&self) -> Result<UserProfile, ApiError> {
+        self.json(self.client.get(self.url("/auth/user"))).await
+    }
+
+    // Projects
+
+    pub async fn list_projects(&self) -> Result<Vec<Project>, ApiError> {
+        self.json(self.client.get(self.url("/projects"))).await
+    }
+
+    pub async fn get_project(&self, project_uuid: &str) -> Result<Project, ApiError> {
+        self.json(self.client.get(self.url(&format!("/projects/{}/details", project_uuid)))).await
+    }
+
+    pub async fn create_project(&self, request: &CreateProjectRequest) -> Result<Project, ApiError> {
+        self.json(self.client.post(self.url("/projects")).json(request)).await
+    }
+
+    pub async fn update_project(&self, project_uuid: &str, request: &UpdateProjectRequest) -> Result<Project, ApiError> {
+        self.json(
+            self.client
+                .patch(self.url(&format!("/projects/{}/update", project_uuid)))
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn delete_project(&self, project_uuid: &str) -> Result<(), ApiError> {
+        self.empty(self.client.delete(self.url(&format!("/projects/{}/delete", project_uuid)))).await
+    }
+
+    // Documents
+
+    pub async fn list_documents(&self, project_uuid: &str) -> Result<Vec<Document>, ApiError> {
+        self.json(
+            self.client
+                .get(self.url("/documents"))
+                .query(&[("projectUuid", project_uuid)]),
+        )
+        .await
+    }
+
+    pub async fn get_document(&self, document_uuid: &str, project_uuid: &str) -> Result<Document, ApiError> {
+        self.json(
+            self.client
+                .get(self.url(&format!("/documents/{}", document_uuid)))
+                .query(&[("projectUuid", project_uuid)]),
+        )
+        .await
+    }
+
+    pub async fn update_document(
+        &self,
+        document_uuid: &str,
+        project_uuid: &str,
+        request: &UpdateDocumentRequest,
+    ) -> Result<Document, ApiError> {
+        self.json(
+            self.client
+                .patch(self.url(&format!("/documents/{}", document_uuid)))
+                .query(&[("projectUuid", project_uuid)])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn delete_document(&self, document_uuid: &str, project_uuid: &str) -> Result<(), ApiError> {
+        self.empty(
+            self.client
+                .delete(self.url(&format!("/documents/{}", document_uuid)))
+                .query(&[("projectUuid", project_uuid)]),
+        )
+        .await
+    }
+
+    pub async fn agent_edit(&self, project_uuid: &str, request: &AgentEditRequest) -> Result<AgentEditResponse, ApiError> {
+        self.json(
+            self.client
+                .post(self.url("/documents/agent-edit"))
+                .query(&[("projectUuid", project_uuid)])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn upload_document(&self, project_uuid: &str, file_name: &str, bytes: Vec<u8>) -> Result<Document, ApiError> {
+        let part = reqwest::multipart::Part::bytes(bytes)
+            .file_name(file_name.to_string())
+            .mime_str("application/vnd.openxmlformats-officedocument.wordprocessingml.document")
+            .map_err(|e| ApiError::Network(e.to_string()))?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        self.json(
+            self.client
+                .post(self.url("/documents/upload-and-preview"))
+                .query(&[("projectUuid", project_uuid)])
+                .multipart(form),
+        )
+        .await
+    }
+
+    // Chat
+
+    pub async fn chat_history(&self) -> Result<Vec<ChatMessage>, ApiError> {
+        self.json(self.client.get(self.url("/chat/history"))).await
+    }
+
+    // Google API
+
+    pub async fn google_api_auth_url(&self) -> Result<GoogleApiAuthUrlResponse, ApiError> {
+        self.json(self.client.get(self.url("/google-api/auth-url"))).await
+    }
+
+    pub async fn exchange_google_api_code(&self, request: &ExchangeGoogleApiCodeRequest) -> Result<GoogleApiStatusResponse, ApiError> {
+        self.json(self.client.post(self.url("/google-api/exchange-code")).json(request)).await
+    }
+
+    pub async fn disconnect_google_api(&self) -> Result<GoogleApiStatusResponse, ApiError> {
+        self.json(self.client.delete(self.url("/google-api/disconnect"))).await
+    }
+
+    pub async fn search_contacts(&self, query: &str) -> Result<Vec<Contact>, ApiError> {
+        self.json(
+            self.client
+                .get(self.url("/google-api/contacts/search"))
+                .query(&[("q", query)]),
+        )
+        .await
+    }
+
+    pub async fn send_email(&self, request: &SendEmailRequest) -> Result<SendEmailResponse, ApiError> {
+        self.json(self.client.post(self.url("/google-api/send-email")).json(request)).await
+    }
+
+    pub async fn download_pdf(&self, document_uuid: &str) -> Result<Vec<u8>, ApiError> {
+        let res = self
+            .send(self.client.get(self.url(&format!("/google-api/download-pdf/{}", document_uuid))))
+            .await?;
+        res.bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| ApiError::Network(e.to_string()))
+    }
+}
+
+/// Runs `f` with a session client, refreshing the token and retrying once on 401.
+/// Intended for Tauri commands; errors are returned as strings like other commands.
+pub async fn with_session<T, F, Fut>(app: &AppHandle, f: F) -> Result<T, String>
+where
+    F: Fn(ApiClient) -> Fut,
+    Fut: Future<Output = Result<T, ApiError>>,
+{
+    with_session_typed(app, f).await.map_err(|e| e.to_string())
+}
+
+/// Like `with_session`, but keeps the `ApiError` for callers that handle an
+/// unreachable backend differently from a rejected request
+pub async fn with_session_typed<T, F, Fut>(app: &AppHandle, f: F) -> Result<T, ApiError>
+where
+    F: Fn(ApiClient) -> Fut,
+    Fut: Future<Output = Result<T, ApiError>>,
+{
+    capabilities::ensure_compatible().map_err(ApiError::Unavailable)?;
+    connectivity::ensure_online().await.map_err(ApiError::Unavailable)?;
+    let session = session::manager(app);
+    let client = ApiClient::from_session(&session)?;
+    let result = f(client).await;
+    connectivity::report_request_result(!matches!(result, Err(ApiError::Network(_))));
+    match result {
+        Err(ApiError::Unauthorized) => {
+            log::warn!("Backend request returned 401 - attempting token refresh");
+            session.refresh_access_token().await.map_err(|e| {
+                log::error!("Token refresh failed: {}", e);
+                ApiError::Unauthorized
+            })?;
+            let client = ApiClient::from_session(&session)?;
+            f(client).await
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use warp::http::StatusCode as WarpStatus;
+    use warp::Filter;
+
+    const TOKEN: &str = "test-token";
+
+    fn project(uuid: &str, name: &str) -> Value {
+        json!({
+            "id": 1,
+            "uuid": uuid,
+            "name": name,
+            "description": null,
+            "customInstructions": null,
+            "createdAt": "2026-01-05T09:30:00.000Z",
+            "updatedAt": "2026-01-05T09:30:00.000Z",
+        })
+    }
+
+    /// Serves a few gateway routes on a free local port. Requests without the
+    /// test token are answered with 401, like the gateway's JWT guard.
+    async fn stub_gateway() -> SocketAddr {
+        let authorized = warp::header::optional::<String>("authorization").and_then(|header: Option<String>| async move {
+            if header.as_deref() == Some(&format!("Bearer {}", TOKEN)) {
+                Ok(())
+            } else {
+                Err(warp::reject::custom(Unauthorized))
+            }
+        });
+
+        let list_projects = warp::path!("api" / "v1" / "projects")
+            .and(warp::get())
+            .map(|| warp::reply::json(&json!([project("p-1", "Essays"), project("p-2", "Letters")])));
+        let create_project = warp::path!("api" / "v1" / "projects")
+            .and(warp::post())
+            .and(warp::body::json())
+            .map(|body: Value| {
+                let name = body["name"].as_str().unwrap_or_default().to_string();
+                warp::reply::with_status(warp::reply::json(&project("p-3", &name)), WarpStatus::CREATED)
+            });
+        let missing_document = warp::path!("api" / "v1" / "documents" / String)
+            .and(warp::get())
+            .map(|_uuid: String| warp::reply::with_status("Document not found", WarpStatus::NOT_FOUND));
+        let malformed = warp::path!("api" / "v1" / "chat" / "history")
+            .and(warp::get())
+            .map(|| warp::reply::json(&json!({ "not": "a list" })));
+
+        let routes = authorized
+            .untuple_one()
+            .and(list_projects.or(create_project).or(missing_document).or(malformed))
+            .recover(|rejection: warp::Rejection| async move {
+                if rejection.find::<Unauthorized>().is_some() {
+                    Ok(warp::reply::with_status("Unauthorized", WarpStatus::UNAUTHORIZED))
+                } else {
+                    Err(rejection)
+                }
+            });
+
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[derive(Debug)]
+    struct Unauthorized;
+    impl warp::reject::Reject for Unauthorized {}
+
+    fn client(addr: SocketAddr, token: &str) -> ApiClient {
+        ApiClient::new(format!("http://{}/api/v1/", addr), token)
+    }
+
+    #[tokio::test]
+    async fn typed_calls_decode_gateway_responses() {
+        let client = client(stub_gateway().await, TOKEN);
+
+        let projects = client.list_projects().await.unwrap();
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[1].uuid, "p-2");
+        assert_eq!(projects[1].name, "Letters");
+
+        let request = CreateProjectRequest { name: "Poems".to_string(), ..Default::default() };
+        let created = client.create_project(&request).await.unwrap();
+        assert_eq!(created.uuid, "p-3");
+        assert_eq!(created.name, "Poems");
+    }
+
+    #[tokio::test]
+    async fn rejected_tokens_map_to_unauthorized() {
+        let client = client(stub_gateway().await, "expired-token");
+        assert!(matches!(client.list_projects().await, Err(ApiError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn error_statuses_keep_the_status_and_body() {
+        let client = client(stub_gateway().await, TOKEN);
+
+        let error = client.get_document("d-404", "p-1").await.unwrap_err();
+        assert!(matches!(&error, ApiError::Status { status: 404, body } if body == "Document not found"));
+        assert_eq!(String::from(error), "API request failed: 404 Document not found");
+    }
+
+    #[tokio::test]
+    async fn unexpected_bodies_map_to_decode_errors() {
+        let client = client(stub_gateway().await, TOKEN);
+        assert!(matches!(client.chat_history().await, Err(ApiError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn unreachable_gateways_map_to_network_errors() {
+        // Bind and drop a listener so the port is known to be closed
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let client = client(addr, TOKEN);
+        assert!(matches!(client.list_projects().await, Err(ApiError::Network(_))));
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+pub use crate::session::UserProfile;
+
+// Request and response types mirroring the gateway DTOs and entities.
+// Field names follow the gateway's camelCase JSON.
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Project {
+    pub id: i64,
+    pub uuid: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "customInstructions")]
+    pub custom_instructions: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CreateProjectRequest {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "customInstructions", skip_serializing_if = "Option::is_none")]
+    pub custom_instructions: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateProjectRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "customInstructions", skip_serializing_if = "Option::is_none")]
+    pub custom_instructions: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentStatus {
+    #[serde(rename = "PROCESSING")]
+    Processing,
+    #[serde(rename = "READY")]
+    Ready,
+    #[serde(rename = "ERROR")]
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Document {
+    pub id: i64,
+    pub uuid: String,
+    pub title: String,
+    pub content: String,
+    pub status: DocumentStatus,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateDocumentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AgentEditRequest {
+    #[serde(rename = "documentUuid")]
+    pub document_uuid: String,
+    #[serde(rename = "promptText")]
+    pub prompt_text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AgentEditResponse {
+    #[serde(rename = "originalContent")]
+    pub original_content: String,
+    #[serde(rename = "suggestedContent")]
+    pub suggested_content: String,
+    #[serde(rename = "diffHtml")]
+    pub diff_html: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatMode {
+    Chat,
+    Agent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatMessageRole {
+    User,
+    Assistant,
+    System,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub id: i64,
+    pub role: ChatMessageRole,
+    pub content: String,
+    pub mode: Option<ChatMode>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatQueryRequest {
+    #[serde(rename = "promptText")]
+    pub prompt_text: String,
+    #[serde(rename = "documentUuid", skip_serializing_if = "Option::is_none")]
+    pub document_uuid: Option<String>,
+    #[serde(rename = "projectUuid", skip_serializing_if = "Option::is_none")]
+    pub project_uuid: Option<String>,
+    pub mode: ChatMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GoogleApiAuthUrlResponse {
+    #[serde(rename = "authUrl")]
+    pub auth_url: String,
+    #[serde(rename = "codeVerifier")]
+    pub code_verifier: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExchangeGoogleApiCodeRequest {
+    pub code: String,
+    #[serde(rename = "codeVerifier")]
+    pub code_verifier: String,
+    #[serde(rename = "redirectUri")]
+    pub redirect_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GoogleApiStatusResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Contact {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SendEmailRequest {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    #[serde(rename = "documentUuid")]
+    pub document_uuid: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SendEmailResponse {
+    pub success: bool,
+    #[serde(rename = "messageId")]
+    pub message_id: String,
+}
@@ -1,197 +1,38 @@
+use crate::backend;
+use crate::capabilities::{self, Feature};
+use crate::connectivity;
 use crate::http_client;
-use crate::config::AppConfig;
-use serde::{Deserialize, Serialize};
+use crate::inspector::RecordedSend;
+use crate::locks::lock;
+use crate::session::{SessionManager, UserProfile};
+use crate::shutdown::{CallbackServerShutdown, Shutdown};
 use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Mutex;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use tauri_plugin_store::StoreExt;
+use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_opener::OpenerExt;
 use warp::Filter;
 use tokio::sync::oneshot;
-use url;
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Server {
-    pub id: String,
-    pub profile: Option<UserProfile>,
-    pub available: bool,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ServerAccessToken {
-    pub server_id: String,
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: u64,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct UserProfile {
-    pub id: i32,
-    pub email: String,
-    pub name: String,
-    #[serde(rename = "profilePicture")]
-    pub profile_picture: Option<String>,
-    #[serde(rename = "authProvider")]
-    pub auth_provider: String,
-    #[serde(rename = "isGoogleApiConnected")]
-    pub is_google_api_connected: Option<bool>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct AuthUrlResponse {
-    url: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct TokenExchangeRequest {
-    code: String,
-    #[serde(rename = "codeVerifier")]
-    code_verifier: String,
-    provider: String,
-    #[serde(rename = "tauriRedirectUri")]
-    tauri_redirect_uri: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct TokenResponse {
-    #[serde(rename = "accessToken")]
-    access_token: String,
-    #[serde(rename = "refreshToken")]
-    refresh_token: String,
-}
-
-impl ServerAccessToken {
-    pub fn new(server_id: String, access_token: String, refresh_token: String, expires_at: u64) -> Self {
-        Self {
-            server_id,
-            access_token,
-            refresh_token,
-            expires_at,
-        }
-    }
-}
-
-lazy_static::lazy_static! {
-    static ref SERVERS: Mutex<Vec<Server>> = Mutex::new(vec![]);
-    static ref ACCESS_TOKENS: Mutex<HashMap<String, ServerAccessToken>> = Mutex::new(HashMap::new());
-    static ref OAUTH_STATE: Mutex<Option<String>> = Mutex::new(None);
-    static ref CONFIG: AppConfig = AppConfig::load();
-}
-
-/// Gets a server by ID from the global state
-pub fn get_server_by_id(id: &str) -> Option<Server> {
-    SERVERS.lock().unwrap().iter().find(|s| s.id == id).cloned()
-}
-
-/// Saves or updates a server in the global state
-pub fn save_server(server: &Server) {
-    let mut servers = SERVERS.lock().unwrap();
-    if let Some(idx) = servers.iter().position(|s| s.id == server.id) {
-        servers[idx] = server.clone();
-    } else {
-        servers.push(server.clone());
-    }
-}
-
-/// Saves an access token for a server
-pub fn save_access_token(server_id: String, token: ServerAccessToken) {
-    ACCESS_TOKENS.lock().unwrap().insert(server_id, token);
-}
-
-/// Removes an access token for a server (used during logout)
-pub fn remove_access_token(server_id: &str) {
-    ACCESS_TOKENS.lock().unwrap().remove(server_id);
-}
 
 /// Initialize the stores during app setup
+/// The store files themselves are read and written by `store_schema`
 pub fn initialize_stores(app: &AppHandle) -> Result<(), Box<dyn Error>> {
-    use tauri_plugin_store::StoreBuilder;
-    
-    // Create servers store
-    let _servers_store = StoreBuilder::new(app, "servers.json")
-        .build()?;
-    
-    // Create tokens store  
-    let _tokens_store = StoreBuilder::new(app, "tokens.json")
-        .build()?;
+    let dir = app.path().app_data_dir()?;
+    std::fs::create_dir_all(&dir)?;
     
     log::info!("Stores initialized successfully");
     Ok(())
 }
 
-/// Checks if a server has a valid access token
-pub fn has_access_token(server_id: &str) -> bool {
-    ACCESS_TOKENS.lock().unwrap().contains_key(server_id)
-}
-
-/// Persists servers to storage
-pub async fn persist_servers(app: &AppHandle) -> Result<(), Box<dyn Error>> {
-    let store = app
-        .get_store("servers.json")
-        .ok_or_else(|| "Could not get servers store - store not initialized")?;
-    let servers = SERVERS.lock().unwrap().clone();
-    store.set("servers".to_string(), serde_json::to_value(servers)?);
-    store.save()?;
-    log::info!("Servers persisted to storage");
-    Ok(())
-}
-
-/// Persists access tokens to storage
-pub async fn persist_servers_token(app: &AppHandle) -> Result<(), Box<dyn Error>> {
-    let store = app
-        .get_store("tokens.json")
-        .ok_or_else(|| "Could not get tokens store - store not initialized")?;
-    let tokens = ACCESS_TOKENS.lock().unwrap().clone();
-    store.set("tokens".to_string(), serde_json::to_value(tokens)?);
-    store.save()?;
-    log::info!("Access tokens persisted to storage");
-    Ok(())
-}
-
-/// Loads servers from storage
-pub fn load_servers(app: &AppHandle) -> Result<(), Box<dyn Error>> {
-    let store = app
-        .get_store("servers.json")
-        .ok_or_else(|| "Could not get servers store - store not initialized")?;
-    if let Some(v) = store.get("servers") {
-        let loaded: Vec<Server> = serde_json::from_value(v.clone())?;
-        *SERVERS.lock().unwrap() = loaded;
-        log::info!("Servers loaded from storage");
-    } else {
-        log::info!("No servers found in storage - starting fresh");
-    }
-    Ok(())
-}
-
-/// Loads access tokens from storage
-pub fn load_servers_token(app: &AppHandle) -> Result<(), Box<dyn Error>> {
-    let store = app
-        .get_store("tokens.json")
-        .ok_or_else(|| "Could not get tokens store - store not initialized")?;
-    if let Some(v) = store.get("tokens") {
-        let loaded: HashMap<String, ServerAccessToken> = serde_json::from_value(v.clone())?;
-        *ACCESS_TOKENS.lock().unwrap() = loaded;
-        log::info!("Access tokens loaded from storage");
-    } else {
-        log::info!("No tokens found in storage - starting fresh");
-    }
-    Ok(())
-}
-
-/// Generate a random state parameter for OAuth security
-fn generate_state() -> String {
-    use base64::{engine::general_purpose, Engine as _};
-    
-    general_purpose::URL_SAFE_NO_PAD.encode(
-        (0..32).map(|_| rand::random::<u8>()).collect::<Vec<u8>>()
-    )
+/// Query parameters of a successful OAuth callback
+struct OAuthCallback {
+    code: String,
+    state: Option<String>,
 }
 
 /// Find an available port starting from the given port
-fn find_available_port(start_port: u16) -> Option<u16> {
+pub fn find_available_port(start_port: u16) -> Option<u16> {
     use std::net::TcpListener;
     
     for port in start_port..start_port + 100 {
@@ -202,64 +43,14 @@ fn find_available_port(start_port: u16) -> Option<u16> {
     None
 }
 
-/// Gets user profile from the backend using JWT token
-/// Desktop apps use JWT tokens in Authorization headers, NOT cookies
-async fn get_user_profile(server_id: &str) -> Result<UserProfile, String> {
-    log::info!("Fetching user profile from backend using JWT token");
-    
-    let token = {
-        let tokens = ACCESS_TOKENS.lock().unwrap();
-        tokens.get(server_id)
-            .map(|t| t.access_token.clone())
-            .ok_or_else(|| "No JWT access token found".to_string())?
-    };
-
-    let client = http_client::get_client();
-    // Desktop apps use JWT tokens via Authorization header (NOT cookies)
-    let res = client
-        .get(&CONFIG.user_profile_url()) // JWT-protected endpoint
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("Error fetching profile with JWT token: {}", e);
-            e.to_string()
-        })?;
-
-    if res.status().is_success() {
-        // Debug: log the raw response
-        let response_text = res.text().await.map_err(|e| {
-            log::error!("Error reading response text: {}", e);
-            e.to_string()
-        })?;
-        
-        log::debug!("Profile response from backend: {}", response_text);
-        
-        let profile: UserProfile = serde_json::from_str(&response_text).map_err(|e| {
-            log::error!("Error parsing profile JSON: {}", e);
-            log::error!("Raw response was: {}", response_text);
-            e.to_string()
-        })?;
-        
-        log::info!("Successfully fetched user profile: {:?}", profile);
-        Ok(profile)
-    } else if res.status() == reqwest::StatusCode::UNAUTHORIZED {
-        log::warn!("Profile request returned 401 Unauthorized - token expired");
-        Err("Unauthorized: Token expired or invalid".to_string())
-    } else {
-        let status = res.status();
-        log::error!("Profile request failed with status: {}", status);
-        Err(format!("Request failed with status: {}", status))
-    }
-}
-
 /// Start a temporary HTTP server to catch OAuth callback
-async fn start_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result<String, String> {
+async fn start_oauth_callback_server(app_handle: AppHandle, port: u16, timeout_seconds: u64) -> Result<OAuthCallback, String> {
     log::info!("Starting OAuth callback server on port {}", port);
     
-    let (tx, rx) = oneshot::channel::<String>();
+    let (tx, rx) = oneshot::channel::<Result<OAuthCallback, String>>();
     let tx = Arc::new(Mutex::new(Some(tx)));
-    let shutdown_tx = Arc::new(Mutex::new(None::<oneshot::Sender<()>>));
+    let shutdown_tx: CallbackServerShutdown = Arc::new(Mutex::new(None));
+    let app = app_handle.clone();
 
     // Clone shutdown_tx before moving into closure
     let shutdown_tx_clone = shutdown_tx.clone();
@@ -270,19 +61,22 @@ async fn start_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result
         .and(warp::any().map(move || tx.clone()))
         .and(warp::any().map(move || app_handle.clone()))
         .and(warp::any().map(move || shutdown_tx_clone.clone()))
-        .and_then(|query_params: HashMap<String, String>, tx: Arc<Mutex<Option<oneshot::Sender<String>>>>, _app: AppHandle, shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>| async move {
+        .and_then(|query_params: HashMap<String, String>, tx: Arc<Mutex<Option<oneshot::Sender<Result<OAuthCallback, String>>>>>, _app: AppHandle, shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>| async move {
             log::info!("OAuth callback received");
             
             if let Some(code) = query_params.get("code") {
-                log::info!("Authorization code received: {}", &code[..10.min(code.len())]);
+                log::info!("Authorization code received");
                 
                 // Send the code through the channel
-                if let Some(sender) = tx.lock().unwrap().take() {
-                    let _ = sender.send(code.clone());
+                if let Some(sender) = lock(&tx).take() {
+                    let _ = sender.send(Ok(OAuthCallback {
+                        code: code.clone(),
+                        state: query_params.get("state").cloned(),
+                    }));
                 }
                 
                 // Schedule server shutdown after response
-                if let Some(shutdown_sender) = shutdown_tx.lock().unwrap().take() {
+                if let Some(shutdown_sender) = lock(&shutdown_tx).take() {
                     tokio::spawn(async move {
                         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                         let _ = shutdown_sender.send(());
@@ -455,12 +249,12 @@ async fn start_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result
                 log::error!("OAuth error received: {}", error);
                 
                 // Send error through the channel
-                if let Some(sender) = tx.lock().unwrap().take() {
-                    let _ = sender.send(format!("error:{}", error));
+                if let Some(sender) = lock(&tx).take() {
+                    let _ = sender.send(Err(error.clone()));
                 }
                 
                 // Schedule server shutdown after response
-                if let Some(shutdown_sender) = shutdown_tx.lock().unwrap().take() {
+                if let Some(shutdown_sender) = lock(&shutdown_tx).take() {
                     tokio::spawn(async move {
                         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                         let _ = shutdown_sender.send(());
@@ -774,7 +568,8 @@ async fn start_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result
     
     // Create shutdown channel
     let (shutdown_tx_main, shutdown_rx) = oneshot::channel::<()>();
-    *shutdown_tx.lock().unwrap() = Some(shutdown_tx_main);
+    *lock(&shutdown_tx) = Some(shutdown_tx_main);
+    app.state::<Shutdown>().register_callback_server(port, shutdown_tx.clone());
     
     // Start the server with graceful shutdown
     let (_, server) = warp::serve(routes)
@@ -787,33 +582,32 @@ async fn start_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result
     let _server_handle = tokio::spawn(server);
     
     // Update the redirect URI to use the actual port
-    tokio::select! {
+    let result = tokio::select! {
                  result = rx => {
              match result {
-                 Ok(auth_result) => {
-                     if auth_result.starts_with("error:") {
-                         Err(auth_result.replace("error:", ""))
-                     } else {
-                         Ok(auth_result)
-                     }
-                 }
+                 Ok(callback) => callback,
                  Err(_) => Err("Failed to receive OAuth callback".to_string())
              }
          }
-                 _ = tokio::time::sleep(std::time::Duration::from_secs(CONFIG.oauth.timeout_seconds)) => {
-                         log::warn!("OAuth callback server timed out after {} seconds", CONFIG.oauth.timeout_seconds);
+                 _ = tokio::time::sleep(std::time::Duration::from_secs(timeout_seconds)) => {
+                         log::warn!("OAuth callback server timed out after {} seconds", timeout_seconds);
             Err("Authentication timed out".to_string())
         }
-    }
+    };
+
+    // Stops the server too when the callback never came
+    app.state::<Shutdown>().release_callback_server(port);
+    result
 }
 
 /// Start a temporary HTTP server to catch Gmail API OAuth callback
-async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) -> Result<String, String> {
+async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16, timeout_seconds: u64) -> Result<String, String> {
     log::info!("Starting Gmail API OAuth callback server on port {}", port);
     
     let (tx, rx) = oneshot::channel::<String>();
     let tx = Arc::new(Mutex::new(Some(tx)));
-    let shutdown_tx = Arc::new(Mutex::new(None::<oneshot::Sender<()>>));
+    let shutdown_tx: CallbackServerShutdown = Arc::new(Mutex::new(None));
+    let app = app_handle.clone();
 
     let shutdown_tx_clone = shutdown_tx.clone();
     
@@ -824,19 +618,18 @@ async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) ->
         .and(warp::any().map(move || shutdown_tx_clone.clone()))
         .and_then(|query_params: HashMap<String, String>, tx: Arc<Mutex<Option<oneshot::Sender<String>>>>, _app: AppHandle, shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>| async move {
             log::info!("Gmail API OAuth callback received");
-            log::info!("Query parameters: {:?}", query_params);
             
             if let Some(code) = query_params.get("code") {
-                log::info!("Gmail API authorization code received: {}", &code[..10.min(code.len())]);
+                log::info!("Gmail API authorization code received");
                 
-                if let Some(sender) = tx.lock().unwrap().take() {
+                if let Some(sender) = lock(&tx).take() {
                     log::info!("Sending code through channel");
                     let _ = sender.send(code.clone());
                 } else {
                     log::error!("No sender available in channel");
                 }
                 
-                if let Some(shutdown_sender) = shutdown_tx.lock().unwrap().take() {
+                if let Some(shutdown_sender) = lock(&shutdown_tx).take() {
                     tokio::spawn(async move {
                         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                         let _ = shutdown_sender.send(());
@@ -1000,11 +793,11 @@ async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) ->
             } else if let Some(error) = query_params.get("error") {
                 log::error!("Gmail API OAuth error received: {}", error);
                 
-                if let Some(sender) = tx.lock().unwrap().take() {
+                if let Some(sender) = lock(&tx).take() {
                     let _ = sender.send(format!("error:{}", error));
                 }
                 
-                if let Some(shutdown_sender) = shutdown_tx.lock().unwrap().take() {
+                if let Some(shutdown_sender) = lock(&shutdown_tx).take() {
                     tokio::spawn(async move {
                         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                         let _ = shutdown_sender.send(());
@@ -1315,7 +1108,8 @@ async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) ->
     let routes = callback_route.with(warp::log("gmail_oauth_callback"));
     
     let (shutdown_tx_main, shutdown_rx) = oneshot::channel::<()>();
-    *shutdown_tx.lock().unwrap() = Some(shutdown_tx_main);
+    *lock(&shutdown_tx) = Some(shutdown_tx_main);
+    app.state::<Shutdown>().register_callback_server(port, shutdown_tx.clone());
     
     let (_, server) = warp::serve(routes)
         .bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
@@ -1325,7 +1119,7 @@ async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) ->
     
     let _server_handle = tokio::spawn(server);
     
-    tokio::select! {
+    let result = tokio::select! {
         result = rx => {
             match result {
                 Ok(auth_result) => {
@@ -1338,58 +1132,37 @@ async fn start_gmail_oauth_callback_server(app_handle: AppHandle, port: u16) ->
                 Err(_) => Err("Failed to receive Gmail API OAuth callback".to_string())
             }
         }
-        _ = tokio::time::sleep(std::time::Duration::from_secs(CONFIG.oauth.timeout_seconds)) => {
-            log::warn!("Gmail API OAuth callback server timed out after {} seconds", CONFIG.oauth.timeout_seconds);
+        _ = tokio::time::sleep(std::time::Duration::from_secs(timeout_seconds)) => {
+            log::warn!("Gmail API OAuth callback server timed out after {} seconds", timeout_seconds);
             Err("Gmail API authentication timed out".to_string())
         }
-    }
+    };
+
+    // Stops the server too when the callback never came
+    app.state::<Shutdown>().release_callback_server(port);
+    result
 }
 
 /// Tauri command to start the Google OAuth login flow
 #[tauri::command]
-pub async fn start_login_flow(app: AppHandle) -> Result<(), String> {
+pub async fn start_login_flow(app: AppHandle, session: State<'_, SessionManager>) -> Result<(), String> {
     log::info!("Starting Google OAuth login flow");
-    
-    // Generate state for OAuth security
-    let state = generate_state();
-    *OAUTH_STATE.lock().unwrap() = Some(state);
+    let config = session.config();
     
     // Start the callback server first to get the port
-    let port = find_available_port(CONFIG.oauth.callback_port_start).ok_or_else(|| "No available port found".to_string())?;
-    let redirect_uri = CONFIG.oauth_callback_url(port);
+    let port = find_available_port(config.oauth.callback_port_start).ok_or_else(|| "No available port found".to_string())?;
+    let redirect_uri = config.oauth_callback_url(port);
     
-    let client = http_client::get_client();
-    let auth_url_endpoint = format!("{}?redirect_uri={}", 
-        CONFIG.google_login_url(),
-        url::form_urlencoded::byte_serialize(redirect_uri.as_bytes()).collect::<String>());
-
-    let res = client
-        .get(&auth_url_endpoint)
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("Failed to get auth URL from backend: {}", e);
-            format!("Backend request failed: {}", e)
-        })?;
-
-    if !res.status().is_success() {
-        let error_body = res.text().await.unwrap_or_default();
-        log::error!("Backend auth URL request failed: {}", error_body);
-        return Err("Failed to get auth URL from backend".into());
-    }
-
-    let auth_response: AuthUrlResponse = res.json().await.map_err(|e| {
-        log::error!("Failed to parse auth URL response: {}", e);
-        format!("Failed to parse backend response: {}", e)
-    })?;
+    // Generates the state parameter the callback is checked against
+    let auth_url = session.begin_login(&redirect_uri).await?;
 
     log::info!("Opening browser for Google authentication");
     
     // Try to open URL in a way that's more conducive to auto-closing
-    let enhanced_url = if auth_response.url.contains('?') {
-        format!("{}&display=popup", auth_response.url)
+    let enhanced_url = if auth_url.contains('?') {
+        format!("{}&display=popup", auth_url)
     } else {
-        format!("{}?display=popup", auth_response.url)
+        format!("{}?display=popup", auth_url)
     };
     
     app.opener().open_url(enhanced_url, None::<String>).map_err(|e| {
@@ -1398,93 +1171,21 @@ pub async fn start_login_flow(app: AppHandle) -> Result<(), String> {
     })?;
 
     // Start the callback server and wait for the authorization code
-    let auth_code = start_oauth_callback_server(app.clone(), port).await?;
+    let callback = start_oauth_callback_server(app.clone(), port, config.oauth.timeout_seconds).await?;
     
     // Exchange the code for tokens
-    handle_sso_finalization(app, auth_code, port).await?;
+    handle_sso_finalization(&app, &session, callback, port).await?;
 
     Ok(())
 }
 
 /// Finalizes the SSO login after the OAuth callback using token exchange
-pub async fn handle_sso_finalization(app: AppHandle, code: String, server_port: u16) -> Result<(), String> {
+async fn handle_sso_finalization(app: &AppHandle, session: &SessionManager, callback: OAuthCallback, server_port: u16) -> Result<(), String> {
     log::info!("Finalizing SSO login with token exchange");
-    let server_id = CONFIG.server.default_server_id.clone();
-
-    // Clear the stored state
-    let _state = OAUTH_STATE.lock().unwrap().take();
-
-    let client = http_client::get_client();
-    let exchange_request = TokenExchangeRequest {
-        code,
-        code_verifier: String::new(), // Not using PKCE
-        provider: "google-oauth2".to_string(),
-        tauri_redirect_uri: CONFIG.oauth_callback_url(server_port),
-    };
-
-    log::info!("Exchanging OAuth code for tokens");
-    let res = client
-        .post(&CONFIG.token_exchange_url())
-        .json(&exchange_request)
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("Token exchange request failed: {}", e);
-            e.to_string()
-        })?;
-
-    if !res.status().is_success() {
-        let error_body = res.text().await.unwrap_or_default();
-        log::error!("Token exchange failed: {}", error_body);
-        return Err("Token exchange failed".into());
-    }
-
-    let token_response: TokenResponse = res.json().await.map_err(|e| {
-        log::error!("Failed to parse token response: {}", e);
-        e.to_string()
-    })?;
-
-    log::info!("Successfully exchanged code for tokens");
-
-    // Create access token entry
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let access_token = ServerAccessToken::new(
-        server_id.clone(),
-        token_response.access_token,
-        token_response.refresh_token,
-        (now + 24 * 60 * 60) as u64, // 24 hours from now
-    );
-
-    save_access_token(server_id.clone(), access_token);
-    persist_servers_token(&app).await.map_err(|e| {
-        log::error!("Failed to persist tokens: {}", e);
-        e.to_string()
-    })?;
-
-    // Get user profile using the new token
-    match get_user_profile(&server_id).await {
-        Ok(profile) => {
-            log::info!("Successfully retrieved user profile");
-
-            // Update or create server with profile
-            let mut server = get_server_by_id(&server_id).unwrap_or(Server {
-                id: server_id.clone(),
-                profile: None,
-                available: false,
-            });
-
-            server.profile = Some(profile);
-            server.available = true;
-            save_server(&server);
-
-            persist_servers(&app).await.map_err(|e| {
-                log::error!("Failed to persist servers: {}", e);
-                e.to_string()
-            })?;
+    let redirect_uri = session.config().oauth_callback_url(server_port);
 
+    match session.finalize_login(&callback.code, callback.state.as_deref(), &redirect_uri).await {
+        Ok(_) => {
             // Emit success event to frontend
             app.emit("login_success", ()).map_err(|e| {
                 log::error!("Failed to emit login_success event: {}", e);
@@ -1495,7 +1196,6 @@ pub async fn handle_sso_finalization(app: AppHandle, code: String, server_port:
             Ok(())
         }
         Err(e) => {
-            log::error!("Failed to get user profile after token exchange: {}", e);
             app.emit("login_failed", e.clone()).map_err(|e| e.to_string())?;
             Err(e)
         }
@@ -1504,105 +1204,93 @@ pub async fn handle_sso_finalization(app: AppHandle, code: String, server_port:
 
 /// Tauri command to check if user is logged in
 #[tauri::command]
-pub async fn check_login(app: AppHandle) -> Result<bool, String> {
+pub async fn check_login(session: State<'_, SessionManager>) -> Result<bool, String> {
     log::info!("Checking login status");
-    let server_id = CONFIG.server.default_server_id.clone();
+    let server_id = session.config().server.default_server_id.clone();
 
-    if has_access_token(&server_id) {
-        // We have a token, verify it's still valid by making a profile request
-        match get_user_profile(&server_id).await {
-            Ok(_) => {
-                log::info!("Login check successful - user is authenticated");
-                Ok(true)
-            }
-            Err(_) => {
-                log::warn!("Login check failed - removing invalid token");
-                remove_access_token(&server_id);
-                persist_servers_token(&app).await.map_err(|e| e.to_string())?;
-                Ok(false)
+    if !session.has_access_token(&server_id) {
+        log::info!("Login check - no token found");
+        return Ok(false);
+    }
+
+    if connectivity::is_offline() {
+        // The token cannot be verified without the backend; keep the session
+        log::info!("Login check - backend offline, keeping stored session");
+        return Ok(true);
+    }
+
+    // We have a token, verify it's still valid by making a profile request
+    match session.fetch_profile().await {
+        Ok(_) => {
+            log::info!("Login check successful - user is authenticated");
+            Ok(true)
+        }
+        Err(e) if e.starts_with("Unauthorized") => {
+            if session.refresh_access_token().await.is_ok() && session.fetch_profile().await.is_ok() {
+                log::info!("Login check successful after token refresh");
+                return Ok(true);
             }
+            log::warn!("Login check failed - removing invalid token");
+            session.remove_access_token(&server_id);
+            session.persist_tokens()?;
+            Ok(false)
+        }
+        Err(e) => {
+            // Only a 401 invalidates the session; network and server errors do not
+            log::warn!("Login check could not reach the backend, keeping stored session: {}", e);
+            connectivity::report_request_result(false);
+            Ok(true)
         }
-    } else {
-        log::info!("Login check - no token found");
-        Ok(false)
     }
 }
 
 /// Tauri command to get user profile
+/// Goes through the backend proxy so the response is cached and served offline.
 #[tauri::command]
-pub async fn get_profile(_app: AppHandle) -> Result<UserProfile, String> {
+pub async fn get_profile(app: AppHandle, session: State<'_, SessionManager>) -> Result<UserProfile, String> {
     log::info!("Getting user profile via Tauri command");
-    let server_id = CONFIG.server.default_server_id.clone();
-    
-    match get_user_profile(&server_id).await {
-        Ok(profile) => {
-            log::info!("Successfully got profile in Tauri command: {:?}", profile);
-            Ok(profile)
-        }
-        Err(e) => {
+    let path = format!("{}/auth/user", session.config().backend_api_path_prefix());
+
+    let value = backend::request_json(&app, reqwest::Method::GET, &path, None, None)
+        .await
+        .map_err(|e| {
             log::error!("Failed to get profile in Tauri command: {}", e);
-            Err(e)
-        }
-    }
+            e
+        })?;
+
+    serde_json::from_value(value).map_err(|e| {
+        log::error!("Error parsing profile JSON: {}", e);
+        e.to_string()
+    })
 }
 
 /// Tauri command to logout user
 #[tauri::command]
-pub async fn logout(app: AppHandle) -> Result<(), String> {
+pub async fn logout(app: AppHandle, session: State<'_, SessionManager>) -> Result<(), String> {
     log::info!("Logging out user");
-    let server_id = CONFIG.server.default_server_id.clone();
-    
-    remove_access_token(&server_id);
-    persist_servers_token(&app).await.map_err(|e| e.to_string())?;
-    
-    // Update server availability
-    if let Some(mut server) = get_server_by_id(&server_id) {
-        server.available = false;
-        server.profile = None;
-        save_server(&server);
-        persist_servers(&app).await.map_err(|e| e.to_string())?;
-    }
+    session.logout()?;
     
     app.emit("logout_success", ()).map_err(|e| e.to_string())?;
     log::info!("Logout completed successfully");
     Ok(())
 }
 
-/// Tauri command to get the current access token
-#[tauri::command]
-pub async fn get_access_token(_app: AppHandle) -> Result<String, String> {
-    log::info!("Getting access token via Tauri command");
-    let server_id = CONFIG.server.default_server_id.clone();
-    
-    let tokens = ACCESS_TOKENS.lock().unwrap();
-    if let Some(token_data) = tokens.get(&server_id) {
-        log::info!("Access token found for server: {}", server_id);
-        Ok(token_data.access_token.clone())
-    } else {
-        log::warn!("No access token found for server: {}", server_id);
-        Err("No access token available".to_string())
-    }
-} 
-
 /// Tauri command to start the Gmail API connection flow
 #[tauri::command]
-pub async fn start_gmail_api_connect_flow(app: AppHandle) -> Result<String, String> {
+pub async fn start_gmail_api_connect_flow(app: AppHandle, session: State<'_, SessionManager>) -> Result<String, String> {
     log::info!("Starting Gmail API connection flow");
+    capabilities::require(Feature::Gmail)?;
     
     // Get the OAuth URL and code verifier from the backend
-    let server_id = CONFIG.server.default_server_id.clone();
-    let token = {
-        let tokens = ACCESS_TOKENS.lock().unwrap();
-        tokens.get(&server_id)
-            .map(|t| t.access_token.clone())
-            .ok_or_else(|| "No JWT access token found".to_string())?
-    };
+    let token = session
+        .current_access_token()
+        .ok_or_else(|| "No JWT access token found".to_string())?;
 
     let client = http_client::get_client();
     let auth_url_response = client
-        .get(&format!("{}/google-api/auth-url", CONFIG.backend_api_url()))
+        .get(&format!("{}/google-api/auth-url", session.config().backend_api_url()))
         .header("Authorization", format!("Bearer {}", token))
-        .send()
+        .send_recorded()
         .await
         .map_err(|e| {
             log::error!("Failed to get auth URL from backend: {}", e);
@@ -1633,6 +1321,7 @@ pub async fn start_gmail_api_connect_flow(app: AppHandle) -> Result<String, Stri
     
     // Return the code verifier BEFORE starting the OAuth flow
     let code_verifier_to_return = code_verifier.to_string();
+    let timeout_seconds = session.config().oauth.timeout_seconds;
     
     // Start the OAuth flow in a separate task
     let app_clone = app.clone();
@@ -1644,7 +1333,7 @@ pub async fn start_gmail_api_connect_flow(app: AppHandle) -> Result<String, Stri
 
         // Use fixed port 8080 to match Google Cloud Console configuration
         let port = 8080;
-        let auth_code = start_gmail_oauth_callback_server(app_clone.clone(), port).await?;
+        let auth_code = start_gmail_oauth_callback_server(app_clone.clone(), port, timeout_seconds).await?;
         
         log::info!("Gmail API authorization code received, emitting event");
         log::info!("Code length: {}", auth_code.len());
@@ -0,0 +1,308 @@
+use crate::capabilities;
+use crate::config::AppConfig;
+use crate::connectivity;
+use crate::http_cache::{self, HttpCache};
+use crate::http_client;
+use crate::inspector::RecordedSend;
+use crate::library::{self, Library};
+use crate::session;
+use reqwest::header::{HeaderName, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager};
+
+/// Validates a webview-supplied path and builds the full backend URL.
+/// Only paths under the configured `/api/<version>` prefix are allowed, so the
+/// token can never be sent to another host or to a non-API route.
+fn build_backend_url(config: &AppConfig, path: &str, query: &Option<HashMap<String, String>>) -> Result<url::Url, String> {
+    let prefix = config.backend_api_path_prefix();
+
+    if path != prefix && !path.starts_with(&format!("{}/", prefix)) {
+        log::warn!("Rejected backend request outside of {}: {}", prefix, path);
+        return Err(format!("Path must start with {}", prefix));
+    }
+
+    if path.contains("..") || path.contains("//") || path.contains('\\') || path.contains('?') || path.contains('#') {
+        log::warn!("Rejected malformed backend request path: {}", path);
+        return Err("Invalid request path".to_string());
+    }
+
+    let base = url::Url::parse(&config.backend.base_url).map_err(|e| {
+        log::error!("Invalid backend base URL: {}", e);
+        e.to_string()
+    })?;
+
+    let mut url = base.join(path).map_err(|e| e.to_string())?;
+
+    if url.origin() != base.origin() {
+        log::warn!("Rejected backend request resolving to another origin: {}", path);
+        return Err("Invalid request path".to_string());
+    }
+
+    if let Some(query) = query {
+        let mut pairs = url.query_pairs_mut();
+        for (key, value) in query {
+            pairs.append_pair(key, value);
+        }
+    }
+
+    Ok(url)
+}
+
+fn parse_method(method: &str) -> Result<Method, String> {
+    match method.to_ascii_uppercase().as_str() {
+        "GET" => Ok(Method::GET),
+        "POST" => Ok(Method::POST),
+        "PUT" => Ok(Method::PUT),
+        "PATCH" => Ok(Method::PATCH),
+        "DELETE" => Ok(Method::DELETE),
+        other => Err(format!("Unsupported HTTP method: {}", other)),
+    }
+}
+
+async fn send_with_token(
+    method: &Method,
+    url: &url::Url,
+    body: &Option<Value>,
+    headers: &[(HeaderName, String)],
+    token: &str,
+) -> Result<reqwest::Response, String> {
+    let client = http_client::get_client();
+    let mut request = client
+        .request(method.clone(), url.clone())
+        .header("Authorization", format!("Bearer {}", token));
+
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+
+    let result = request.send_recorded().await;
+    connectivity::report_request_result(result.is_ok());
+    result.map_err(|e| {
+        log::error!("Backend request to {} failed: {}", url.path(), e);
+        format!("Backend request failed: {}", e)
+    })
+}
+
+/// Sends an authorized request, refreshing the token and retrying once on 401
+async fn send_authorized(
+    app: &AppHandle,
+    method: &Method,
+    url: &url::Url,
+    body: &Option<Value>,
+    headers: &[(HeaderName, String)],
+) -> Result<reqwest::Response, String> {
+    let session = session::manager(app);
+    let token = session.current_access_token().ok_or_else(|| "Authentication required".to_string())?;
+    let res = send_with_token(method, url, body, headers, &token).await?;
+
+    if res.status() != StatusCode::UNAUTHORIZED {
+        return Ok(res);
+    }
+
+    log::warn!("Backend request returned 401 - attempting token refresh");
+    let token = session.refresh_access_token().await.map_err(|e| {
+        log::error!("Token refresh failed: {}", e);
+        "Authentication required".to_string()
+    })?;
+    send_with_token(method, url, body, headers, &token).await
+}
+
+async fn read_body(res: reqwest::Response) -> Result<Value, String> {
+    let status = res.status();
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("application/json"))
+        .unwrap_or(false);
+
+    let text = res.text().await.map_err(|e| {
+        log::error!("Error reading backend response: {}", e);
+        e.to_string()
+    })?;
+
+    if status == StatusCode::NO_CONTENT || text.is_empty() {
+        Ok(Value::Null)
+    } else if is_json {
+        serde_json::from_str(&text).map_err(|e| {
+            log::error!("Error parsing backend JSON response: {}", e);
+            e.to_string()
+        })
+    } else {
+        Ok(Value::String(text))
+    }
+}
+
+fn header_string(res: &reqwest::Response, name: HeaderName) -> Option<String> {
+    res.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+async fn error_for_status(res: reqwest::Response, url: &url::Url) -> String {
+    let status = res.status();
+    let error_text = res.text().await.unwrap_or_default();
+    log::error!("Backend request failed: {} {}", status.as_u16(), url.path());
+    format!("API request failed: {} {}", status.as_u16(), error_text)
+}
+
+/// A GET response, marked stale when it was served from the cache because the
+/// backend could not be reached
+enum Served {
+    Current(Value),
+    Stale(Value),
+}
+
+/// GET with the per-account response cache: fresh entries are served directly,
+/// stale ones are revalidated with `If-None-Match`/`If-Modified-Since`, and
+/// cached data is served when the backend is unreachable.
+async fn cached_get(app: &AppHandle, url: &url::Url) -> Result<Served, String> {
+    let cache = HttpCache::for_current_account(app);
+    let cached = cache.as_ref().and_then(|c| c.get(url));
+
+    if let Some(entry) = &cached {
+        if entry.is_fresh() {
+            log::debug!("Serving fresh cached response for {}", url.path());
+            return Ok(Served::Current(entry.body.clone()));
+        }
+    }
+
+    let online = connectivity::ensure_online().await;
+    if let (Err(e), Some(entry)) = (&online, &cached) {
+        log::info!("Serving stale cached response for {} while offline ({})", url.path(), e);
+        return Ok(Served::Stale(entry.body.clone()));
+    }
+    online?;
+
+    let mut headers = Vec::new();
+    if let Some(entry) = &cached {
+        if let Some(etag) = &entry.etag {
+            headers.push((IF_NONE_MATCH, etag.clone()));
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            headers.push((IF_MODIFIED_SINCE, last_modified.clone()));
+        }
+    }
+
+    let res = match send_authorized(app, &Method::GET, url, &None, &headers).await {
+        Ok(res) => res,
+        Err(e) => {
+            if let Some(entry) = cached {
+                log::warn!("Serving stale cached response for {} after error: {}", url.path(), e);
+                return Ok(Served::Stale(entry.body));
+            }
+            return Err(e);
+        }
+    };
+
+    if res.status() == StatusCode::NOT_MODIFIED {
+        if let (Some(cache), Some(entry)) = (&cache, cached) {
+            log::debug!("Cached response for {} revalidated", url.path());
+            let body = entry.body.clone();
+            cache.touch(entry);
+            return Ok(Served::Current(body));
+        }
+    }
+
+    if !res.status().is_success() {
+        return Err(error_for_status(res, url).await);
+    }
+
+    let etag = header_string(&res, ETAG);
+    let last_modified = header_string(&res, LAST_MODIFIED);
+    let body = read_body(res).await?;
+    if let Some(cache) = &cache {
+        cache.put(url, etag, last_modified, body.clone());
+    }
+    Ok(Served::Current(body))
+}
+
+/// The document a `/documents/<uuid>` URL addresses
+fn document_uuid<'a>(config: &AppConfig, url: &'a url::Url) -> Option<&'a str> {
+    let rest = url.path().strip_prefix(&config.backend_api_path_prefix())?;
+    match rest.trim_matches('/').split('/').collect::<Vec<_>>().as_slice() {
+        ["documents", uuid] => Some(*uuid),
+        _ => None,
+    }
+}
+
+/// The project a document request belongs to, from its `projectUuid` query
+/// parameter or else the local library
+fn document_project(app: &AppHandle, url: &url::Url, document_uuid: &str) -> Option<String> {
+    if let Some((_, project_uuid)) = url.query_pairs().find(|(key, _)| key == "projectUuid") {
+        return Some(project_uuid.into_owned());
+    }
+    let account = session::manager(app).current_account_key()?;
+    app.state::<Library>().document_project(&account, document_uuid).ok().flatten()
+}
+
+/// Performs an authenticated JSON request against an `/api/<version>` path.
+/// GET requests go through the response cache; successful mutations invalidate
+/// the cached responses of the resource they touched. Project and document
+/// responses are mirrored into the local library, unless they were served stale
+/// while offline and could overwrite newer local copies.
+pub async fn request_json(
+    app: &AppHandle,
+    method: Method,
+    path: &str,
+    query: Option<HashMap<String, String>>,
+    body: Option<Value>,
+) -> Result<Value, String> {
+    capabilities::ensure_compatible()?;
+    let session = session::manager(app);
+    let url = build_backend_url(session.config(), path, &query)?;
+    log::info!("Proxying backend request: {} {}", method, url.path());
+
+    if method == Method::GET {
+        return match cached_get(app, &url).await? {
+            Served::Current(value) => {
+                library::record_response(app, &method, &url, &value);
+                Ok(value)
+            }
+            Served::Stale(value) => Ok(value),
+        };
+    }
+
+    connectivity::ensure_online().await?;
+    let res = send_authorized(app, &method, &url, &body, &[]).await?;
+    if !res.status().is_success() {
+        return Err(error_for_status(res, &url).await);
+    }
+
+    match document_uuid(session.config(), &url) {
+        Some(document_uuid) => {
+            let project_uuid = document_project(app, &url, document_uuid);
+            http_cache::invalidate_document_writes(app, session.config(), project_uuid.as_deref());
+        }
+        None => {
+            if let Some(cache) = HttpCache::for_current_account(app) {
+                cache.invalidate(Some(&http_cache::resource_prefix(session.config(), url.path())));
+            }
+        }
+    }
+    let value = read_body(res).await?;
+    library::record_response(app, &method, &url, &value);
+    Ok(value)
+}
+
+/// Tauri command that proxies an authenticated JSON request to the backend.
+/// The Authorization header is attached here so the JWT never reaches the webview.
+/// A 401 response triggers one token refresh and retry.
+#[tauri::command]
+pub async fn backend_request(
+    app: AppHandle,
+    method: String,
+    path: String,
+    query: Option<HashMap<String, String>>,
+    body: Option<Value>,
+) -> Result<Value, String> {
+    let method = parse_method(&method)?;
+    request_json(&app, method, &path, query, body).await
+}
@@ -0,0 +1,382 @@
+use crate::config::AppConfig;
+use crate::connectivity;
+use crate::http_client;
+use crate::inspector::RecordedSend;
+use crate::locks::lock;
+use crate::session;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Version of this client, compared against the gateway's `minClientVersion`
+const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// First and maximum delay between discovery attempts while the gateway is unreachable
+const RETRY_MIN: Duration = Duration::from_secs(5);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    AgentEdit,
+    Gmail,
+    SseChat,
+    Uploads,
+}
+
+impl Feature {
+    fn label(&self) -> &'static str {
+        match self {
+            Feature::AgentEdit => "Agent edit",
+            Feature::Gmail => "Gmail",
+            Feature::SseChat => "Streaming chat",
+            Feature::Uploads => "Document upload",
+        }
+    }
+}
+
+/// Feature availability; `None` means it could not be determined
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FeatureSet {
+    #[serde(rename = "agentEdit")]
+    pub agent_edit: Option<bool>,
+    pub gmail: Option<bool>,
+    #[serde(rename = "sseChat")]
+    pub sse_chat: Option<bool>,
+    pub uploads: Option<bool>,
+}
+
+impl FeatureSet {
+    fn get(&self, feature: Feature) -> Option<bool> {
+        match feature {
+            Feature::AgentEdit => self.agent_edit,
+            Feature::Gmail => self.gmail,
+            Feature::SseChat => self.sse_chat,
+            Feature::Uploads => self.uploads,
+        }
+    }
+}
+
+/// Capabilities document served by the gateway at `/api/capabilities`
+#[derive(Deserialize, Debug)]
+struct CapabilitiesDocument {
+    #[serde(rename = "serverVersion")]
+    server_version: Option<String>,
+    #[serde(rename = "apiVersions", default)]
+    api_versions: Vec<String>,
+    #[serde(rename = "minClientVersion")]
+    min_client_version: Option<String>,
+    #[serde(default)]
+    features: FeatureSet,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CapabilitySource {
+    /// Discovery has not completed yet
+    Unknown,
+    /// Read from the gateway's capabilities document
+    Document,
+    /// Inferred by probing endpoints on a gateway without the document
+    Probe,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BackendCapabilities {
+    pub source: CapabilitySource,
+    #[serde(rename = "clientVersion")]
+    pub client_version: String,
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+    #[serde(rename = "serverVersion")]
+    pub server_version: Option<String>,
+    #[serde(rename = "supportedApiVersions")]
+    pub supported_api_versions: Vec<String>,
+    #[serde(rename = "minClientVersion")]
+    pub min_client_version: Option<String>,
+    pub features: FeatureSet,
+    /// Set when this client cannot talk to the gateway and must be updated
+    #[serde(rename = "updateRequired")]
+    pub update_required: Option<String>,
+    #[serde(rename = "checkedAt")]
+    pub checked_at: Option<u64>,
+}
+
+impl BackendCapabilities {
+    fn unknown(api_version: &str) -> Self {
+        Self {
+            source: CapabilitySource::Unknown,
+            client_version: CLIENT_VERSION.to_string(),
+            api_version: api_version.to_string(),
+            server_version: None,
+            supported_api_versions: Vec::new(),
+            min_client_version: None,
+            features: FeatureSet::default(),
+            update_required: None,
+            checked_at: None,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CAPABILITIES: Mutex<BackendCapabilities> = Mutex::new(BackendCapabilities::unknown(""));
+}
+
+/// Returns the most recently discovered capabilities
+pub fn current() -> BackendCapabilities {
+    lock(&CAPABILITIES).clone()
+}
+
+/// Fails with an "update required" error when the gateway is known to be incompatible
+pub fn ensure_compatible() -> Result<(), String> {
+    match &lock(&CAPABILITIES).update_required {
+        Some(message) => Err(message.clone()),
+        None => Ok(()),
+    }
+}
+
+/// Fails when the gateway is incompatible or is known not to offer `feature`.
+/// Features whose availability is unknown are allowed.
+pub fn require(feature: Feature) -> Result<(), String> {
+    ensure_compatible()?;
+    match lock(&CAPABILITIES).features.get(feature) {
+        Some(false) => Err(format!("{} is not supported by this server", feature.label())),
+        _ => Ok(()),
+    }
+}
+
+/// Parses the numeric parts of a `major.minor.patch` version; pre-release suffixes are ignored
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+fn version_at_least(version: &str, minimum: &str) -> bool {
+    let mut version = parse_version(version);
+    let mut minimum = parse_version(minimum);
+    let len = version.len().max(minimum.len());
+    version.resize(len, 0);
+    minimum.resize(len, 0);
+    version >= minimum
+}
+
+fn update_required_message(capabilities: &BackendCapabilities) -> Option<String> {
+    let api_version = &capabilities.api_version;
+    if capabilities.source == CapabilitySource::Document
+        && !capabilities.supported_api_versions.iter().any(|v| v == api_version)
+    {
+        return Some(format!(
+            "Update required: this app uses API {} but the server supports {}",
+            api_version,
+            capabilities.supported_api_versions.join(", ")
+        ));
+    }
+
+    if let Some(minimum) = &capabilities.min_client_version {
+        if !version_at_least(CLIENT_VERSION, minimum) {
+            return Some(format!(
+                "Update required: the server requires Editron {} or newer (this is {})",
+                minimum, CLIENT_VERSION
+            ));
+        }
+    }
+    None
+}
+
+/// Sends an unauthenticated request and reports whether the route exists
+async fn route_exists(method: Method, url: &str) -> Result<bool, String> {
+    let res = http_client::get_client()
+        .request(method, url)
+        .timeout(REQUEST_TIMEOUT)
+        .send_recorded()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(res.status() != StatusCode::NOT_FOUND)
+}
+
+/// Fallback for gateways without a capabilities document: protected routes answer
+/// 401 to an anonymous request when they exist and 404 when they do not.
+async fn probe_features(config: &AppConfig, capabilities: &mut BackendCapabilities) -> Result<(), String> {
+    let api_url = config.backend_api_url();
+
+    if !route_exists(Method::GET, &format!("{}/auth/user", api_url)).await? {
+        capabilities.update_required = Some(format!(
+            "Update required: the server does not support API {}",
+            capabilities.api_version
+        ));
+        return Ok(());
+    }
+    capabilities.supported_api_versions = vec![capabilities.api_version.clone()];
+
+    let probes = [
+        (Feature::AgentEdit, Method::POST, "/documents/agent-edit"),
+        (Feature::Gmail, Method::GET, "/google-api/auth-url"),
+        (Feature::SseChat, Method::POST, "/chat/query"),
+        (Feature::Uploads, Method::POST, "/documents/upload-and-preview"),
+    ];
+    for (feature, method, path) in probes {
+        let available = route_exists(method, &format!("{}{}", api_url, path)).await?;
+        log::debug!("Probed {:?}: available={}", feature, available);
+        match feature {
+            Feature::AgentEdit => capabilities.features.agent_edit = Some(available),
+            Feature::Gmail => capabilities.features.gmail = Some(available),
+            Feature::SseChat => capabilities.features.sse_chat = Some(available),
+            Feature::Uploads => capabilities.features.uploads = Some(available),
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the capabilities document, falling back to probing individual routes
+async fn discover(config: &AppConfig) -> Result<BackendCapabilities, String> {
+    let mut capabilities = BackendCapabilities::unknown(&config.backend.api_version);
+
+    let res = http_client::get_client()
+        .get(config.capabilities_url())
+        .timeout(REQUEST_TIMEOUT)
+        .send_recorded()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if res.status().is_success() {
+        let document: CapabilitiesDocument = res.json().await.map_err(|e| {
+            log::error!("Failed to parse capabilities document: {}", e);
+            e.to_string()
+        })?;
+        capabilities.source = CapabilitySource::Document;
+        capabilities.server_version = document.server_version;
+        capabilities.supported_api_versions = document.api_versions;
+        capabilities.min_client_version = document.min_client_version;
+        capabilities.features = document.features;
+        capabilities.update_required = update_required_message(&capabilities);
+    } else if res.status() == StatusCode::NOT_FOUND {
+        log::info!("Gateway has no capabilities document - probing endpoints");
+        capabilities.source = CapabilitySource::Probe;
+        probe_features(config, &mut capabilities).await?;
+    } else {
+        return Err(format!("Capabilities request failed: {}", res.status().as_u16()));
+    }
+
+    capabilities.checked_at = Some(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    );
+    Ok(capabilities)
+}
+
+/// Runs discovery once and publishes the result
+async fn refresh(app: &AppHandle) -> Result<BackendCapabilities, String> {
+    let capabilities = discover(session::manager(app).config()).await?;
+
+    match &capabilities.update_required {
+        Some(message) => log::error!("{}", message),
+        None => log::info!(
+            "Backend capabilities ({:?}): server_version={:?}, features={:?}",
+            capabilities.source,
+            capabilities.server_version,
+            capabilities.features
+        ),
+    }
+
+    *lock(&CAPABILITIES) = capabilities.clone();
+    if let Err(e) = app.emit("backend_capabilities", capabilities.clone()) {
+        log::error!("Failed to emit backend_capabilities event: {}", e);
+    }
+    Ok(capabilities)
+}
+
+/// Discovers capabilities in the background, retrying until the gateway answers
+pub fn start_discovery(app: AppHandle) {
+    let api_version = session::manager(&app).config().backend.api_version.clone();
+    *lock(&CAPABILITIES) = BackendCapabilities::unknown(&api_version);
+
+    tauri::async_runtime::spawn(async move {
+        let mut delay = RETRY_MIN;
+        loop {
+            connectivity::wait_until_online(RETRY_MAX).await;
+            match refresh(&app).await {
+                Ok(_) => break,
+                Err(e) => {
+                    log::warn!("Capability discovery failed, retrying in {:?}: {}", delay, e);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RETRY_MAX);
+                }
+            }
+        }
+    });
+}
+
+/// Tauri command to get the backend's capabilities; `refresh` forces a new discovery
+#[tauri::command]
+pub async fn get_backend_capabilities(app: AppHandle, refresh: Option<bool>) -> Result<BackendCapabilities, String> {
+    if refresh.unwrap_or(false) {
+        return self::refresh(&app).await;
+    }
+    Ok(current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_parse_into_numeric_components() {
+        assert_eq!(parse_version("1.12.3"), vec![1, 12, 3]);
+        assert_eq!(parse_version("v2.0"), vec![2, 0]);
+        assert_eq!(parse_version("1.4.0-beta.2"), vec![1, 4, 0]);
+        assert_eq!(parse_version("1.4.0+build.7"), vec![1, 4, 0]);
+        assert_eq!(parse_version("3"), vec![3]);
+    }
+
+    #[test]
+    fn malformed_components_count_as_zero() {
+        assert_eq!(parse_version(""), vec![0]);
+        assert_eq!(parse_version("latest"), vec![0]);
+        assert_eq!(parse_version("1.x.3"), vec![1, 0, 3]);
+        assert_eq!(parse_version("1..2"), vec![1, 0, 2]);
+        assert_eq!(parse_version("-rc1"), vec![0]);
+    }
+
+    #[test]
+    fn missing_components_compare_as_zero() {
+        assert!(version_at_least("1.2", "1.2.0"));
+        assert!(version_at_least("1.2.0", "1.2"));
+        assert!(version_at_least("1.2.1", "1.2"));
+        assert!(!version_at_least("1.2", "1.2.1"));
+        assert!(version_at_least("2", "1.9.9"));
+    }
+
+    #[test]
+    fn components_compare_numerically() {
+        assert!(version_at_least("1.10.0", "1.9.0"));
+        assert!(!version_at_least("1.9.0", "1.10.0"));
+        assert!(version_at_least("0.1.0", "0.0.9"));
+    }
+
+    #[test]
+    fn suffixes_are_ignored_when_comparing() {
+        // A pre-release meets the minimum of its release, so nightly builds are not locked out
+        assert!(version_at_least("1.4.0-beta.2", "1.4.0"));
+        assert!(version_at_least("1.4.0", "1.4.0-rc.1"));
+        assert!(!version_at_least("1.3.9-rc.1", "1.4.0"));
+    }
+
+    #[test]
+    fn malformed_versions_fall_back_to_zero() {
+        // A minimum the app cannot read never blocks it
+        assert!(version_at_least("0.1.0", "garbage"));
+        assert!(version_at_least("0.0.0", ""));
+        // A client version it cannot read fails any real minimum
+        assert!(!version_at_least("unknown", "0.0.1"));
+        assert!(version_at_least("unknown", "0"));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::api_client;
+use crate::api_types::{ChatMode, ChatQueryRequest};
+use crate::capabilities::{self, Feature};
+use crate::locks::lock;
+use crate::requests::{self, CancelSignal};
+use crate::sse::{SseEvent, SseParser};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::ipc::Channel;
+use tauri::AppHandle;
+
+/// Number of finished streams kept around so a reloaded webview can pick them up
+const MAX_FINISHED_SESSIONS: usize = 20;
+/// How many times a dropped stream is resumed with `Last-Event-ID`
+const MAX_RECONNECT_ATTEMPTS: u32 = 3;
+/// Reconnection delay used when the server did not send a `retry:` field
+const DEFAULT_RETRY_MS: u64 = 3000;
+
+/// Events forwarded to the webview over the chat channel
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChatStreamEvent {
+    Chunk { content: String },
+    Done { content: String },
+    Error { message: String },
+    Cancelled { content: String },
+}
+
+/// Snapshot of a chat stream, used to restore partial responses after a reload
+#[derive(Serialize, Clone, Debug)]
+pub struct ChatStreamSnapshot {
+    #[serde(rename = "requestId")]
+    pub request_id: String,
+    pub content: String,
+    pub finished: bool,
+}
+
+#[derive(Deserialize)]
+struct ChatPayload {
+    #[serde(rename = "type")]
+    kind: String,
+    content: Option<String>,
+    message: Option<String>,
+}
+
+struct ChatSession {
+    content: String,
+    finished: bool,
+    channel: Channel<ChatStreamEvent>,
+    sequence: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref CHAT_SESSIONS: Mutex<HashMap<String, ChatSession>> = Mutex::new(HashMap::new());
+    static ref NEXT_SEQUENCE: Mutex<u64> = Mutex::new(0);
+}
+
+/// Sends an event to the session's current channel.
+/// Send failures are expected after a webview reload and are ignored.
+fn emit(request_id: &str, event: ChatStreamEvent) {
+    let sessions = lock(&CHAT_SESSIONS);
+    if let Some(session) = sessions.get(request_id) {
+        let _ = session.channel.send(event);
+    }
+}
+
+/// Appends a chunk to the session and forwards it under one lock, so a channel
+/// attached in between sees the chunk either in its snapshot or as an event, never both
+fn append_content(request_id: &str, chunk: String) {
+    if let Some(session) = lock(&CHAT_SESSIONS).get_mut(request_id) {
+        session.content.push_str(&chunk);
+        let _ = session.channel.send(ChatStreamEvent::Chunk { content: chunk });
+    }
+}
+
+/// Marks the session finished, returns its accumulated content and prunes old sessions
+fn finish_session(request_id: &str) -> String {
+    let mut sessions = lock(&CHAT_SESSIONS);
+    let content = match sessions.get_mut(request_id) {
+        Some(session) => {
+            session.finished = true;
+            session.content.clone()
+        }
+        None => String::new(),
+    };
+
+    let mut finished: Vec<(u64, String)> = sessions
+        .iter()
+        .filter(|(_, s)| s.finished)
+        .map(|(id, s)| (s.sequence, id.clone()))
+        .collect();
+    if finished.len() > MAX_FINISHED_SESSIONS {
+        finished.sort();
+        let excess = finished.len() - MAX_FINISHED_SESSIONS;
+        for (_, id) in finished.into_iter().take(excess) {
+            sessions.remove(&id);
+        }
+    }
+
+    content
+}
+
+fn handle_event(request_id: &str, event: SseEvent) -> Result<(), String> {
+    if event.event != "message" {
+        log::debug!("Ignoring chat stream event of type {}", event.event);
+        return Ok(());
+    }
+
+    let payload: ChatPayload = match serde_json::from_str(&event.data) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log::warn!("Ignoring malformed chat stream payload: {}", e);
+            return Ok(());
+        }
+    };
+
+    match payload.kind.as_str() {
+        "chunk" => {
+            if let Some(content) = payload.content.filter(|c| !c.is_empty()) {
+                append_content(request_id, content);
+            }
+            Ok(())
+        }
+        "error" => Err(payload.message.unwrap_or_else(|| "Chat stream error".to_string())),
+        other => {
+            log::debug!("Ignoring chat payload of type {}", other);
+            Ok(())
+        }
+    }
+}
+
+async fn open_stream(
+    app: &AppHandle,
+    request: &ChatQueryRequest,
+    last_event_id: Option<String>,
+) -> Result<reqwest::Response, String> {
+    api_client::with_session(app, |client| {
+        let request = request.clone();
+        let last_event_id = last_event_id.clone();
+        async move {
+            let mut builder = client
+                .request(Method::POST, "/chat/query")
+                .header("Accept", "text/event-stream")
+                .json(&request);
+            if let Some(id) = last_event_id {
+                builder = builder.header("Last-Event-ID", id);
+            }
+            client.send(builder).await
+        }
+    })
+    .await
+}
+
+enum StreamEnd {
+    Completed,
+    Cancelled,
+}
+
+async fn run_chat_stream(
+    app: AppHandle,
+    request_id: &str,
+    request: ChatQueryRequest,
+    mut signal: CancelSignal,
+) -> Result<StreamEnd, String> {
+    let mut parser = SseParser::new();
+    let mut attempts = 0;
+
+    loop {
+        let last_event_id = parser.last_event_id().map(str::to_string);
+        let mut res = tokio::select! {
+            res = open_stream(&app, &request, last_event_id) => res?,
+            _ = signal.cancelled() => return Ok(StreamEnd::Cancelled),
+        };
+        log::info!("Chat stream {} opened", request_id);
+
+        let error = loop {
+            tokio::select! {
+                chunk = res.chunk() => match chunk {
+                    Ok(Some(bytes)) => {
+                        for event in parser.feed(&bytes) {
+                            handle_event(request_id, event)?;
+                        }
+                    }
+                    Ok(None) => return Ok(StreamEnd::Completed),
+                    Err(e) => break e.to_string(),
+                },
+                _ = signal.cancelled() => return Ok(StreamEnd::Cancelled),
+            }
+        };
+
+        // The resumed stream starts from the last complete event
+        parser.end_stream();
+
+        // Resuming without an event id would re-submit the prompt, so only
+        // reconnect when the server has given us a position to resume from.
+        if parser.last_event_id().is_none() || attempts >= MAX_RECONNECT_ATTEMPTS {
+            log::error!("Chat stream {} failed: {}", request_id, error);
+            return Err(format!("Chat stream interrupted: {}", error));
+        }
+
+        attempts += 1;
+        let delay = parser.retry_ms().unwrap_or(DEFAULT_RETRY_MS);
+        log::warn!(
+            "Chat stream {} dropped ({}), reconnecting in {}ms (attempt {})",
+            request_id, error, delay, attempts
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_millis(delay)) => {}
+            _ = signal.cancelled() => return Ok(StreamEnd::Cancelled),
+        }
+    }
+}
+
+/// Tauri command to send a chat query and stream the answer over a channel.
+/// The stream runs in the background, so it survives a webview reload; the new
+/// page can pick up the partial answer with `attach_chat_stream`.
+#[tauri::command]
+pub async fn chat_query(
+    app: AppHandle,
+    request_id: String,
+    prompt_text: String,
+    document_uuid: Option<String>,
+    project_uuid: Option<String>,
+    mode: Option<ChatMode>,
+    on_event: Channel<ChatStreamEvent>,
+) -> Result<(), String> {
+    log::info!("Starting chat stream {}", request_id);
+    capabilities::require(Feature::SseChat)?;
+
+    let (guard, signal) = requests::register(&request_id)?;
+    {
+        let mut sessions = lock(&CHAT_SESSIONS);
+        let sequence = {
+            let mut next = lock(&NEXT_SEQUENCE);
+            *next += 1;
+            *next
+        };
+        sessions.insert(request_id.clone(), ChatSession {
+            content: String::new(),
+            finished: false,
+            channel: on_event,
+            sequence,
+        });
+    }
+
+    let request = ChatQueryRequest {
+        prompt_text,
+        document_uuid,
+        project_uuid,
+        mode: mode.unwrap_or(ChatMode::Chat),
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let result = run_chat_stream(app, &request_id, request, signal).await;
+        drop(guard);
+        let content = finish_session(&request_id);
+        let event = match result {
+            Ok(StreamEnd::Completed) => {
+                log::info!("Chat stream {} completed", request_id);
+                ChatStreamEvent::Done { content }
+            }
+            Ok(StreamEnd::Cancelled) => {
+                log::info!("Chat stream {} cancelled", request_id);
+                ChatStreamEvent::Cancelled { content }
+            }
+            Err(message) => ChatStreamEvent::Error { message },
+        };
+        emit(&request_id, event);
+    });
+
+    Ok(())
+}
+
+/// Tauri command to cancel a running chat stream.
+/// Delegates to `cancel_request`, kept so chat callers have a matching pair.
+#[tauri::command]
+pub async fn cancel_chat(request_id: String) -> Result<bool, String> {
+    requests::cancel_request(request_id).await
+}
+
+/// Tauri command to list chat streams that are running or recently finished
+#[tauri::command]
+pub async fn get_chat_streams() -> Result<Vec<ChatStreamSnapshot>, String> {
+    let sessions = lock(&CHAT_SESSIONS);
+    let mut snapshots: Vec<(u64, ChatStreamSnapshot)> = sessions
+        .iter()
+        .map(|(id, s)| (s.sequence, ChatStreamSnapshot {
+            request_id: id.clone(),
+            content: s.content.clone(),
+            finished: s.finished,
+        }))
+        .collect();
+    snapshots.sort_by_key(|(sequence, _)| *sequence);
+    Ok(snapshots.into_iter().map(|(_, s)| s).collect())
+}
+
+/// Tauri command to attach a new channel to an existing chat stream, e.g. after
+/// a webview reload. Returns the content received so far.
+#[tauri::command]
+pub async fn attach_chat_stream(
+    request_id: String,
+    on_event: Channel<ChatStreamEvent>,
+) -> Result<ChatStreamSnapshot, String> {
+    let mut sessions = lock(&CHAT_SESSIONS);
+    let session = sessions
+        .get_mut(&request_id)
+        .ok_or_else(|| format!("No chat stream with id {}", request_id))?;
+    session.channel = on_event;
+    Ok(ChatStreamSnapshot {
+        request_id,
+        content: session.content.clone(),
+        finished: session.finished,
+    })
+}
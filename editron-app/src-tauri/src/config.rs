@@ -2,11 +2,18 @@ use serde::{Deserialize, Serialize};
 use std::env;
 use dotenv::dotenv;
 
+/// Store holding user-adjustable app settings
+pub const SETTINGS_STORE: &str = "settings.json";
+
+/// Server id used in demo mode, so demo accounts never share a key with real ones
+pub const DEMO_SERVER_ID: &str = "demo";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub backend: BackendConfig,
     pub oauth: OAuthConfig,
     pub server: ServerConfig,
+    pub demo: DemoConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,12 +33,19 @@ pub struct ServerConfig {
     pub default_server_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DemoConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             backend: BackendConfig::default(),
             oauth: OAuthConfig::default(),
             server: ServerConfig::default(),
+            demo: DemoConfig::default(),
         }
     }
 }
@@ -62,6 +76,15 @@ impl Default for ServerConfig {
     }
 }
 
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 5055,
+        }
+    }
+}
+
 impl AppConfig {
     /// Load configuration from environment variables with fallback to defaults
     pub fn load() -> Self {
@@ -97,11 +120,26 @@ impl AppConfig {
             config.server.default_server_id = server_id;
         }
 
-        log::info!("Loaded configuration: backend_url={}, api_version={}, oauth_port={}, server_id={}", 
+        // Demo mode: serve canned data from the built-in fake gateway
+        if let Ok(port_str) = env::var("EDITRON_DEMO_PORT") {
+            if let Ok(port) = port_str.parse::<u16>() {
+                config.demo.port = port;
+            }
+        }
+
+        let demo_env = env::var("EDITRON_DEMO").map(|v| v == "1" || v == "true").unwrap_or(false);
+        if demo_env || env::args().any(|arg| arg == "--demo") {
+            config.demo.enabled = true;
+            config.backend.base_url = format!("http://127.0.0.1:{}", config.demo.port);
+            config.server.default_server_id = DEMO_SERVER_ID.to_string();
+        }
+
+        log::info!("Loaded configuration: backend_url={}, api_version={}, oauth_port={}, server_id={}, demo={}", 
             config.backend.base_url, 
             config.backend.api_version,
             config.oauth.callback_port_start,
-            config.server.default_server_id
+            config.server.default_server_id,
+            config.demo.enabled
         );
 
         config
@@ -127,6 +165,21 @@ impl AppConfig {
         format!("{}/auth/token/exchange", self.backend_api_url())
     }
 
+    /// Get the token refresh URL
+    pub fn token_refresh_url(&self) -> String {
+        format!("{}/auth/token/refresh", self.backend_api_url())
+    }
+
+    /// Get the version-neutral capabilities document URL
+    pub fn capabilities_url(&self) -> String {
+        format!("{}/api/capabilities", self.backend.base_url)
+    }
+
+    /// Get the path prefix that proxied backend requests must start with
+    pub fn backend_api_path_prefix(&self) -> String {
+        format!("/api/{}", self.backend.api_version)
+    }
+
     /// Get OAuth callback URL for a specific port
     pub fn oauth_callback_url(&self, port: u16) -> String {
         format!("http://localhost:{}/auth/callback", port)
@@ -0,0 +1,212 @@
+use crate::http_client;
+use crate::locks::lock;
+use crate::session;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+/// Probe timeout; a slower answer counts as offline
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Latency above which the backend is considered degraded
+const DEGRADED_LATENCY: Duration = Duration::from_millis(2000);
+/// How long a request waits for a fresh probe when the backend looks offline
+const OFFLINE_GRACE: Duration = Duration::from_secs(3);
+/// First and maximum interval between probes while online
+const ONLINE_INTERVAL_MIN: Duration = Duration::from_secs(30);
+const ONLINE_INTERVAL_MAX: Duration = Duration::from_secs(300);
+/// First and maximum interval between probes after a failure
+const FAILURE_INTERVAL_MIN: Duration = Duration::from_secs(5);
+const FAILURE_INTERVAL_MAX: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectivityState {
+    Unknown,
+    Online,
+    Degraded,
+    Offline,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectivityStatus {
+    pub state: ConnectivityState,
+    #[serde(rename = "latencyMs")]
+    pub latency_ms: Option<u64>,
+    #[serde(rename = "lastCheckedAt")]
+    pub last_checked_at: Option<u64>,
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: u32,
+}
+
+lazy_static::lazy_static! {
+    static ref STATUS: Mutex<ConnectivityStatus> = Mutex::new(ConnectivityStatus {
+        state: ConnectivityState::Unknown,
+        latency_ms: None,
+        last_checked_at: None,
+        consecutive_failures: 0,
+    });
+    static ref PROBE_NOW: Notify = Notify::new();
+}
+
+/// Returns the most recent connectivity status
+pub fn current_status() -> ConnectivityStatus {
+    lock(&STATUS).clone()
+}
+
+/// Returns true when the last probe could not reach the backend
+pub fn is_offline() -> bool {
+    lock(&STATUS).state == ConnectivityState::Offline
+}
+
+/// Called by the request layer after every backend call. A network failure
+/// while online, or a success while offline, triggers an immediate probe.
+pub fn report_request_result(network_ok: bool) {
+    let state = lock(&STATUS).state;
+    let stale = if network_ok {
+        state == ConnectivityState::Offline
+    } else {
+        state != ConnectivityState::Offline
+    };
+    if stale {
+        PROBE_NOW.notify_one();
+    }
+}
+
+/// Waits until the backend is reachable or `timeout` elapses.
+/// Returns whether the backend is reachable.
+pub async fn wait_until_online(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if !is_offline() {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        tokio::time::sleep((deadline - now).min(Duration::from_secs(1))).await;
+    }
+}
+
+/// Fails fast when the backend is known to be offline. A fresh probe is
+/// requested first so a stale offline state does not block requests for long.
+pub async fn ensure_online() -> Result<(), String> {
+    if !is_offline() {
+        return Ok(());
+    }
+    PROBE_NOW.notify_one();
+    if wait_until_online(OFFLINE_GRACE).await {
+        Ok(())
+    } else {
+        Err("Backend is unreachable - you appear to be offline".to_string())
+    }
+}
+
+async fn probe(base_url: &str) -> (ConnectivityState, Option<u64>) {
+    let client = http_client::get_client();
+    let started = Instant::now();
+    let result = client
+        .get(base_url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await;
+    let latency = started.elapsed();
+
+    match result {
+        // Any HTTP answer means the gateway is reachable; 5xx means it is struggling
+        Ok(res) if res.status().is_server_error() => (ConnectivityState::Degraded, Some(latency.as_millis() as u64)),
+        Ok(_) if latency > DEGRADED_LATENCY => (ConnectivityState::Degraded, Some(latency.as_millis() as u64)),
+        Ok(_) => (ConnectivityState::Online, Some(latency.as_millis() as u64)),
+        Err(e) => {
+            log::debug!("Connectivity probe failed: {}", e);
+            (ConnectivityState::Offline, None)
+        }
+    }
+}
+
+fn update_status(app: &AppHandle, state: ConnectivityState, latency_ms: Option<u64>) -> ConnectivityStatus {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let (status, changed) = {
+        let mut status = lock(&STATUS);
+        let changed = status.state != state;
+        status.state = state;
+        status.latency_ms = latency_ms;
+        status.last_checked_at = Some(now);
+        status.consecutive_failures = if state == ConnectivityState::Online {
+            0
+        } else {
+            status.consecutive_failures + 1
+        };
+        (status.clone(), changed)
+    };
+
+    if changed {
+        log::info!("Connectivity changed to {:?} (latency: {:?}ms)", state, latency_ms);
+        if let Err(e) = app.emit("connectivity_changed", status.clone()) {
+            log::error!("Failed to emit connectivity_changed event: {}", e);
+        }
+    }
+    status
+}
+
+/// Interval until the next probe: slow down while things are stable, probe
+/// more often after failures so recovery is noticed quickly.
+fn next_interval(status: &ConnectivityStatus, stable_rounds: u32) -> Duration {
+    if status.state == ConnectivityState::Online {
+        (ONLINE_INTERVAL_MIN * 2u32.saturating_pow(stable_rounds.min(8))).min(ONLINE_INTERVAL_MAX)
+    } else {
+        let exponent = status.consecutive_failures.saturating_sub(1).min(8);
+        (FAILURE_INTERVAL_MIN * 2u32.pow(exponent)).min(FAILURE_INTERVAL_MAX)
+    }
+}
+
+/// Starts the background health monitor
+pub fn start_monitor(app: AppHandle) {
+    let base_url = session::manager(&app).config().backend.base_url.clone();
+    tauri::async_runtime::spawn(async move {
+        log::info!("Starting connectivity monitor for {}", base_url);
+        let mut stable_rounds = 0;
+        let mut last_state = ConnectivityState::Unknown;
+
+        loop {
+            let (state, latency_ms) = probe(&base_url).await;
+            let status = update_status(&app, state, latency_ms);
+
+            stable_rounds = if state == last_state { stable_rounds + 1 } else { 0 };
+            last_state = state;
+
+            tokio::select! {
+                _ = tokio::time::sleep(next_interval(&status, stable_rounds)) => {}
+                _ = PROBE_NOW.notified() => {
+                    log::debug!("Connectivity probe requested");
+                    stable_rounds = 0;
+                }
+            }
+        }
+    });
+}
+
+/// Tauri command to get the current connectivity status
+#[tauri::command]
+pub async fn get_connectivity() -> Result<ConnectivityStatus, String> {
+    Ok(current_status())
+}
+
+/// Probes the backend right away and records the result
+pub async fn check_now(app: &AppHandle) -> ConnectivityStatus {
+    let base_url = session::manager(app).config().backend.base_url.clone();
+    let (state, latency_ms) = probe(&base_url).await;
+    update_status(app, state, latency_ms)
+}
+
+/// Tauri command to probe the backend right away
+#[tauri::command]
+pub async fn check_connectivity(app: AppHandle) -> Result<ConnectivityStatus, String> {
+    Ok(check_now(&app).await)
+}
@@ -0,0 +1,472 @@
+use crate::api_types::*;
+use crate::locks::lock;
+use crate::timestamp;
+use futures_util::StreamExt;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::reply::{Reply, Response};
+use warp::{Filter, Rejection};
+
+// Built-in fake gateway for demos and UI work. It serves canned data from memory
+// on localhost and implements just enough of the real API for the app to run.
+
+const DEMO_DELAY_MS: u64 = 60;
+
+struct DemoDocument {
+    project_uuid: String,
+    document: Document,
+}
+
+struct DemoState {
+    projects: Vec<Project>,
+    documents: Vec<DemoDocument>,
+    history: Vec<ChatMessage>,
+    next_id: i64,
+}
+
+impl DemoState {
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<DemoState> = Mutex::new(seed());
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+fn new_uuid() -> String {
+    let bytes: [u8; 16] = rand::random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-4{}-a{}-{}-{}", &hex[0..8], &hex[9..12], &hex[13..16], &hex[16..20], &hex[20..32])
+}
+
+fn seed() -> DemoState {
+    let created = timestamp::now_iso8601();
+    let project = |id: i64, uuid: &str, name: &str, description: &str| Project {
+        id,
+        uuid: uuid.to_string(),
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        custom_instructions: None,
+        created_at: created.clone(),
+        updated_at: created.clone(),
+    };
+    let document = |id: i64, uuid: &str, title: &str, content: &str| Document {
+        id,
+        uuid: uuid.to_string(),
+        title: title.to_string(),
+        content: content.to_string(),
+        status: DocumentStatus::Ready,
+        created_at: created.clone(),
+        updated_at: created.clone(),
+    };
+
+    let launch = "6f1c2a8e-4b7d-4e21-9a3f-1d2c3b4a5e60";
+    let research = "0b9e8d7c-6a5f-4e3d-8c2b-1a0f9e8d7c6b";
+
+    DemoState {
+        projects: vec![
+            project(1, launch, "Product Launch", "Press release and launch announcement drafts"),
+            project(2, research, "Market Research", "Notes and summaries from customer interviews"),
+        ],
+        documents: vec![
+            DemoDocument {
+                project_uuid: launch.to_string(),
+                document: document(
+                    1,
+                    "3c4d5e6f-7a8b-4c9d-8e0f-1a2b3c4d5e6f",
+                    "Press Release",
+                    "<h1>Editron launches AI-assisted document editing</h1>\
+                     <p>Today we are announcing Editron, a desktop editor that helps teams draft, review and send documents faster.</p>\
+                     <h2>Highlights</h2>\
+                     <ul><li>Chat with your documents</li><li>Agent edits with reviewable diffs</li><li>Send as PDF straight from Gmail</li></ul>",
+                ),
+            },
+            DemoDocument {
+                project_uuid: launch.to_string(),
+                document: document(
+                    2,
+                    "4d5e6f7a-8b9c-4d0e-9f1a-2b3c4d5e6f7a",
+                    "Launch Checklist",
+                    "<h1>Launch checklist</h1><ol><li>Finalize press release</li><li>Prepare demo video</li><li>Brief the support team</li></ol>",
+                ),
+            },
+            DemoDocument {
+                project_uuid: research.to_string(),
+                document: document(
+                    3,
+                    "5e6f7a8b-9c0d-4e1f-8a2b-3c4d5e6f7a8b",
+                    "Interview Summary",
+                    "<h1>Customer interviews</h1><p>Most participants draft long documents in Word and paste them into email.</p>\
+                     <table><tr><th>Theme</th><th>Mentions</th></tr><tr><td>Formatting</td><td>7</td></tr><tr><td>Review cycles</td><td>5</td></tr></table>",
+                ),
+            },
+        ],
+        history: Vec::new(),
+        next_id: 100,
+    }
+}
+
+fn json_response<T: serde::Serialize>(value: &T, status: StatusCode) -> Response {
+    warp::reply::with_status(warp::reply::json(value), status).into_response()
+}
+
+fn not_found(message: &str) -> Response {
+    json_response(&json!({ "statusCode": 404, "message": message }), StatusCode::NOT_FOUND)
+}
+
+fn no_content() -> Response {
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Accepts any bearer token issued by the demo gateway
+fn authorized() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(|header: Option<String>| async move {
+            match header {
+                Some(h) if h.starts_with("Bearer demo-") => Ok(()),
+                _ => Err(warp::reject::custom(Unauthorized)),
+            }
+        })
+        .untuple_one()
+}
+
+fn demo_profile() -> UserProfile {
+    UserProfile {
+        id: 1,
+        email: "demo@editron.app".to_string(),
+        name: "Demo User".to_string(),
+        profile_picture: None,
+        auth_provider: "google-oauth2".to_string(),
+        is_google_api_connected: Some(false),
+    }
+}
+
+fn auth_routes() -> warp::filters::BoxedFilter<(Response,)> {
+    // The login URL points straight back at the app's callback server, so the
+    // browser completes the flow without any real OAuth provider.
+    let login = warp::path!("auth" / "google" / "login")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let redirect_uri = query.get("redirect_uri").cloned().unwrap_or_default();
+            let state = query.get("state").map(|s| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>());
+            let url = match state {
+                Some(state) => format!("{}?code=demo-code&state={}", redirect_uri, state),
+                None => format!("{}?code=demo-code", redirect_uri),
+            };
+            json_response(&json!({ "url": url }), StatusCode::OK)
+        });
+
+    let exchange = warp::path!("auth" / "token" / "exchange")
+        .and(warp::post())
+        .map(|| {
+            json_response(
+                &json!({ "accessToken": format!("demo-{}", new_uuid()), "refreshToken": "demo-refresh" }),
+                StatusCode::OK,
+            )
+        });
+
+    let refresh = warp::path!("auth" / "token" / "refresh")
+        .and(warp::post())
+        .map(|| json_response(&json!({ "accessToken": format!("demo-{}", new_uuid()) }), StatusCode::OK));
+
+    let user = warp::path!("auth" / "user")
+        .and(warp::get())
+        .and(authorized())
+        .map(|| json_response(&demo_profile(), StatusCode::OK));
+
+    login.or(exchange).unify().or(refresh).unify().or(user).unify().boxed()
+}
+
+fn project_routes() -> warp::filters::BoxedFilter<(Response,)> {
+    let list = warp::path!("projects")
+        .and(warp::get())
+        .map(|| json_response(&lock(&STATE).projects, StatusCode::OK));
+
+    let create = warp::path!("projects")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(|request: CreateProjectRequest| {
+            let mut state = lock(&STATE);
+            let id = state.next_id();
+            let project = Project {
+                id,
+                uuid: new_uuid(),
+                name: request.name,
+                description: request.description,
+                custom_instructions: request.custom_instructions,
+                created_at: timestamp::now_iso8601(),
+                updated_at: timestamp::now_iso8601(),
+            };
+            state.projects.insert(0, project.clone());
+            json_response(&project, StatusCode::CREATED)
+        });
+
+    let details = warp::path!("projects" / String / "details")
+        .and(warp::get())
+        .map(|uuid: String| {
+            let state = lock(&STATE);
+            match state.projects.iter().find(|p| p.uuid == uuid) {
+                Some(project) => json_response(project, StatusCode::OK),
+                None => not_found("Project not found"),
+            }
+        });
+
+    let update = warp::path!("projects" / String / "update")
+        .and(warp::patch())
+        .and(warp::body::json())
+        .map(|uuid: String, request: UpdateProjectRequest| {
+            let mut state = lock(&STATE);
+            match state.projects.iter_mut().find(|p| p.uuid == uuid) {
+                Some(project) => {
+                    if let Some(name) = request.name {
+                        project.name = name;
+                    }
+                    if request.description.is_some() {
+                        project.description = request.description;
+                    }
+                    if request.custom_instructions.is_some() {
+                        project.custom_instructions = request.custom_instructions;
+                    }
+                    project.updated_at = timestamp::now_iso8601();
+                    json_response(project, StatusCode::OK)
+                }
+                None => not_found("Project not found"),
+            }
+        });
+
+    let delete = warp::path!("projects" / String / "delete")
+        .and(warp::delete())
+        .map(|uuid: String| {
+            let mut state = lock(&STATE);
+            state.projects.retain(|p| p.uuid != uuid);
+            state.documents.retain(|d| d.project_uuid != uuid);
+            no_content()
+        });
+
+    list.or(create)
+        .unify()
+        .or(details)
+        .unify()
+        .or(update)
+        .unify()
+        .or(delete)
+        .unify()
+        .boxed()
+}
+
+fn document_routes() -> warp::filters::BoxedFilter<(Response,)> {
+    let list = warp::path!("documents")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let state = lock(&STATE);
+            let project_uuid = query.get("projectUuid");
+            let documents: Vec<&Document> = state
+                .documents
+                .iter()
+                .filter(|d| project_uuid.map(|p| &d.project_uuid == p).unwrap_or(true))
+                .map(|d| &d.document)
+                .collect();
+            json_response(&documents, StatusCode::OK)
+        });
+
+    let agent_edit = warp::path!("documents" / "agent-edit")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(|request: AgentEditRequest| async move {
+            // Pretend to think for a moment, like the real agent
+            tokio::time::sleep(Duration::from_millis(DEMO_DELAY_MS * 20)).await;
+            let original = {
+                let state = lock(&STATE);
+                state
+                    .documents
+                    .iter()
+                    .find(|d| d.document.uuid == request.document_uuid)
+                    .map(|d| d.document.content.clone())
+            };
+            let response = match original {
+                Some(original_content) => {
+                    let addition = format!(
+                        "<p><em>Demo edit for \"{}\": this paragraph was suggested by the agent.</em></p>",
+                        request.prompt_text.replace('<', "&lt;").replace('>', "&gt;")
+                    );
+                    json_response(
+                        &AgentEditResponse {
+                            suggested_content: format!("{}{}", original_content, addition),
+                            diff_html: format!("{}<ins>{}</ins>", original_content, addition),
+                            original_content,
+                        },
+                        StatusCode::OK,
+                    )
+                }
+                None => not_found("Document not found"),
+            };
+            Ok::<Response, Rejection>(response)
+        });
+
+    let get = warp::path!("documents" / String)
+        .and(warp::get())
+        .map(|uuid: String| {
+            let state = lock(&STATE);
+            match state.documents.iter().find(|d| d.document.uuid == uuid) {
+                Some(d) => json_response(&d.document, StatusCode::OK),
+                None => not_found("Document not found"),
+            }
+        });
+
+    let update = warp::path!("documents" / String)
+        .and(warp::patch())
+        .and(warp::body::json())
+        .map(|uuid: String, request: UpdateDocumentRequest| {
+            let mut state = lock(&STATE);
+            match state.documents.iter_mut().find(|d| d.document.uuid == uuid) {
+                Some(d) => {
+                    if let Some(content) = request.content {
+                        d.document.content = content;
+                    }
+                    if let Some(title) = request.title {
+                        d.document.title = title;
+                    }
+                    d.document.updated_at = timestamp::now_iso8601();
+                    json_response(&d.document, StatusCode::OK)
+                }
+                None => not_found("Document not found"),
+            }
+        });
+
+    let delete = warp::path!("documents" / String)
+        .and(warp::delete())
+        .map(|uuid: String| {
+            lock(&STATE).documents.retain(|d| d.document.uuid != uuid);
+            no_content()
+        });
+
+    list.or(agent_edit)
+        .unify()
+        .or(get)
+        .unify()
+        .or(update)
+        .unify()
+        .or(delete)
+        .unify()
+        .boxed()
+}
+
+fn canned_answer(prompt: &str) -> String {
+    format!(
+        "This is the Editron demo assistant. You asked: \"{}\". In demo mode answers are canned, \
+         but they stream exactly like the real assistant so you can try out the chat panel.",
+        prompt
+    )
+}
+
+fn chat_routes() -> warp::filters::BoxedFilter<(Response,)> {
+    let history = warp::path!("chat" / "history")
+        .and(warp::get())
+        .map(|| json_response(&lock(&STATE).history, StatusCode::OK));
+
+    let query = warp::path!("chat" / "query")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(|request: ChatQueryRequest| {
+            let answer = canned_answer(&request.prompt_text);
+            {
+                let mut state = lock(&STATE);
+                let user_id = state.next_id();
+                let assistant_id = state.next_id();
+                state.history.push(ChatMessage {
+                    id: user_id,
+                    role: ChatMessageRole::User,
+                    content: request.prompt_text.clone(),
+                    mode: Some(request.mode),
+                    created_at: timestamp::now_iso8601(),
+                });
+                state.history.push(ChatMessage {
+                    id: assistant_id,
+                    role: ChatMessageRole::Assistant,
+                    content: answer.clone(),
+                    mode: Some(request.mode),
+                    created_at: timestamp::now_iso8601(),
+                });
+            }
+
+            let words: Vec<String> = answer.split_inclusive(' ').map(str::to_string).collect();
+            let events = futures_util::stream::iter(words.into_iter().enumerate()).then(|(i, word)| async move {
+                tokio::time::sleep(Duration::from_millis(DEMO_DELAY_MS)).await;
+                log::debug!("Demo chat chunk {}", i);
+                Ok::<_, Infallible>(
+                    warp::sse::Event::default().data(json!({ "type": "chunk", "content": word }).to_string()),
+                )
+            });
+            warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response()
+        });
+
+    history.or(query).unify().boxed()
+}
+
+async fn handle_rejection(err: Rejection) -> Result<Response, Infallible> {
+    if err.find::<Unauthorized>().is_some() {
+        return Ok(json_response(&json!({ "statusCode": 401, "message": "Unauthorized" }), StatusCode::UNAUTHORIZED));
+    }
+    if err.is_not_found() {
+        return Ok(not_found("Not available in demo mode"));
+    }
+    log::warn!("Demo gateway rejected request: {:?}", err);
+    Ok(json_response(&json!({ "statusCode": 400, "message": "Bad request" }), StatusCode::BAD_REQUEST))
+}
+
+/// Starts the fake gateway on 127.0.0.1 at the configured demo port
+pub fn start(port: u16, api_version: String) {
+    let api_versions = json!([api_version]);
+
+    let health = warp::path::end().map(|| json_response(&json!({ "status": "ok", "demo": true }), StatusCode::OK));
+    let capabilities = warp::path!("api" / "capabilities").and(warp::get()).map(move || {
+        json_response(
+            &json!({
+                "serverVersion": "demo",
+                "apiVersions": api_versions.clone(),
+                "features": { "agentEdit": true, "gmail": false, "sseChat": true, "uploads": false },
+            }),
+            StatusCode::OK,
+        )
+    });
+
+    let protected = project_routes()
+        .or(document_routes())
+        .unify()
+        .or(chat_routes())
+        .unify();
+    let api = warp::path("api")
+        .and(warp::path(api_version))
+        .and(auth_routes().or(authorized().and(protected)).unify());
+
+    let routes = health
+        .or(capabilities)
+        .unify()
+        .or(api)
+        .unify()
+        .recover(handle_rejection)
+        .with(warp::log("demo_gateway"));
+
+    tauri::async_runtime::spawn(async move {
+        match warp::serve(routes).try_bind_with_graceful_shutdown(([127, 0, 0, 1], port), std::future::pending::<()>()) {
+            Ok((addr, server)) => {
+                log::info!("Demo gateway listening on http://{}", addr);
+                server.await;
+            }
+            Err(e) => log::error!("Failed to start demo gateway on port {}: {}", port, e),
+        }
+    });
+}
@@ -0,0 +1,501 @@
+use crate::auth;
+use crate::capabilities;
+use crate::config::AppConfig;
+use crate::connectivity::{self, ConnectivityState};
+use crate::inspector;
+use crate::locks::lock;
+use crate::logging;
+use crate::requests::{self, TempFile};
+use crate::session::{self, SessionManager};
+use crate::store_schema::{self, StoreSchema};
+use crate::timestamp;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use zip::write::SimpleFileOptions;
+
+/// Number of ports the OAuth callback servers may fall back to
+const CALLBACK_PORT_RANGE: u16 = 100;
+/// Access tokens expiring sooner than this are reported as a warning
+const TOKEN_EXPIRY_WARNING_SECS: u64 = 5 * 60;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckResult {
+    fn new(name: &str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status, message: message.into() }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiagnosticsReport {
+    /// The worst status of all checks
+    pub status: CheckStatus,
+    pub checks: Vec<CheckResult>,
+    #[serde(rename = "ranAt")]
+    pub ran_at: String,
+}
+
+/// Holds the report of the checks run at startup, once they have finished
+#[derive(Default)]
+pub struct StartupReport {
+    report: Mutex<Option<DiagnosticsReport>>,
+}
+
+impl StartupReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set(&self, report: DiagnosticsReport) {
+        *lock(&self.report) = Some(report);
+    }
+
+    pub fn get(&self) -> Option<DiagnosticsReport> {
+        lock(&self.report).clone()
+    }
+}
+
+/// Whether a store file exists and can be parsed. Never includes the contents.
+#[derive(Serialize, Clone, Debug)]
+pub struct StoreHealth {
+    pub name: String,
+    pub exists: bool,
+    pub bytes: Option<u64>,
+    pub parsed: bool,
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: Option<u32>,
+    /// Number of entries under the store's data key
+    pub entries: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// Checks that a store file can be read and parsed without loading it into the app
+pub fn check_store_file(app: &AppHandle, schema: &StoreSchema) -> StoreHealth {
+    let mut health = StoreHealth {
+        name: schema.file.to_string(),
+        exists: false,
+        bytes: None,
+        parsed: false,
+        schema_version: None,
+        entries: None,
+        error: None,
+    };
+
+    let path = match store_schema::path(app, schema) {
+        Ok(path) => path,
+        Err(e) => {
+            health.error = Some(e);
+            return health;
+        }
+    };
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return health,
+        Err(e) => {
+            health.exists = true;
+            health.error = Some(e.to_string());
+            return health;
+        }
+    };
+
+    health.exists = true;
+    health.bytes = Some(data.len() as u64);
+    match serde_json::from_slice::<Value>(&data) {
+        Ok(value) => {
+            health.parsed = true;
+            if let Some(raw) = value.get(schema.key) {
+                let (version, data) = store_schema::open_envelope(raw.clone());
+                health.schema_version = Some(version);
+                health.entries = Some(match data {
+                    Value::Array(items) => items.len(),
+                    Value::Object(map) => map.len(),
+                    _ => 1,
+                });
+            }
+        }
+        Err(e) => health.error = Some(e.to_string()),
+    }
+    health
+}
+
+pub fn check_store_files(app: &AppHandle) -> Vec<StoreHealth> {
+    let demo = session::manager(app).config().demo.enabled;
+    store_schema::session_stores(demo)
+        .into_iter()
+        .map(|schema| check_store_file(app, schema))
+        .collect()
+}
+
+fn check_stores(app: &AppHandle) -> CheckResult {
+    let stores = check_store_files(app);
+    let broken: Vec<String> = stores
+        .iter()
+        .filter(|s| s.exists && !s.parsed)
+        .map(|s| format!("{} ({})", s.name, s.error.clone().unwrap_or_default()))
+        .collect();
+    if !broken.is_empty() {
+        return CheckResult::new("stores", CheckStatus::Fail, format!("Unreadable store files: {}", broken.join(", ")));
+    }
+
+    let recovered = store_schema::recovered();
+    if !recovered.is_empty() {
+        let details: Vec<String> = recovered
+            .iter()
+            .map(|r| format!("{} ({})", r.file, if r.restored { "restored from backup" } else { "reset" }))
+            .collect();
+        return CheckResult::new("stores", CheckStatus::Warn, format!("Recovered unreadable store files: {}", details.join(", ")));
+    }
+
+    // Stores are saved next to each other, so one write probe covers them all
+    let probe = match app.path().app_data_dir() {
+        Ok(dir) => std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(dir.join(".write-test"), b"ok"))
+            .and_then(|_| std::fs::remove_file(dir.join(".write-test")))
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match probe {
+        Ok(()) => CheckResult::new("stores", CheckStatus::Pass, "Store files are readable and writable"),
+        Err(e) => CheckResult::new("stores", CheckStatus::Fail, format!("App data directory is not writable: {}", e)),
+    }
+}
+
+fn check_config(config: &AppConfig) -> CheckResult {
+    let mut problems = Vec::new();
+
+    match url::Url::parse(&config.backend.base_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        Ok(url) => problems.push(format!("backend URL has unsupported scheme {}", url.scheme())),
+        Err(e) => problems.push(format!("backend URL is invalid ({})", e)),
+    }
+    let api_version = &config.backend.api_version;
+    if !(api_version.starts_with('v') && api_version.len() > 1 && api_version[1..].chars().all(|c| c.is_ascii_digit())) {
+        problems.push(format!("API version {:?} is not of the form v<number>", api_version));
+    }
+    if config.oauth.callback_port_start == 0 || config.oauth.callback_port_start.checked_add(CALLBACK_PORT_RANGE).is_none() {
+        problems.push(format!("OAuth callback port {} is out of range", config.oauth.callback_port_start));
+    }
+    if config.oauth.timeout_seconds == 0 {
+        problems.push("OAuth timeout is zero".to_string());
+    }
+    if config.server.default_server_id.is_empty() {
+        problems.push("server id is empty".to_string());
+    }
+
+    if problems.is_empty() {
+        CheckResult::new("config", CheckStatus::Pass, "Configuration is valid")
+    } else {
+        CheckResult::new("config", CheckStatus::Fail, format!("Invalid configuration: {}", problems.join("; ")))
+    }
+}
+
+fn check_callback_ports(config: &AppConfig) -> CheckResult {
+    let start = config.oauth.callback_port_start;
+    if start.checked_add(CALLBACK_PORT_RANGE).is_none() {
+        return CheckResult::new("callback_ports", CheckStatus::Fail, format!("OAuth callback port {} is out of range", start));
+    }
+    match auth::find_available_port(start) {
+        Some(port) if port == start => {
+            CheckResult::new("callback_ports", CheckStatus::Pass, format!("OAuth callback port {} is free", port))
+        }
+        Some(port) => CheckResult::new(
+            "callback_ports",
+            CheckStatus::Warn,
+            format!("OAuth callback port {} is in use, port {} will be used instead", start, port),
+        ),
+        None => CheckResult::new(
+            "callback_ports",
+            CheckStatus::Fail,
+            format!("No free OAuth callback port in {}-{}", start, start.saturating_add(CALLBACK_PORT_RANGE - 1)),
+        ),
+    }
+}
+
+async fn check_backend(app: &AppHandle) -> CheckResult {
+    let status = connectivity::check_now(app).await;
+    let base_url = &session::manager(app).config().backend.base_url;
+    let mut result = match status.state {
+        ConnectivityState::Online => CheckResult::new(
+            "backend",
+            CheckStatus::Pass,
+            format!("Backend reachable in {}ms", status.latency_ms.unwrap_or_default()),
+        ),
+        ConnectivityState::Degraded => CheckResult::new(
+            "backend",
+            CheckStatus::Warn,
+            format!("Backend is slow or returning errors ({}ms)", status.latency_ms.unwrap_or_default()),
+        ),
+        _ => CheckResult::new("backend", CheckStatus::Fail, format!("Backend at {} is unreachable", base_url)),
+    };
+
+    if let Some(message) = capabilities::current().update_required {
+        result = CheckResult::new("backend", CheckStatus::Fail, message);
+    }
+    result
+}
+
+/// Decodes the `exp` claim of a JWT without verifying its signature
+fn jwt_expiry(token: &str) -> Result<Option<u64>, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let payload = token.split('.').nth(1).ok_or("token is not a JWT")?;
+    let bytes = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("payload is not base64url ({})", e))?;
+    let claims: Value = serde_json::from_slice(&bytes).map_err(|e| format!("payload is not JSON ({})", e))?;
+    Ok(claims.get("exp").and_then(Value::as_u64))
+}
+
+fn check_token(session: &SessionManager) -> CheckResult {
+    // The demo gateway issues opaque tokens that never expire
+    if session.config().demo.enabled {
+        return CheckResult::new("token", CheckStatus::Pass, "Demo mode uses a built-in access token");
+    }
+
+    let token = match session.current_token() {
+        Some(token) => token,
+        None => return CheckResult::new("token", CheckStatus::Warn, "Not signed in"),
+    };
+
+    let expires_at = match jwt_expiry(&token.access_token) {
+        Ok(Some(exp)) => exp,
+        // Tokens without an exp claim fall back to the locally recorded expiry
+        Ok(None) => token.expires_at,
+        Err(e) => return CheckResult::new("token", CheckStatus::Fail, format!("Access token cannot be decoded: {}", e)),
+    };
+
+    let now = timestamp::now_millis() / 1000;
+    if expires_at <= now {
+        let status = if token.refresh_token.is_empty() { CheckStatus::Fail } else { CheckStatus::Warn };
+        CheckResult::new("token", status, "Access token has expired and will be refreshed on the next request")
+    } else if expires_at - now < TOKEN_EXPIRY_WARNING_SECS {
+        CheckResult::new("token", CheckStatus::Warn, format!("Access token expires in {}s", expires_at - now))
+    } else {
+        CheckResult::new("token", CheckStatus::Pass, format!("Access token valid for {}m", (expires_at - now) / 60))
+    }
+}
+
+fn check_gmail(session: &SessionManager) -> CheckResult {
+    if capabilities::current().features.gmail == Some(false) {
+        return CheckResult::new("gmail", CheckStatus::Warn, "The server does not support Gmail");
+    }
+    match session.current_profile().and_then(|p| p.is_google_api_connected) {
+        Some(true) => CheckResult::new("gmail", CheckStatus::Pass, "Gmail is connected"),
+        Some(false) => CheckResult::new("gmail", CheckStatus::Warn, "Gmail is not connected"),
+        None => CheckResult::new("gmail", CheckStatus::Warn, "Gmail connection state is unknown"),
+    }
+}
+
+/// Runs every check and returns a pass/warn/fail report
+pub async fn run_checks(app: &AppHandle) -> DiagnosticsReport {
+    let session = session::manager(app);
+    let checks = vec![
+        check_stores(app),
+        check_config(session.config()),
+        check_callback_ports(session.config()),
+        check_backend(app).await,
+        check_token(&session),
+        check_gmail(&session),
+    ];
+    let status = checks.iter().map(|c| c.status).max().unwrap_or(CheckStatus::Pass);
+    DiagnosticsReport { status, checks, ran_at: timestamp::now_iso8601() }
+}
+
+/// Runs the checks once the app has started and emits `startup_warnings` with
+/// every failing check and every setup step that failed
+pub fn start_startup_checks(app: AppHandle, setup_errors: Vec<CheckResult>) {
+    tauri::async_runtime::spawn(async move {
+        let mut report = run_checks(&app).await;
+        if !setup_errors.is_empty() {
+            report.status = CheckStatus::Fail;
+            report.checks.splice(0..0, setup_errors);
+        }
+
+        let warnings: Vec<CheckResult> = report
+            .checks
+            .iter()
+            .filter(|c| c.status != CheckStatus::Pass)
+            .cloned()
+            .collect();
+        for check in &warnings {
+            log::warn!("Startup check {} ({:?}): {}", check.name, check.status, check.message);
+        }
+        log::info!("Startup checks finished: {:?}", report.status);
+
+        app.state::<StartupReport>().set(report);
+        if !warnings.is_empty() {
+            if let Err(e) = app.emit("startup_warnings", warnings) {
+                log::error!("Failed to emit startup_warnings event: {}", e);
+            }
+        }
+    });
+}
+
+/// Records a setup step that failed, to be reported with the startup checks
+pub fn setup_failure(step: &str, error: impl std::fmt::Display) -> CheckResult {
+    log::error!("Setup step {} failed: {}", step, error);
+    CheckResult::new(step, CheckStatus::Fail, error.to_string())
+}
+
+/// Tauri command to run all diagnostics checks now
+#[tauri::command]
+pub async fn run_diagnostics(app: AppHandle) -> Result<DiagnosticsReport, String> {
+    log::info!("Running diagnostics");
+    Ok(run_checks(&app).await)
+}
+
+/// Tauri command to get the report of the checks run at startup, once they have finished
+#[tauri::command]
+pub async fn get_startup_report(startup: State<'_, StartupReport>) -> Result<Option<DiagnosticsReport>, String> {
+    Ok(startup.get())
+}
+
+/// Best-effort operating system version string
+fn os_version() -> Option<String> {
+    if cfg!(target_os = "linux") {
+        let release = std::fs::read_to_string("/etc/os-release").ok()?;
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    } else {
+        let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+            ("sw_vers", &["-productVersion"])
+        } else if cfg!(target_os = "windows") {
+            ("cmd", &["/C", "ver"])
+        } else {
+            return None;
+        };
+        let output = std::process::Command::new(program).args(args).output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+fn versions(app: &AppHandle) -> Value {
+    json!({
+        "app": app.package_info().version.to_string(),
+        "tauri": tauri::VERSION,
+        "webview": tauri::webview_version().ok(),
+        "os": std::env::consts::OS,
+        "osVersion": os_version(),
+        "arch": std::env::consts::ARCH,
+    })
+}
+
+/// Effective configuration with credentials in URLs masked
+fn masked_config(config: &AppConfig) -> Value {
+    let mut config = serde_json::to_value(config).unwrap_or(Value::Null);
+    if let Some(base_url) = config.pointer_mut("/backend/base_url") {
+        if let Some(parsed) = base_url.as_str().and_then(|u| url::Url::parse(u).ok()) {
+            *base_url = json!(inspector::redact_url(&parsed));
+        }
+    }
+    config
+}
+
+fn write_json<W: Write + std::io::Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    name: &str,
+    value: &Value,
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    zip.start_file(name, options).map_err(|e| e.to_string())?;
+    let text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    zip.write_all(logging::redact(&text).as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Copies a log file into the archive, redacting every line again in case it
+/// was written by an older version
+fn write_log<W: Write + std::io::Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    path: &Path,
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    zip.start_file(format!("logs/{}", name), options).map_err(|e| e.to_string())?;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        writeln!(zip, "{}", logging::redact(&line)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn write_bundle(path: &Path, summary: &Value, requests: &Value) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    write_json(&mut zip, "diagnostics.json", summary, options)?;
+    write_json(&mut zip, "requests.har.json", requests, options)?;
+
+    if let Some(dir) = logging::log_dir() {
+        for log_file in logging::log_files(&dir) {
+            if let Err(e) = write_log(&mut zip, &log_file, options) {
+                log::warn!("Skipping log file {:?} in diagnostics bundle: {}", log_file, e);
+            }
+        }
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Tauri command to write a zip with redacted logs, configuration, versions,
+/// store health, connectivity and recent requests into the Downloads folder
+/// and return its path
+#[tauri::command]
+pub async fn export_diagnostics(app: AppHandle) -> Result<String, String> {
+    log::info!("Exporting diagnostics bundle");
+    let destination = requests::download_destination(&app, &format!("editron-diagnostics-{}", timestamp::now_millis()), "zip")?;
+
+    let report = run_checks(&app).await;
+    let session = session::manager(&app);
+    let connectivity = connectivity::current_status();
+    let summary = json!({
+        "generatedAt": timestamp::now_iso8601(),
+        "versions": versions(&app),
+        "config": masked_config(session.config()),
+        "logLevel": log::max_level().as_str(),
+        "stores": check_store_files(&app),
+        "connectivity": connectivity,
+        "capabilities": capabilities::current(),
+        "signedIn": session.current_access_token().is_some(),
+        "checks": report,
+        "startupChecks": app.state::<StartupReport>().get(),
+    });
+    let requests = inspector::to_har();
+
+    let target = destination.clone();
+    tokio::task::spawn_blocking(move || {
+        let temp = TempFile::beside(&target);
+        write_bundle(temp.path(), &summary, &requests)?;
+        temp.persist(&target).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| {
+        log::error!("Failed to write diagnostics bundle: {}", e);
+        e
+    })?;
+
+    log::info!("Diagnostics bundle saved to {:?}", destination);
+    Ok(destination.to_string_lossy().into_owned())
+}
@@ -0,0 +1,97 @@
+use crate::api_client;
+use crate::api_types::{AgentEditRequest, AgentEditResponse, Document};
+use crate::capabilities::{self, Feature};
+use crate::docx;
+use crate::http_cache::{self, HttpCache};
+use crate::library;
+use crate::requests::{self, RequestOutcome};
+use crate::session;
+use tauri::AppHandle;
+
+/// Tauri command to upload a DOCX file and create a document from it
+#[tauri::command]
+pub async fn upload_document(
+    app: AppHandle,
+    request_id: String,
+    project_uuid: String,
+    file_name: String,
+    contents: Vec<u8>,
+) -> Result<RequestOutcome<Document>, String> {
+    log::info!("Uploading document {} ({} bytes)", file_name, contents.len());
+    capabilities::require(Feature::Uploads)?;
+    // Reject what the gateway would reject before spending the upload on it
+    docx::validate(&file_name, &contents)?;
+
+    requests::run_cancellable(&request_id, async {
+        let document = api_client::with_session(&app, |client| {
+            let project_uuid = project_uuid.clone();
+            let file_name = file_name.clone();
+            let contents = contents.clone();
+            async move { client.upload_document(&project_uuid, &file_name, contents).await }
+        })
+        .await?;
+
+        if let Some(cache) = HttpCache::for_current_account(&app) {
+            cache.invalidate(Some(&http_cache::resource_prefix(session::manager(&app).config(), "/documents")));
+        }
+        library::remember_document(&app, &project_uuid, &document);
+        Ok(document)
+    })
+    .await
+}
+
+/// Tauri command to request an AI agent edit of a document
+#[tauri::command]
+pub async fn agent_edit(
+    app: AppHandle,
+    request_id: String,
+    project_uuid: String,
+    document_uuid: String,
+    prompt_text: String,
+) -> Result<RequestOutcome<AgentEditResponse>, String> {
+    log::info!("Requesting agent edit for document {}", document_uuid);
+    capabilities::require(Feature::AgentEdit)?;
+
+    let request = AgentEditRequest { document_uuid, prompt_text };
+    requests::run_cancellable(&request_id, async {
+        api_client::with_session(&app, |client| {
+            let project_uuid = project_uuid.clone();
+            let request = request.clone();
+            async move { client.agent_edit(&project_uuid, &request).await }
+        })
+        .await
+    })
+    .await
+}
+
+/// Tauri command to download a document as PDF into the Downloads folder,
+/// named after `title`. The file is written next to the destination first and
+/// only renamed into place once complete, so a cancelled download leaves
+/// nothing behind. Returns the path the PDF was saved to.
+#[tauri::command]
+pub async fn download_pdf(
+    app: AppHandle,
+    request_id: String,
+    document_uuid: String,
+    title: String,
+) -> Result<RequestOutcome<String>, String> {
+    log::info!("Downloading PDF for document {}", document_uuid);
+
+    requests::run_cancellable(&request_id, async {
+        let destination = requests::download_destination(&app, &title, "pdf")?;
+        let bytes = api_client::with_session(&app, |client| {
+            let document_uuid = document_uuid.clone();
+            async move { client.download_pdf(&document_uuid).await }
+        })
+        .await?;
+
+        requests::write_file(&destination, bytes).await.map_err(|e| {
+            log::error!("Failed to save PDF: {}", e);
+            e
+        })?;
+
+        log::info!("PDF saved to {:?}", destination);
+        Ok(destination.to_string_lossy().into_owned())
+    })
+    .await
+}
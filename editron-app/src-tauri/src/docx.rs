@@ -0,0 +1,1633 @@
+use crate::http_client;
+use crate::library::{self, Library};
+use crate::outbox::{self, DocumentChange, Outbox, OutboxEntry};
+use crate::requests::{self, RequestOutcome};
+use crate::session::SessionManager;
+use crate::shutdown::Shutdown;
+use crate::timestamp;
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+use roxmltree::{Document as Xml, Node};
+use scraper::{ElementRef, Html, Node as HtmlNode, Selector};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use tauri::{AppHandle, State};
+use zip::write::SimpleFileOptions;
+use zip::ZipArchive;
+
+/// Largest file `POST /documents/upload-and-preview` accepts
+pub const MAX_DOCX_BYTES: usize = 10 * 1024 * 1024;
+/// Largest single part read from a package, against zip bombs
+const MAX_PART_BYTES: u64 = 64 * 1024 * 1024;
+/// Depth limit when following `basedOn` style chains
+const MAX_STYLE_DEPTH: usize = 10;
+
+const MAIN_DOCUMENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml";
+const OFFICE_DOCUMENT_RELATIONSHIP: &str = "/officeDocument";
+
+/// A DOCX file converted to editor HTML
+#[derive(Serialize, Clone, Debug)]
+pub struct DocxImport {
+    pub title: String,
+    pub html: String,
+    /// Features of the file that were dropped or simplified in the conversion
+    pub unsupported: Vec<String>,
+}
+
+/// Checks that a file is a .docx the gateway will accept, without uploading it
+pub fn validate(file_name: &str, contents: &[u8]) -> Result<(), String> {
+    let is_docx = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("docx"));
+    if !is_docx {
+        return Err(format!("{} is not a .docx file", file_name));
+    }
+    if contents.len() > MAX_DOCX_BYTES {
+        return Err(format!(
+            "{} is {:.1} MB; files up to {} MB can be imported",
+            file_name,
+            contents.len() as f64 / (1024.0 * 1024.0),
+            MAX_DOCX_BYTES / (1024 * 1024)
+        ));
+    }
+
+    let mut package = Package::open(contents).map_err(|e| format!("{} is not a Word document: {}", file_name, e))?;
+    let content_types = package
+        .read_string("[Content_Types].xml")?
+        .ok_or_else(|| format!("{} is not a Word document: no content types", file_name))?;
+    if !content_types.contains(MAIN_DOCUMENT_TYPE) {
+        return Err(format!("{} is not a Word document (macro-enabled and template files are not supported)", file_name));
+    }
+    Ok(())
+}
+
+/// Converts a .docx file to HTML the editor understands: paragraphs, headings,
+/// lists, tables, bold, italic, strikethrough, links and images
+pub fn import(file_name: &str, contents: &[u8]) -> Result<DocxImport, String> {
+    validate(file_name, contents)?;
+    let mut package = Package::open(contents)?;
+
+    let main_part = package.main_part()?;
+    let xml = package.read_string(&main_part)?.ok_or_else(|| format!("Missing {}", main_part))?;
+    let document = Xml::parse(&xml).map_err(|e| format!("Invalid {}: {}", main_part, e))?;
+    let body = child(document.root_element(), "body").ok_or_else(|| format!("No body in {}", main_part))?;
+
+    let relationships = package.relationships(&main_part)?;
+    let styles = match relationships.values().find(|r| r.kind.ends_with("/styles")) {
+        Some(r) => package.read_string(&r.target)?.map(|xml| parse_styles(&xml)).transpose()?.unwrap_or_default(),
+        None => HashMap::new(),
+    };
+    let numbering = match relationships.values().find(|r| r.kind.ends_with("/numbering")) {
+        Some(r) => package.read_string(&r.target)?.map(|xml| parse_numbering(&xml)).transpose()?.unwrap_or_default(),
+        None => HashMap::new(),
+    };
+
+    let title = package
+        .read_string("docProps/core.xml")?
+        .and_then(|xml| core_title(&xml))
+        .unwrap_or_else(|| {
+            Path::new(file_name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+
+    let mut converter = Converter {
+        package,
+        relationships,
+        styles,
+        numbering,
+        unsupported: BTreeSet::new(),
+        in_header: false,
+    };
+    let html = converter.blocks(body);
+    let unsupported: Vec<String> = converter.unsupported.into_iter().map(String::from).collect();
+    if !unsupported.is_empty() {
+        log::info!("Imported {} without: {}", file_name, unsupported.join(", "));
+    }
+    Ok(DocxImport { title, html, unsupported })
+}
+
+struct Package<'a> {
+    archive: ZipArchive<Cursor<&'a [u8]>>,
+}
+
+struct Relationship {
+    kind: String,
+    /// Part name inside the package, or the URL of an external target
+    target: String,
+    external: bool,
+}
+
+impl<'a> Package<'a> {
+    fn open(contents: &'a [u8]) -> Result<Self, String> {
+        ZipArchive::new(Cursor::new(contents))
+            .map(|archive| Self { archive })
+            .map_err(|e| e.to_string())
+    }
+
+    fn read(&mut self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let mut file = match self.archive.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+        };
+        if file.size() > MAX_PART_BYTES {
+            return Err(format!("{} is too large", name));
+        }
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        Ok(Some(bytes))
+    }
+
+    fn read_string(&mut self, name: &str) -> Result<Option<String>, String> {
+        self.read(name)?
+            .map(|bytes| String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8", name)))
+            .transpose()
+    }
+
+    /// Relationships of a part, keyed by id
+    fn relationships(&mut self, part: &str) -> Result<HashMap<String, Relationship>, String> {
+        let (dir, file) = part.rsplit_once('/').unwrap_or(("", part));
+        let rels_name = if dir.is_empty() { format!("_rels/{}.rels", file) } else { format!("{}/_rels/{}.rels", dir, file) };
+        let Some(xml) = self.read_string(&rels_name)? else { return Ok(HashMap::new()) };
+        let rels = Xml::parse(&xml).map_err(|e| format!("Invalid {}: {}", rels_name, e))?;
+
+        Ok(rels
+            .root_element()
+            .children()
+            .filter(|n| is(*n, "Relationship"))
+            .filter_map(|n| {
+                let external = n.attribute("TargetMode") == Some("External");
+                let target = n.attribute("Target")?;
+                Some((
+                    n.attribute("Id")?.to_string(),
+                    Relationship {
+                        kind: n.attribute("Type").unwrap_or_default().to_string(),
+                        target: if external { target.to_string() } else { resolve(dir, target) },
+                        external,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Name of the main document part, found through the package relationships
+    fn main_part(&mut self) -> Result<String, String> {
+        Ok(self
+            .relationships("")?
+            .into_values()
+            .find(|r| r.kind.ends_with(OFFICE_DOCUMENT_RELATIONSHIP))
+            .map(|r| r.target)
+            .unwrap_or_else(|| "word/document.xml".to_string()))
+    }
+}
+
+/// Resolves a relationship target against the directory of its source part
+fn resolve(dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => dir.split('/').filter(|s| !s.is_empty()).collect(),
+    };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Whether a node is an element with the given local name
+fn is(node: Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is(*n, name))
+}
+
+/// Attribute by local name, so both `w:val` and `r:id` style prefixes work
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|a| a.name() == name).map(|a| a.value())
+}
+
+fn child_val<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|n| attr(n, "val"))
+}
+
+/// Whether a toggle property such as `<w:b/>` is on; `w:val` may switch it off
+fn toggle(properties: Option<Node>, name: &str) -> bool {
+    properties
+        .and_then(|p| child(p, name))
+        .is_some_and(|n| !matches!(attr(n, "val"), Some("0" | "false" | "off" | "none")))
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn core_title(xml: &str) -> Option<String> {
+    let core = Xml::parse(xml).ok()?;
+    let title = child(core.root_element(), "title")?.text()?.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[derive(Default)]
+struct Style {
+    name: String,
+    based_on: Option<String>,
+    outline_level: Option<u8>,
+    num_id: Option<String>,
+}
+
+fn parse_styles(xml: &str) -> Result<HashMap<String, Style>, String> {
+    let styles = Xml::parse(xml).map_err(|e| format!("Invalid styles: {}", e))?;
+    Ok(styles
+        .root_element()
+        .children()
+        .filter(|n| is(*n, "style") && attr(*n, "type") == Some("paragraph"))
+        .filter_map(|n| {
+            let properties = child(n, "pPr");
+            let style = Style {
+                name: child_val(n, "name").unwrap_or_default().to_ascii_lowercase(),
+                based_on: child_val(n, "basedOn").map(String::from),
+                outline_level: properties.and_then(|p| child_val(p, "outlineLvl")).and_then(|v| v.parse().ok()),
+                num_id: properties.and_then(|p| child(p, "numPr")).and_then(|p| child_val(p, "numId")).map(String::from),
+            };
+            Some((attr(n, "styleId")?.to_string(), style))
+        })
+        .collect())
+}
+
+/// Whether each level of each numbering is ordered, keyed by `numId`
+fn parse_numbering(xml: &str) -> Result<HashMap<String, HashMap<u32, bool>>, String> {
+    let numbering = Xml::parse(xml).map_err(|e| format!("Invalid numbering: {}", e))?;
+    let root = numbering.root_element();
+
+    let abstract_levels: HashMap<&str, HashMap<u32, bool>> = root
+        .children()
+        .filter(|n| is(*n, "abstractNum"))
+        .filter_map(|n| {
+            let levels = n
+                .children()
+                .filter(|l| is(*l, "lvl"))
+                .filter_map(|l| {
+                    let level = attr(l, "ilvl")?.parse().ok()?;
+                    let format = child_val(l, "numFmt").unwrap_or("bullet");
+                    Some((level, !matches!(format, "bullet" | "none")))
+                })
+                .collect();
+            Some((attr(n, "abstractNumId")?, levels))
+        })
+        .collect();
+
+    Ok(root
+        .children()
+        .filter(|n| is(*n, "num"))
+        .filter_map(|n| {
+            let levels = abstract_levels.get(child_val(n, "abstractNumId")?)?.clone();
+            Some((attr(n, "numId")?.to_string(), levels))
+        })
+        .collect())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Paragraph,
+    Heading(u8),
+    Quote,
+    Code,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VerticalMerge {
+    None,
+    Restart,
+    Continue,
+}
+
+struct Cell<'a, 'input> {
+    node: Node<'a, 'input>,
+    column: usize,
+    span: usize,
+    merge: VerticalMerge,
+}
+
+struct Converter<'a> {
+    package: Package<'a>,
+    relationships: HashMap<String, Relationship>,
+    styles: HashMap<String, Style>,
+    numbering: HashMap<String, HashMap<u32, bool>>,
+    unsupported: BTreeSet<&'static str>,
+    /// Inside a header cell, which is bold already, so bold runs are not marked
+    in_header: bool,
+}
+
+impl Converter<'_> {
+    /// Styles from `style_id` up its `basedOn` chain
+    fn style_chain(&self, style_id: Option<&str>) -> Vec<&Style> {
+        let mut chain = Vec::new();
+        let mut next = style_id;
+        while let Some(style) = next.and_then(|id| self.styles.get(id)) {
+            chain.push(style);
+            if chain.len() == MAX_STYLE_DEPTH {
+                break;
+            }
+            next = style.based_on.as_deref();
+        }
+        chain
+    }
+
+    fn block_kind(&self, properties: Option<Node>) -> BlockKind {
+        let style_id = properties.and_then(|p| child_val(p, "pStyle"));
+        let direct_level = properties.and_then(|p| child_val(p, "outlineLvl")).and_then(|v| v.parse::<u8>().ok());
+        if let Some(level) = direct_level.filter(|l| *l < 6) {
+            return BlockKind::Heading(level + 1);
+        }
+
+        for style in self.style_chain(style_id) {
+            if style.name == "title" {
+                return BlockKind::Heading(1);
+            }
+            if let Some(level) = style.name.strip_prefix("heading ").and_then(|l| l.parse::<u8>().ok()) {
+                return BlockKind::Heading(level.clamp(1, 6));
+            }
+            if let Some(level) = style.outline_level.filter(|l| *l < 6) {
+                return BlockKind::Heading(level + 1);
+            }
+            if style.name.contains("quote") {
+                return BlockKind::Quote;
+            }
+            if style.name.contains("code") || style.name == "html preformatted" {
+                return BlockKind::Code;
+            }
+        }
+        BlockKind::Paragraph
+    }
+
+    /// List level, whether it is ordered and the numbering instance, for a numbered paragraph
+    fn list_item(&self, properties: Option<Node>) -> Option<(usize, bool, String)> {
+        let numbering = properties.and_then(|p| child(p, "numPr"));
+        let num_id = numbering.and_then(|n| child_val(n, "numId")).map(String::from).or_else(|| {
+            let style_id = properties.and_then(|p| child_val(p, "pStyle"));
+            self.style_chain(style_id).into_iter().find_map(|s| s.num_id.clone())
+        })?;
+        // numId 0 removes numbering inherited from the style
+        if num_id == "0" {
+            return None;
+        }
+        let level = numbering.and_then(|n| child_val(n, "ilvl")).and_then(|v| v.parse().ok()).unwrap_or(0u32);
+        let ordered = self.numbering.get(&num_id).and_then(|levels| levels.get(&level)).copied().unwrap_or(false);
+        Some((level.min(8) as usize, ordered, num_id))
+    }
+
+    /// Converts the block content of a body, table cell or content control
+    fn blocks(&mut self, parent: Node) -> String {
+        let mut html = String::new();
+        // Open lists with their numbering instance, innermost last, each with an open <li>
+        let mut lists: Vec<(bool, String)> = Vec::new();
+
+        for node in block_nodes(parent) {
+            let item = if is(node, "p") { self.list_item(child(node, "pPr")) } else { None };
+            let heading = is(node, "p") && matches!(self.block_kind(child(node, "pPr")), BlockKind::Heading(_));
+
+            match item.filter(|_| !heading) {
+                Some((level, ordered, num_id)) => {
+                    // A different numbering instance at the same level starts a new list
+                    while lists.len() > level + 1 || (lists.len() == level + 1 && lists[level] != (ordered, num_id.clone())) {
+                        close_list(&mut html, lists.pop());
+                    }
+                    if lists.len() == level + 1 {
+                        html.push_str("</li><li>");
+                    }
+                    while lists.len() < level + 1 {
+                        html.push_str(if ordered { "<ol><li>" } else { "<ul><li>" });
+                        lists.push((ordered, num_id.clone()));
+                    }
+                    html.push_str(&format!("<p>{}</p>", self.inline(node)));
+                    continue;
+                }
+                // An indented paragraph without a number continues the open item
+                None if !lists.is_empty() && !heading && is(node, "p") && indented(child(node, "pPr")) => {
+                    html.push_str(&self.paragraph(node));
+                    continue;
+                }
+                None => {
+                    while !lists.is_empty() {
+                        close_list(&mut html, lists.pop());
+                    }
+                }
+            }
+
+            match node.tag_name().name() {
+                "p" => {
+                    let paragraph = self.paragraph(node);
+                    // Word keeps one paragraph per line of code; join them into one block
+                    match paragraph.strip_prefix("<pre><code>") {
+                        Some(code) if html.ends_with("</code></pre>") => {
+                            html.truncate(html.len() - "</code></pre>".len());
+                            html.push('\n');
+                            html.push_str(code);
+                        }
+                        _ => html.push_str(&paragraph),
+                    }
+                }
+                "tbl" => html.push_str(&self.table(node)),
+                "sectPr" if node.children().any(|n| is(n, "headerReference") || is(n, "footerReference")) => {
+                    self.unsupported.insert("headers and footers");
+                }
+                "altChunk" => {
+                    self.unsupported.insert("embedded documents");
+                }
+                _ => {}
+            }
+        }
+        while !lists.is_empty() {
+            close_list(&mut html, lists.pop());
+        }
+        html
+    }
+
+    fn paragraph(&mut self, node: Node) -> String {
+        let content = self.inline(node);
+        match self.block_kind(child(node, "pPr")) {
+            BlockKind::Paragraph => format!("<p>{}</p>", content),
+            BlockKind::Heading(level) => format!("<h{0}>{1}</h{0}>", level, content),
+            BlockKind::Quote => format!("<blockquote><p>{}</p></blockquote>", content),
+            BlockKind::Code => format!("<pre><code>{}</code></pre>", content.replace("<br>", "\n")),
+        }
+    }
+
+    fn table(&mut self, node: Node) -> String {
+        let rows: Vec<(bool, Vec<Cell>)> = node
+            .children()
+            .filter(|n| is(*n, "tr"))
+            .map(|row| {
+                let header = child(row, "trPr").is_some_and(|p| child(p, "tblHeader").is_some());
+                let mut column = 0;
+                let cells = row
+                    .children()
+                    .filter(|n| is(*n, "tc"))
+                    .map(|cell| {
+                        let properties = child(cell, "tcPr");
+                        let span = properties.and_then(|p| child_val(p, "gridSpan")).and_then(|v| v.parse().ok()).unwrap_or(1usize).max(1);
+                        let merge = match properties.and_then(|p| child(p, "vMerge")) {
+                            Some(merge) if attr(merge, "val") == Some("restart") => VerticalMerge::Restart,
+                            Some(_) => VerticalMerge::Continue,
+                            None => VerticalMerge::None,
+                        };
+                        let cell = Cell { node: cell, column, span, merge };
+                        column += span;
+                        cell
+                    })
+                    .collect();
+                (header, cells)
+            })
+            .collect();
+
+        let mut html = String::from("<table><tbody>");
+        for (index, (header, cells)) in rows.iter().enumerate() {
+            html.push_str("<tr>");
+            for cell in cells {
+                if cell.merge == VerticalMerge::Continue {
+                    continue;
+                }
+                let tag = if *header { "th" } else { "td" };
+                html.push('<');
+                html.push_str(tag);
+                if cell.span > 1 {
+                    html.push_str(&format!(" colspan=\"{}\"", cell.span));
+                }
+                if cell.merge == VerticalMerge::Restart {
+                    let merged = rows[index + 1..]
+                        .iter()
+                        .take_while(|(_, cells)| {
+                            cells.iter().any(|c| c.column == cell.column && c.merge == VerticalMerge::Continue)
+                        })
+                        .count();
+                    if merged > 0 {
+                        html.push_str(&format!(" rowspan=\"{}\"", merged + 1));
+                    }
+                }
+                html.push('>');
+                let in_header = std::mem::replace(&mut self.in_header, *header);
+                let content = self.blocks(cell.node);
+                self.in_header = in_header;
+                html.push_str(if content.is_empty() { "<p></p>" } else { &content });
+                html.push_str(&format!("</{}>", tag));
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</tbody></table>");
+        html
+    }
+
+    /// Converts the runs, links and fields of a paragraph
+    fn inline(&mut self, parent: Node) -> String {
+        let mut html = String::new();
+        for node in parent.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "r" => html.push_str(&self.run(node)),
+                "hyperlink" => {
+                    let href = match attr(node, "id").and_then(|id| self.relationships.get(id)) {
+                        Some(relationship) if relationship.external => Some(relationship.target.clone()),
+                        _ => attr(node, "anchor").map(|anchor| format!("#{}", anchor)),
+                    };
+                    let content = self.inline(node);
+                    match href {
+                        Some(href) => html.push_str(&format!("<a href=\"{}\">{}</a>", escape(&href), content)),
+                        None => html.push_str(&content),
+                    }
+                }
+                "ins" | "moveTo" => {
+                    self.unsupported.insert("tracked changes");
+                    html.push_str(&self.inline(node));
+                }
+                "del" | "moveFrom" => {
+                    self.unsupported.insert("tracked changes");
+                }
+                "smartTag" | "customXml" | "fldSimple" | "dir" | "bdo" => html.push_str(&self.inline(node)),
+                "sdt" => {
+                    if let Some(content) = child(node, "sdtContent") {
+                        html.push_str(&self.inline(content));
+                    }
+                }
+                "commentRangeStart" => {
+                    self.unsupported.insert("comments");
+                }
+                "oMath" | "oMathPara" => {
+                    self.unsupported.insert("equations");
+                }
+                _ => {}
+            }
+        }
+        html
+    }
+
+    fn run(&mut self, run: Node) -> String {
+        let properties = child(run, "rPr");
+        if toggle(properties, "vanish") {
+            return String::new();
+        }
+
+        let mut text = String::new();
+        for node in run.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "t" => text.push_str(&escape(node.text().unwrap_or_default())),
+                "tab" => text.push('\t'),
+                "br" if attr(node, "type").is_some_and(|t| t != "textWrapping") => {}
+                "br" | "cr" => text.push_str("<br>"),
+                "noBreakHyphen" | "softHyphen" => text.push('-'),
+                "drawing" | "pict" => text.push_str(&self.images(node)),
+                "AlternateContent" => {
+                    if let Some(fallback) = child(node, "Fallback") {
+                        text.push_str(&self.images(fallback));
+                    }
+                    if node.descendants().any(|n| is(n, "txbxContent")) {
+                        self.unsupported.insert("text boxes");
+                    }
+                }
+                "object" => {
+                    self.unsupported.insert("embedded objects");
+                }
+                "footnoteReference" | "endnoteReference" => {
+                    self.unsupported.insert("footnotes and endnotes");
+                }
+                "commentReference" => {
+                    self.unsupported.insert("comments");
+                }
+                "sym" => {
+                    self.unsupported.insert("symbol characters");
+                }
+                _ => {}
+            }
+        }
+        if text.is_empty() {
+            return text;
+        }
+
+        if toggle(properties, "u") {
+            self.unsupported.insert("underline");
+        }
+        if properties.and_then(|p| child_val(p, "vertAlign")).is_some_and(|v| v != "baseline") {
+            self.unsupported.insert("superscript and subscript");
+        }
+        if properties.is_some_and(|p| child(p, "highlight").is_some() || child_val(p, "color").is_some_and(|c| c != "auto")) {
+            self.unsupported.insert("text colours and highlighting");
+        }
+
+        let code = properties
+            .and_then(|p| child_val(p, "rStyle"))
+            .is_some_and(|s| s.to_ascii_lowercase().contains("code"));
+        if code {
+            text = format!("<code>{}</code>", text);
+        }
+        if toggle(properties, "strike") || toggle(properties, "dstrike") {
+            text = format!("<s>{}</s>", text);
+        }
+        if toggle(properties, "i") {
+            text = format!("<em>{}</em>", text);
+        }
+        if toggle(properties, "b") && !self.in_header {
+            text = format!("<strong>{}</strong>", text);
+        }
+        text
+    }
+
+    /// Inlines the pictures of a drawing as data URLs
+    fn images(&mut self, node: Node) -> String {
+        let alt = node
+            .descendants()
+            .find(|n| is(*n, "docPr"))
+            .and_then(|n| attr(n, "descr").or_else(|| attr(n, "title")))
+            .unwrap_or_default()
+            .to_string();
+
+        let mut html = String::new();
+        // DrawingML pictures reference the image with r:embed, VML ones with r:id
+        for reference in node.descendants().filter(|n| is(*n, "blip") || is(*n, "imagedata")) {
+            if attr(reference, "link").is_some() {
+                self.unsupported.insert("linked images");
+                continue;
+            }
+            let Some(target) = attr(reference, "embed")
+                .or_else(|| attr(reference, "id"))
+                .and_then(|id| self.relationships.get(id))
+                .filter(|r| !r.external)
+                .map(|r| r.target.clone())
+            else {
+                continue;
+            };
+
+            let mime = match target.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).as_deref() {
+                Some("png") => "image/png",
+                Some("jpg" | "jpeg") => "image/jpeg",
+                Some("gif") => "image/gif",
+                Some("bmp") => "image/bmp",
+                Some("webp") => "image/webp",
+                Some("svg") => "image/svg+xml",
+                _ => {
+                    self.unsupported.insert("EMF, WMF and TIFF images");
+                    continue;
+                }
+            };
+            match self.package.read(&target) {
+                Ok(Some(bytes)) => html.push_str(&format!(
+                    "<img src=\"data:{};base64,{}\" alt=\"{}\">",
+                    mime,
+                    general_purpose::STANDARD.encode(bytes),
+                    escape(&alt)
+                )),
+                Ok(None) => log::warn!("Image {} is missing from the package", target),
+                Err(e) => log::warn!("Skipping image: {}", e),
+            }
+        }
+        if html.is_empty() && node.descendants().any(|n| is(n, "chart")) {
+            self.unsupported.insert("charts");
+        }
+        html
+    }
+}
+
+/// Block-level children, with content controls and custom XML unwrapped
+fn block_nodes<'a, 'input>(parent: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+    let mut nodes = Vec::new();
+    for node in parent.children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "sdt" => {
+                if let Some(content) = child(node, "sdtContent") {
+                    nodes.extend(block_nodes(content));
+                }
+            }
+            "customXml" => nodes.extend(block_nodes(node)),
+            _ => nodes.push(node),
+        }
+    }
+    nodes
+}
+
+fn indented(properties: Option<Node>) -> bool {
+    properties
+        .and_then(|p| child(p, "ind"))
+        .and_then(|ind| attr(ind, "left").or_else(|| attr(ind, "start")))
+        .and_then(|left| left.parse::<i32>().ok())
+        .is_some_and(|left| left > 0)
+}
+
+fn close_list(html: &mut String, list: Option<(bool, String)>) {
+    match list {
+        Some((true, _)) => html.push_str("</li></ol>"),
+        Some((false, _)) => html.push_str("</li></ul>"),
+        None => {}
+    }
+}
+
+const WORD_NAMESPACES: &str = concat!(
+    r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+    r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" "#,
+    r#"xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" "#,
+    r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" "#,
+    r#"xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture""#
+);
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+const RELATIONSHIP_TYPES: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Text width of a Letter page with one-inch margins, in twentieths of a point
+const TEXT_WIDTH_TWIPS: u32 = 9360;
+/// Images wider than the text are scaled down to it; 6.5in at 96 dpi
+const MAX_IMAGE_WIDTH_PX: u32 = 624;
+/// Size of images whose dimensions cannot be read
+const DEFAULT_IMAGE_SIZE_PX: (u32, u32) = (400, 300);
+const EMU_PER_PX: u64 = 9525;
+/// Indentation per list level, in twentieths of a point
+const LIST_INDENT_TWIPS: u32 = 720;
+
+const BULLETS: [&str; 3] = ["\u{2022}", "\u{25E6}", "\u{25AA}"];
+const NUMBER_FORMATS: [&str; 3] = ["decimal", "lowerLetter", "lowerRoman"];
+
+lazy_static::lazy_static! {
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+/// Metadata written to the core properties of an exported file
+pub struct DocxMetadata<'a> {
+    pub title: &'a str,
+    /// Name of the project the document belongs to
+    pub subject: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub creator: Option<&'a str>,
+    /// W3C date-times, such as the `createdAt` of a document
+    pub created: &'a str,
+    pub modified: &'a str,
+}
+
+/// Converts editor HTML to a .docx file. Images are taken from data URLs, or
+/// from `images` by their `src` for remote ones; others are left out.
+pub fn export(html: &str, metadata: &DocxMetadata, images: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>, String> {
+    let fragment = Html::parse_fragment(html);
+    let mut writer = Writer {
+        images,
+        relationships: Vec::new(),
+        media: Vec::new(),
+        lists: Vec::new(),
+        drawings: 0,
+    };
+    let mut body = writer.blocks(fragment.root_element(), &mut Context::default());
+    if body.is_empty() {
+        body.push_str("<w:p/>");
+    }
+
+    let document = format!(
+        "{}<w:document {}><w:body>{}<w:sectPr><w:pgSz w:w=\"12240\" w:h=\"15840\"/>\
+         <w:pgMar w:top=\"1440\" w:right=\"1440\" w:bottom=\"1440\" w:left=\"1440\" w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/>\
+         </w:sectPr></w:body></w:document>",
+        XML_DECLARATION, WORD_NAMESPACES, body
+    );
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut parts = vec![
+        ("[Content_Types].xml".to_string(), content_types().into_bytes()),
+        ("_rels/.rels".to_string(), package_relationships().into_bytes()),
+        ("docProps/core.xml".to_string(), core_properties(metadata).into_bytes()),
+        ("docProps/app.xml".to_string(), app_properties().into_bytes()),
+        ("word/document.xml".to_string(), document.into_bytes()),
+        ("word/_rels/document.xml.rels".to_string(), writer.document_relationships().into_bytes()),
+        ("word/styles.xml".to_string(), styles().into_bytes()),
+        ("word/numbering.xml".to_string(), writer.numbering().into_bytes()),
+    ];
+    parts.extend(writer.media.into_iter().map(|(name, bytes)| (format!("word/{}", name), bytes)));
+    for (name, bytes) in parts {
+        zip.start_file(name.as_str(), options)
+            .and_then(|_| zip.write_all(&bytes).map_err(Into::into))
+            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    }
+    zip.finish().map(Cursor::into_inner).map_err(|e| e.to_string())
+}
+
+fn content_types() -> String {
+    format!(
+        "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+         <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+         <Default Extension=\"png\" ContentType=\"image/png\"/>\
+         <Default Extension=\"jpeg\" ContentType=\"image/jpeg\"/>\
+         <Default Extension=\"gif\" ContentType=\"image/gif\"/>\
+         <Default Extension=\"bmp\" ContentType=\"image/bmp\"/>\
+         <Override PartName=\"/word/document.xml\" ContentType=\"{}\"/>\
+         <Override PartName=\"/word/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml\"/>\
+         <Override PartName=\"/word/numbering.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml\"/>\
+         <Override PartName=\"/docProps/core.xml\" ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>\
+         <Override PartName=\"/docProps/app.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.extended-properties+xml\"/>\
+         </Types>",
+        XML_DECLARATION, MAIN_DOCUMENT_TYPE
+    )
+}
+
+fn package_relationships() -> String {
+    format!(
+        "{}<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+         <Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"word/document.xml\"/>\
+         <Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties\" Target=\"docProps/core.xml\"/>\
+         <Relationship Id=\"rId3\" Type=\"{}/extended-properties\" Target=\"docProps/app.xml\"/>\
+         </Relationships>",
+        XML_DECLARATION, RELATIONSHIP_TYPES, RELATIONSHIP_TYPES
+    )
+}
+
+fn core_properties(metadata: &DocxMetadata) -> String {
+    let optional = |tag: &str, value: Option<&str>| match value.filter(|v| !v.trim().is_empty()) {
+        Some(value) => format!("<{0}>{1}</{0}>", tag, escape(value)),
+        None => String::new(),
+    };
+    let date = |value: &str| if value.is_empty() { timestamp::now_iso8601() } else { escape(value) };
+    format!(
+        "{}<cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+         <dc:title>{}</dc:title>{}{}{}{}\
+         <dcterms:created xsi:type=\"dcterms:W3CDTF\">{}</dcterms:created>\
+         <dcterms:modified xsi:type=\"dcterms:W3CDTF\">{}</dcterms:modified>\
+         </cp:coreProperties>",
+        XML_DECLARATION,
+        escape(metadata.title),
+        optional("dc:subject", metadata.subject),
+        optional("dc:description", metadata.description),
+        optional("dc:creator", metadata.creator),
+        optional("cp:lastModifiedBy", metadata.creator),
+        date(metadata.created),
+        date(metadata.modified)
+    )
+}
+
+fn app_properties() -> String {
+    format!(
+        "{}<Properties xmlns=\"http://schemas.openxmlformats.org/officeDocument/2006/extended-properties\">\
+         <Application>Editron</Application></Properties>",
+        XML_DECLARATION
+    )
+}
+
+/// Styles the importer maps back: headings by name, Quote and Code paragraphs
+fn styles() -> String {
+    let headings: String = [32, 26, 24, 22, 22, 22]
+        .iter()
+        .enumerate()
+        .map(|(i, size)| {
+            format!(
+                "<w:style w:type=\"paragraph\" w:styleId=\"Heading{0}\"><w:name w:val=\"heading {0}\"/>\
+                 <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/><w:qFormat/>\
+                 <w:pPr><w:keepNext/><w:spacing w:before=\"240\" w:after=\"80\"/><w:outlineLvl w:val=\"{1}\"/></w:pPr>\
+                 <w:rPr><w:b/><w:sz w:val=\"{2}\"/></w:rPr></w:style>",
+                i + 1,
+                i,
+                size
+            )
+        })
+        .collect();
+    format!(
+        "{}<w:styles {}>\
+         <w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii=\"Calibri\" w:hAnsi=\"Calibri\" w:cs=\"Calibri\"/>\
+         <w:sz w:val=\"22\"/></w:rPr></w:rPrDefault>\
+         <w:pPrDefault><w:pPr><w:spacing w:after=\"160\" w:line=\"259\" w:lineRule=\"auto\"/></w:pPr></w:pPrDefault></w:docDefaults>\
+         <w:style w:type=\"paragraph\" w:default=\"1\" w:styleId=\"Normal\"><w:name w:val=\"Normal\"/><w:qFormat/></w:style>\
+         {}\
+         <w:style w:type=\"paragraph\" w:styleId=\"Quote\"><w:name w:val=\"Quote\"/><w:basedOn w:val=\"Normal\"/>\
+         <w:pPr><w:ind w:left=\"720\" w:right=\"720\"/></w:pPr><w:rPr><w:i/><w:color w:val=\"404040\"/></w:rPr></w:style>\
+         <w:style w:type=\"paragraph\" w:styleId=\"Code\"><w:name w:val=\"Code\"/><w:basedOn w:val=\"Normal\"/>\
+         <w:pPr><w:spacing w:after=\"0\" w:line=\"240\" w:lineRule=\"auto\"/></w:pPr>\
+         <w:rPr><w:rFonts w:ascii=\"Consolas\" w:hAnsi=\"Consolas\" w:cs=\"Consolas\"/><w:sz w:val=\"20\"/></w:rPr></w:style>\
+         <w:style w:type=\"character\" w:default=\"1\" w:styleId=\"DefaultParagraphFont\"><w:name w:val=\"Default Paragraph Font\"/></w:style>\
+         <w:style w:type=\"character\" w:styleId=\"Hyperlink\"><w:name w:val=\"Hyperlink\"/><w:basedOn w:val=\"DefaultParagraphFont\"/>\
+         <w:rPr><w:color w:val=\"0563C1\"/><w:u w:val=\"single\"/></w:rPr></w:style>\
+         <w:style w:type=\"character\" w:styleId=\"CodeChar\"><w:name w:val=\"Code Char\"/><w:basedOn w:val=\"DefaultParagraphFont\"/>\
+         <w:rPr><w:rFonts w:ascii=\"Consolas\" w:hAnsi=\"Consolas\" w:cs=\"Consolas\"/></w:rPr></w:style>\
+         <w:style w:type=\"table\" w:default=\"1\" w:styleId=\"TableNormal\"><w:name w:val=\"Normal Table\"/>\
+         <w:tblPr><w:tblCellMar><w:left w:w=\"108\" w:type=\"dxa\"/><w:right w:w=\"108\" w:type=\"dxa\"/></w:tblCellMar></w:tblPr></w:style>\
+         <w:style w:type=\"table\" w:styleId=\"TableGrid\"><w:name w:val=\"Table Grid\"/><w:basedOn w:val=\"TableNormal\"/>\
+         <w:pPr><w:spacing w:after=\"0\"/></w:pPr><w:tblPr><w:tblBorders>\
+         <w:top w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/><w:left w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/>\
+         <w:bottom w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/><w:right w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/>\
+         <w:insideH w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/><w:insideV w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/>\
+         </w:tblBorders></w:tblPr></w:style>\
+         </w:styles>",
+        XML_DECLARATION, WORD_NAMESPACES, headings
+    )
+}
+
+/// Character formatting in effect for a run
+#[derive(Clone, Copy, Default)]
+struct Marks {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    underline: bool,
+    code: bool,
+    superscript: bool,
+    subscript: bool,
+    link: bool,
+}
+
+/// Where paragraphs of a block container are written
+#[derive(Clone, Copy, Default)]
+struct Context {
+    style: Option<&'static str>,
+    /// Numbering instance and level of the list item being written
+    list: Option<(usize, usize)>,
+    /// Whether the next paragraph starts the list item and carries its number
+    item_start: bool,
+    marks: Marks,
+}
+
+/// A list instance in numbering.xml
+struct List {
+    ordered: bool,
+    level: usize,
+    start: u32,
+}
+
+struct Writer<'a> {
+    images: &'a HashMap<String, Vec<u8>>,
+    /// Hyperlink and image relationships of the document, after the fixed ones
+    relationships: Vec<(String, String, bool)>,
+    /// Media parts, named relative to `word/`
+    media: Vec<(String, Vec<u8>)>,
+    lists: Vec<List>,
+    drawings: u32,
+}
+
+/// Relationship ids taken by styles.xml and numbering.xml
+const FIXED_RELATIONSHIPS: usize = 2;
+
+impl Writer<'_> {
+    fn relationship(&mut self, kind: &str, target: String, external: bool) -> String {
+        self.relationships.push((kind.to_string(), target, external));
+        format!("rId{}", FIXED_RELATIONSHIPS + self.relationships.len())
+    }
+
+    fn document_relationships(&self) -> String {
+        let mut xml = format!(
+            "{}<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+             <Relationship Id=\"rId1\" Type=\"{1}/styles\" Target=\"styles.xml\"/>\
+             <Relationship Id=\"rId2\" Type=\"{1}/numbering\" Target=\"numbering.xml\"/>",
+            XML_DECLARATION, RELATIONSHIP_TYPES
+        );
+        for (i, (kind, target, external)) in self.relationships.iter().enumerate() {
+            xml.push_str(&format!(
+                "<Relationship Id=\"rId{}\" Type=\"{}/{}\" Target=\"{}\"{}/>",
+                FIXED_RELATIONSHIPS + i + 1,
+                RELATIONSHIP_TYPES,
+                kind,
+                escape(target),
+                if *external { " TargetMode=\"External\"" } else { "" }
+            ));
+        }
+        xml.push_str("</Relationships>");
+        xml
+    }
+
+    /// One bullet and one numbered definition, instantiated per list so each
+    /// numbered list starts over
+    fn numbering(&self) -> String {
+        let levels = |ordered: bool| -> String {
+            (0..9)
+                .map(|level| {
+                    let (format, text) = if ordered {
+                        (NUMBER_FORMATS[level % 3], format!("%{}.", level + 1))
+                    } else {
+                        ("bullet", BULLETS[level % 3].to_string())
+                    };
+                    format!(
+                        "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/><w:lvlText w:val=\"{}\"/>\
+                         <w:lvlJc w:val=\"left\"/><w:pPr><w:ind w:left=\"{}\" w:hanging=\"360\"/></w:pPr></w:lvl>",
+                        level,
+                        format,
+                        text,
+                        LIST_INDENT_TWIPS * (level as u32 + 1)
+                    )
+                })
+                .collect()
+        };
+
+        let mut xml = format!(
+            "{}<w:numbering {}>\
+             <w:abstractNum w:abstractNumId=\"0\"><w:multiLevelType w:val=\"hybridMultilevel\"/>{}</w:abstractNum>\
+             <w:abstractNum w:abstractNumId=\"1\"><w:multiLevelType w:val=\"hybridMultilevel\"/>{}</w:abstractNum>",
+            XML_DECLARATION,
+            WORD_NAMESPACES,
+            levels(false),
+            levels(true)
+        );
+        for (i, list) in self.lists.iter().enumerate() {
+            xml.push_str(&format!("<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>", i + 1, list.ordered as u8));
+            if list.ordered {
+                xml.push_str(&format!(
+                    "<w:lvlOverride w:ilvl=\"{}\"><w:startOverride w:val=\"{}\"/></w:lvlOverride>",
+                    list.level, list.start
+                ));
+            }
+            xml.push_str("</w:num>");
+        }
+        xml.push_str("</w:numbering>");
+        xml
+    }
+
+    /// Converts the children of a block container. Loose inline content is
+    /// gathered into paragraphs of its own.
+    fn blocks(&mut self, parent: ElementRef, context: &mut Context) -> String {
+        let mut xml = String::new();
+        let mut loose = String::new();
+
+        for node in parent.children() {
+            let element = match node.value() {
+                HtmlNode::Text(text) => {
+                    if !loose.is_empty() || !text.trim().is_empty() {
+                        loose.push_str(&self.text(text, &context.marks));
+                    }
+                    continue;
+                }
+                HtmlNode::Element(_) => ElementRef::wrap(node).expect("element nodes wrap"),
+                _ => continue,
+            };
+
+            let name = element.value().name();
+            if !is_block(name) {
+                loose.push_str(&self.inline_element(element, &context.marks));
+                continue;
+            }
+            if !loose.trim().is_empty() {
+                xml.push_str(&self.paragraph(context, None, &std::mem::take(&mut loose)));
+            }
+            loose.clear();
+
+            match name {
+                "p" => {
+                    let runs = self.inline(element, &context.marks);
+                    xml.push_str(&self.paragraph(context, None, &runs));
+                }
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    let runs = self.inline(element, &context.marks);
+                    let style = ["Heading1", "Heading2", "Heading3", "Heading4", "Heading5", "Heading6"][name[1..].parse::<usize>().unwrap_or(1) - 1];
+                    xml.push_str(&self.paragraph(&mut Context { list: None, ..*context }, Some(style), &runs));
+                }
+                "ul" | "ol" => {
+                    let level = context.list.map_or(0, |(_, level)| (level + 1).min(8));
+                    xml.push_str(&self.list(element, name == "ol", level, context));
+                }
+                "blockquote" => {
+                    xml.push_str(&self.blocks(element, &mut Context { style: Some("Quote"), ..*context }));
+                }
+                "pre" => {
+                    let text: String = element.text().collect();
+                    let runs: Vec<String> = text
+                        .trim_end_matches('\n')
+                        .split('\n')
+                        .map(|line| self.run(line, &context.marks))
+                        .collect();
+                    xml.push_str(&self.paragraph(context, Some("Code"), &runs.join("<w:r><w:br/></w:r>")));
+                }
+                "table" => xml.push_str(&self.table(element)),
+                "hr" => xml.push_str(
+                    "<w:p><w:pPr><w:pBdr><w:bottom w:val=\"single\" w:sz=\"6\" w:space=\"1\" w:color=\"auto\"/></w:pBdr></w:pPr></w:p>",
+                ),
+                _ => xml.push_str(&self.blocks(element, context)),
+            }
+        }
+        if !loose.trim().is_empty() {
+            xml.push_str(&self.paragraph(context, None, &loose));
+        }
+        xml
+    }
+
+    fn paragraph(&mut self, context: &mut Context, style: Option<&str>, runs: &str) -> String {
+        let mut properties = String::new();
+        if let Some(style) = style.or(context.style) {
+            properties.push_str(&format!("<w:pStyle w:val=\"{}\"/>", style));
+        }
+        if let Some((num_id, level)) = context.list {
+            if std::mem::take(&mut context.item_start) {
+                properties.push_str(&format!("<w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>", level, num_id));
+            } else {
+                properties.push_str(&format!("<w:ind w:left=\"{}\"/>", LIST_INDENT_TWIPS * (level as u32 + 1)));
+            }
+        }
+        if properties.is_empty() {
+            format!("<w:p>{}</w:p>", runs)
+        } else {
+            format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", properties, runs)
+        }
+    }
+
+    fn list(&mut self, list: ElementRef, ordered: bool, level: usize, context: &Context) -> String {
+        let start = list.value().attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+        self.lists.push(List { ordered, level, start });
+        let num_id = self.lists.len();
+
+        let mut xml = String::new();
+        for item in list.children().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "li") {
+            let mut item_context = Context {
+                list: Some((num_id, level)),
+                item_start: true,
+                ..*context
+            };
+            let content = self.blocks(item, &mut item_context);
+            if item_context.item_start {
+                // Empty item: keep its number
+                xml.push_str(&self.paragraph(&mut item_context, None, ""));
+            }
+            xml.push_str(&content);
+        }
+        xml
+    }
+
+    fn table(&mut self, table: ElementRef) -> String {
+        let mut rows = Vec::new();
+        for child in table.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "tr" => rows.push((false, child)),
+                "thead" | "tbody" | "tfoot" => {
+                    let header = child.value().name() == "thead";
+                    rows.extend(child.children().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "tr").map(|tr| (header, tr)));
+                }
+                _ => {}
+            }
+        }
+
+        // Rows left to cover and column span of cells merged down from above, by column
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        let mut columns = 1;
+        let mut rows_xml = String::new();
+        for (in_head, row) in rows {
+            let cells: Vec<ElementRef> = row
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|e| matches!(e.value().name(), "td" | "th"))
+                .collect();
+            let header = in_head || (!cells.is_empty() && cells.iter().all(|c| c.value().name() == "th"));
+
+            let mut row_xml = String::from(if header { "<w:tr><w:trPr><w:tblHeader/></w:trPr>" } else { "<w:tr>" });
+            let mut column = 0;
+            let mut cells = cells.into_iter();
+            loop {
+                if let Some(&(remaining, span)) = merged.get(column).filter(|(remaining, _)| *remaining > 0) {
+                    merged[column].0 = remaining - 1;
+                    row_xml.push_str(&format!(
+                        "<w:tc><w:tcPr>{}<w:vMerge/></w:tcPr><w:p/></w:tc>",
+                        grid_span(span)
+                    ));
+                    column += span;
+                    continue;
+                }
+                let Some(cell) = cells.next() else {
+                    // Cells merged down past the end of a short row
+                    if merged.iter().skip(column).any(|(remaining, _)| *remaining > 0) {
+                        column += 1;
+                        continue;
+                    }
+                    break;
+                };
+
+                let span: usize = cell.value().attr("colspan").and_then(|s| s.parse().ok()).unwrap_or(1).clamp(1, 63);
+                let rowspan: usize = cell.value().attr("rowspan").and_then(|s| s.parse().ok()).unwrap_or(1).clamp(1, 1000);
+                if rowspan > 1 {
+                    if merged.len() < column + 1 {
+                        merged.resize(column + 1, (0, 1));
+                    }
+                    merged[column] = (rowspan - 1, span);
+                }
+
+                let mut cell_context = Context::default();
+                cell_context.marks.bold = header || cell.value().name() == "th";
+                let mut content = self.blocks(cell, &mut cell_context);
+                if content.is_empty() || content.ends_with("</w:tbl>") {
+                    content.push_str("<w:p/>");
+                }
+                row_xml.push_str(&format!(
+                    "<w:tc><w:tcPr>{}{}</w:tcPr>{}</w:tc>",
+                    grid_span(span),
+                    if rowspan > 1 { "<w:vMerge w:val=\"restart\"/>" } else { "" },
+                    content
+                ));
+                column += span;
+            }
+            columns = columns.max(column);
+            row_xml.push_str("</w:tr>");
+            rows_xml.push_str(&row_xml);
+        }
+
+        let width = TEXT_WIDTH_TWIPS / columns as u32;
+        format!(
+            "<w:tbl><w:tblPr><w:tblStyle w:val=\"TableGrid\"/><w:tblW w:w=\"5000\" w:type=\"pct\"/>\
+             <w:tblLook w:val=\"04A0\" w:firstRow=\"1\" w:lastRow=\"0\" w:firstColumn=\"0\" w:lastColumn=\"0\" w:noHBand=\"0\" w:noVBand=\"1\"/>\
+             </w:tblPr><w:tblGrid>{}</w:tblGrid>{}</w:tbl>",
+            format!("<w:gridCol w:w=\"{}\"/>", width).repeat(columns),
+            rows_xml
+        )
+    }
+
+    /// Converts the inline children of an element
+    fn inline(&mut self, parent: ElementRef, marks: &Marks) -> String {
+        let mut xml = String::new();
+        for node in parent.children() {
+            match node.value() {
+                HtmlNode::Text(text) => xml.push_str(&self.text(text, marks)),
+                HtmlNode::Element(_) => xml.push_str(&self.inline_element(ElementRef::wrap(node).expect("element nodes wrap"), marks)),
+                _ => {}
+            }
+        }
+        xml
+    }
+
+    fn inline_element(&mut self, element: ElementRef, marks: &Marks) -> String {
+        let mut marks = *marks;
+        match element.value().name() {
+            "strong" | "b" => marks.bold = true,
+            "em" | "i" => marks.italic = true,
+            "s" | "strike" | "del" => marks.strike = true,
+            "u" | "ins" => marks.underline = true,
+            "code" | "kbd" | "samp" => marks.code = true,
+            "sup" => marks.superscript = true,
+            "sub" => marks.subscript = true,
+            "br" => return "<w:r><w:br/></w:r>".to_string(),
+            "img" => return self.image(element),
+            "a" if !marks.link => {
+                let Some(href) = element.value().attr("href").filter(|h| !h.is_empty()) else {
+                    return self.inline(element, &marks);
+                };
+                marks.link = true;
+                let runs = self.inline(element, &marks);
+                return match href.strip_prefix('#') {
+                    Some(anchor) => format!("<w:hyperlink w:anchor=\"{}\">{}</w:hyperlink>", escape(anchor), runs),
+                    None => {
+                        let id = self.relationship("hyperlink", href.to_string(), true);
+                        format!("<w:hyperlink r:id=\"{}\" w:history=\"1\">{}</w:hyperlink>", id, runs)
+                    }
+                };
+            }
+            _ => {}
+        }
+        self.inline(element, &marks)
+    }
+
+    /// Text outside `<pre>`, with whitespace collapsed as a browser would
+    fn text(&self, text: &str, marks: &Marks) -> String {
+        let text = WHITESPACE.replace_all(text, " ");
+        if text.is_empty() {
+            return String::new();
+        }
+        self.run(&text, marks)
+    }
+
+    fn run(&self, text: &str, marks: &Marks) -> String {
+        let mut properties = String::new();
+        if marks.link {
+            properties.push_str("<w:rStyle w:val=\"Hyperlink\"/>");
+        } else if marks.code {
+            properties.push_str("<w:rStyle w:val=\"CodeChar\"/>");
+        }
+        if marks.bold {
+            properties.push_str("<w:b/>");
+        }
+        if marks.italic {
+            properties.push_str("<w:i/>");
+        }
+        if marks.strike {
+            properties.push_str("<w:strike/>");
+        }
+        if marks.underline {
+            properties.push_str("<w:u w:val=\"single\"/>");
+        }
+        if marks.superscript {
+            properties.push_str("<w:vertAlign w:val=\"superscript\"/>");
+        } else if marks.subscript {
+            properties.push_str("<w:vertAlign w:val=\"subscript\"/>");
+        }
+
+        // Control characters are not allowed in XML
+        let text: String = text.chars().filter(|c| !c.is_control() || *c == '\t').collect();
+        let text = escape(&text).replace('\t', "</w:t><w:tab/><w:t xml:space=\"preserve\">");
+        if properties.is_empty() {
+            format!("<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>", text)
+        } else {
+            format!("<w:r><w:rPr>{}</w:rPr><w:t xml:space=\"preserve\">{}</w:t></w:r>", properties, text)
+        }
+    }
+
+    fn image(&mut self, element: ElementRef) -> String {
+        let Some(src) = element.value().attr("src") else { return String::new() };
+        let bytes = match src.strip_prefix("data:") {
+            Some(data) => data
+                .split_once(";base64,")
+                .and_then(|(_, encoded)| general_purpose::STANDARD.decode(encoded.trim()).ok()),
+            None => self.images.get(src).cloned(),
+        };
+        let Some((bytes, extension)) = bytes.and_then(|b| image_extension(&b).map(|e| (b, e))) else {
+            log::warn!("Leaving out an image that could not be embedded");
+            return String::new();
+        };
+
+        let size_attr = |name: &str| element.value().attr(name).and_then(|v| v.trim_end_matches("px").parse::<u32>().ok());
+        let (mut width, mut height) = match (size_attr("width"), size_attr("height"), image_size(&bytes)) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, Some((w, h))) => (width, (h as u64 * width as u64 / w.max(1) as u64) as u32),
+            (None, Some(height), Some((w, h))) => ((w as u64 * height as u64 / h.max(1) as u64) as u32, height),
+            (_, _, Some(size)) => size,
+            _ => DEFAULT_IMAGE_SIZE_PX,
+        };
+        if width > MAX_IMAGE_WIDTH_PX {
+            height = (height as u64 * MAX_IMAGE_WIDTH_PX as u64 / width as u64) as u32;
+            width = MAX_IMAGE_WIDTH_PX;
+        }
+        let (cx, cy) = (width.max(1) as u64 * EMU_PER_PX, height.max(1) as u64 * EMU_PER_PX);
+
+        self.drawings += 1;
+        let id = self.drawings;
+        let name = format!("media/image{}.{}", id, extension);
+        let relationship = self.relationship("image", name.clone(), false);
+        self.media.push((name, bytes));
+        let alt = escape(element.value().attr("alt").unwrap_or_default());
+
+        format!(
+            "<w:r><w:drawing><wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">\
+             <wp:extent cx=\"{cx}\" cy=\"{cy}\"/><wp:docPr id=\"{id}\" name=\"Picture {id}\" descr=\"{alt}\"/>\
+             <wp:cNvGraphicFramePr><a:graphicFrameLocks noChangeAspect=\"1\"/></wp:cNvGraphicFramePr>\
+             <a:graphic><a:graphicData uri=\"http://schemas.openxmlformats.org/drawingml/2006/picture\"><pic:pic>\
+             <pic:nvPicPr><pic:cNvPr id=\"{id}\" name=\"image{id}.{extension}\"/><pic:cNvPicPr/></pic:nvPicPr>\
+             <pic:blipFill><a:blip r:embed=\"{relationship}\"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>\
+             <pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{cx}\" cy=\"{cy}\"/></a:xfrm>\
+             <a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></pic:spPr>\
+             </pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"
+        )
+    }
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "blockquote" | "pre" | "table" | "hr"
+            | "div" | "section" | "article" | "header" | "footer" | "main" | "aside" | "nav" | "figure" | "figcaption"
+    )
+}
+
+fn grid_span(span: usize) -> String {
+    if span > 1 {
+        format!("<w:gridSpan w:val=\"{}\"/>", span)
+    } else {
+        String::new()
+    }
+}
+
+/// File extension of the image formats Word displays, by signature
+fn image_extension(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xFF, 0xD8, ..] => Some("jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'B', b'M', ..] => Some("bmp"),
+        _ => None,
+    }
+}
+
+/// Pixel size of an image, read from its header
+fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]) as u32);
+    let be32 = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+    let le32 = |i: usize| Some(i32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?).unsigned_abs());
+
+    match image_extension(bytes)? {
+        "png" => Some((be32(16)?, be32(20)?)),
+        "gif" => Some((le16(6)?, le16(8)?)),
+        "bmp" => Some((le32(18)?, le32(22)?)),
+        _ => {
+            // JPEG: walk the segments to the start-of-frame marker
+            let mut i = 2;
+            while i + 9 < bytes.len() {
+                if bytes[i] != 0xFF {
+                    return None;
+                }
+                let marker = bytes[i + 1];
+                if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    return Some((be16(i + 7)?, be16(i + 5)?));
+                }
+                i += 2 + be16(i + 2)? as usize;
+            }
+            None
+        }
+    }
+}
+
+/// Sources of the remote images in `html`, to fetch before exporting
+fn remote_images(html: &str) -> Vec<String> {
+    let fragment = Html::parse_fragment(html);
+    let selector = Selector::parse("img[src]").expect("static selector");
+    fragment
+        .select(&selector)
+        .filter_map(|img| img.value().attr("src"))
+        .filter(|src| src.starts_with("http://") || src.starts_with("https://"))
+        .map(String::from)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Tauri command to convert a DOCX file locally, for a preview before upload.
+/// Fails with the same reasons the gateway would reject the file for.
+#[tauri::command]
+pub async fn preview_docx(file_name: String, contents: Vec<u8>) -> Result<DocxImport, String> {
+    log::info!("Converting {} ({} bytes) locally", file_name, contents.len());
+    tauri::async_runtime::spawn_blocking(move || import(&file_name, &contents))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Tauri command to replace the contents of a document with a DOCX file,
+/// converted locally and saved through the outbox, so it works offline.
+/// `baseVersion` is the `updatedAt` of the document the editor holds.
+#[tauri::command]
+pub async fn import_docx(
+    session: State<'_, SessionManager>,
+    shutdown: State<'_, Shutdown>,
+    library: State<'_, Library>,
+    outbox: State<'_, Outbox>,
+    project_uuid: String,
+    document_uuid: String,
+    base_version: String,
+    file_name: String,
+    contents: Vec<u8>,
+) -> Result<OutboxEntry, String> {
+    let _work = shutdown.track();
+    let account = account(&session)?;
+    let converted = tauri::async_runtime::spawn_blocking(move || import(&file_name, &contents))
+        .await
+        .map_err(|e| e.to_string())??;
+    log::info!("Importing a DOCX file into document {}", document_uuid);
+    outbox::queue(
+        &library,
+        &outbox,
+        &account,
+        &project_uuid,
+        &document_uuid,
+        &DocumentChange::Edit { content: converted.html },
+        &base_version,
+    )
+}
+
+/// Tauri command to export a document as a .docx file into the Downloads
+/// folder, named after the document, and return its path. The
+/// library copy with queued changes is used, so it works offline; the gateway
+/// is asked only for documents never opened on this device.
+#[tauri::command]
+pub async fn export_docx(
+    app: AppHandle,
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    request_id: String,
+    project_uuid: String,
+    document_uuid: String,
+) -> Result<RequestOutcome<String>, String> {
+    log::info!("Exporting document {} as DOCX", document_uuid);
+    let account = account(&session)?;
+
+    requests::run_cancellable(&request_id, async {
+        let document = library::current_document(&app, &account, &project_uuid, &document_uuid).await?;
+        let project = library.projects(&account)?.into_iter().find(|p| p.uuid == project_uuid);
+        let creator = session.current_profile().map(|profile| profile.name);
+        let destination = requests::download_destination(&app, &document.title, "docx")?;
+
+        let mut images = HashMap::new();
+        for src in remote_images(&document.content) {
+            let response = http_client::get_client().get(&src).send().await.and_then(|r| r.error_for_status());
+            match response {
+                Ok(response) => match response.bytes().await {
+                    Ok(bytes) => {
+                        images.insert(src, bytes.to_vec());
+                    }
+                    Err(e) => log::warn!("Failed to download image {}: {}", src, e),
+                },
+                Err(e) => log::warn!("Failed to download image {}: {}", src, e),
+            }
+        }
+
+        let bytes = tauri::async_runtime::spawn_blocking(move || {
+            let metadata = DocxMetadata {
+                title: &document.title,
+                subject: project.as_ref().map(|p| p.name.as_str()),
+                description: project.as_ref().and_then(|p| p.description.as_deref()),
+                creator: creator.as_deref(),
+                created: &document.created_at,
+                modified: &document.updated_at,
+            };
+            export(&document.content, &metadata, &images)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        requests::write_file(&destination, bytes).await.map_err(|e| {
+            log::error!("Failed to save DOCX: {}", e);
+            e
+        })?;
+
+        log::info!("DOCX saved to {:?}", destination);
+        Ok(destination.to_string_lossy().into_owned())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(title: &str) -> DocxMetadata<'_> {
+        DocxMetadata {
+            title,
+            subject: Some("Fixtures"),
+            description: None,
+            creator: Some("Editron"),
+            created: "2026-01-05T09:30:00.000Z",
+            modified: "2026-01-06T10:00:00.000Z",
+        }
+    }
+
+    /// Exports `html` and imports the file again
+    fn round_trip(html: &str) -> DocxImport {
+        let bytes = export(html, &metadata("Round trip"), &HashMap::new()).unwrap();
+        import("round-trip.docx", &bytes).unwrap()
+    }
+
+    fn assert_round_trip(html: &str) {
+        let imported = round_trip(html);
+        assert_eq!(imported.html, html);
+        assert!(imported.unsupported.is_empty(), "unsupported: {:?}", imported.unsupported);
+    }
+
+    #[test]
+    fn headings_round_trip() {
+        assert_round_trip("<h1>Report</h1><p>Intro</p><h2>Findings</h2><h3>Detail</h3><h4>Aside</h4>");
+    }
+
+    #[test]
+    fn bold_and_italic_round_trip() {
+        assert_round_trip(
+            "<p>Plain, <strong>bold</strong>, <em>italic</em>, <strong><em>both</em></strong> and <s>struck</s></p>",
+        );
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        assert_round_trip(
+            "<ul><li><p>one</p></li><li><p>two</p><ul><li><p>nested</p><ol><li><p>deeper</p></li></ol></li></ul></li></ul>\
+             <ol><li><p>first</p></li><li><p>second <strong>bold</strong></p></li></ol>",
+        );
+    }
+
+    #[test]
+    fn adjacent_lists_stay_separate() {
+        assert_round_trip("<ol><li><p>a</p></li></ol><p>between</p><ol><li><p>b</p></li></ol><ol><li><p>c</p></li></ol>");
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        assert_round_trip(
+            "<table><tbody><tr><th><p>Name</p></th><th><p>Value</p></th></tr>\
+             <tr><td colspan=\"2\"><p>spanning</p></td></tr>\
+             <tr><td rowspan=\"2\"><p>tall</p></td><td><p><em>x</em></p></td></tr>\
+             <tr><td><p><strong>y</strong></p></td></tr></tbody></table>",
+        );
+    }
+
+    #[test]
+    fn title_comes_from_the_core_properties() {
+        let bytes = export("<p>Body</p>", &metadata("Quarterly plan"), &HashMap::new()).unwrap();
+        assert_eq!(import("file-name.docx", &bytes).unwrap().title, "Quarterly plan");
+    }
+
+    #[test]
+    fn exported_files_pass_validation() {
+        let bytes = export("<p>Body</p>", &metadata("Valid"), &HashMap::new()).unwrap();
+        assert!(validate("valid.docx", &bytes).is_ok());
+        assert!(validate("valid.doc", &bytes).is_err());
+        assert!(validate("notes.docx", b"not a zip").is_err());
+    }
+}
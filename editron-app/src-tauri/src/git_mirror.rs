@@ -0,0 +1,509 @@
+use crate::api_types::{Document, Project};
+use crate::history::VersionSource;
+use crate::library::Library;
+use crate::locks::lock;
+use crate::session::{self, SessionManager};
+use crate::timestamp;
+use git2::{Repository, Signature};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+/// Project metadata and the file of each document, kept at the repository root
+const MANIFEST_FILE: &str = "editron-project.json";
+/// Trailer marking commits of agent edits the user accepted
+const AGENT_TRAILER: &str = "Editron-Agent-Edit: true";
+/// Longest file name stem derived from a document title
+const MAX_SLUG_LEN: usize = 60;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitMirrorConfig {
+    #[serde(rename = "projectUuid")]
+    pub project_uuid: String,
+    pub path: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ManifestDocument {
+    title: String,
+    file: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Manifest {
+    project: Option<Project>,
+    /// Keyed by document UUID
+    documents: BTreeMap<String, ManifestDocument>,
+}
+
+/// Opt-in mirror of projects into local git repositories: one HTML file per
+/// document plus a manifest, with a commit for every confirmed save so
+/// `git log` and `git diff` work on the user's writing. Which projects are
+/// mirrored, and where, is kept in the account's library database.
+#[derive(Default)]
+pub struct GitMirror {
+    lock: Mutex<()>,
+}
+
+fn mirror_config(library: &Library, account: &str, project_uuid: &str) -> Result<Option<GitMirrorConfig>, String> {
+    library.with_db(account, |conn| {
+        conn.query_row(
+            "SELECT project_uuid, path, created_at FROM git_mirrors WHERE project_uuid = ?1",
+            params![project_uuid],
+            |row| {
+                Ok(GitMirrorConfig {
+                    project_uuid: row.get(0)?,
+                    path: row.get(1)?,
+                    created_at: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )
+        .optional()
+    })
+}
+
+fn mirror_configs(library: &Library, account: &str) -> Result<Vec<GitMirrorConfig>, String> {
+    library.with_db(account, |conn| {
+        let mut stmt = conn.prepare("SELECT project_uuid, path, created_at FROM git_mirrors")?;
+        let configs = stmt
+            .query_map([], |row| {
+                Ok(GitMirrorConfig {
+                    project_uuid: row.get(0)?,
+                    path: row.get(1)?,
+                    created_at: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(configs)
+    })
+}
+
+fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= MAX_SLUG_LEN {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() { "untitled".to_string() } else { slug.to_string() }
+}
+
+/// File name for a document; the UUID is appended when another document uses the title
+fn file_name(manifest: &Manifest, document_uuid: &str, title: &str) -> String {
+    let name = format!("{}.html", slug(title));
+    let taken = manifest
+        .documents
+        .iter()
+        .any(|(uuid, doc)| uuid != document_uuid && doc.file == name);
+    if taken {
+        format!("{}-{}.html", slug(title), &document_uuid[..document_uuid.len().min(8)])
+    } else {
+        name
+    }
+}
+
+/// Whether a manifest entry names a file this mirror could have written. The
+/// manifest lives in the working tree, so an edited one must not be able to
+/// point writes or deletes outside the repository.
+fn is_mirror_file(file: &str) -> bool {
+    file.ends_with(".html") && file.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.') && !file.starts_with('.')
+}
+
+/// A mirror can be created in a new or empty directory, or in one that
+/// already holds an Editron mirror; anything else would be committed wholesale
+fn check_mirror_dir(root: &Path) -> Result<(), String> {
+    if !root.is_absolute() {
+        return Err("The mirror path must be absolute".to_string());
+    }
+    if !root.exists() {
+        return Ok(());
+    }
+    if !root.is_dir() {
+        return Err(format!("{:?} is not a folder", root));
+    }
+    if root.join(MANIFEST_FILE).is_file() {
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(root).map_err(|e| format!("Failed to read {:?}: {}", root, e))?;
+    match entries.next() {
+        None => Ok(()),
+        Some(_) => Err(format!("{:?} is not empty; choose a new or empty folder, or an existing Editron mirror", root)),
+    }
+}
+
+fn read_manifest(root: &Path) -> Manifest {
+    std::fs::read(root.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_manifest(root: &Path, manifest: &Manifest) -> Result<(), String> {
+    let bytes = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    std::fs::write(root.join(MANIFEST_FILE), bytes).map_err(|e| format!("Failed to write the project manifest: {}", e))
+}
+
+/// Writes a document into the working tree, moving its file when the title changed.
+/// A file it moved away from is added to `removed`. Returns the previous title
+/// when the document was already mirrored.
+fn write_document(root: &Path, manifest: &mut Manifest, document: &Document, removed: &mut Vec<String>) -> Result<Option<String>, String> {
+    let previous = manifest.documents.get(&document.uuid).cloned();
+    let file = match &previous {
+        Some(previous) if previous.title == document.title && is_mirror_file(&previous.file) => previous.file.clone(),
+        _ => file_name(manifest, &document.uuid, &document.title),
+    };
+    if let Some(previous) = previous.as_ref().filter(|p| p.file != file && is_mirror_file(&p.file)) {
+        let _ = std::fs::remove_file(root.join(&previous.file));
+        removed.push(previous.file.clone());
+    }
+    std::fs::write(root.join(&file), &document.content).map_err(|e| format!("Failed to write {}: {}", file, e))?;
+    manifest.documents.insert(document.uuid.clone(), ManifestDocument { title: document.title.clone(), file });
+    Ok(previous.map(|p| p.title))
+}
+
+fn signature(session: &SessionManager) -> Result<Signature<'static>, git2::Error> {
+    match session.current_profile() {
+        Some(profile) => Signature::now(&profile.name, &profile.email),
+        None => Signature::now("Editron", "editron@localhost"),
+    }
+}
+
+fn commit_message(previous_title: Option<&str>, title: &str, source: VersionSource) -> String {
+    let mut message = match previous_title {
+        None => format!("Add {}", title),
+        Some(previous) if previous != title => format!("Rename {} to {}", previous, title),
+        Some(_) => format!("Update {}", title),
+    };
+    if source == VersionSource::Agent {
+        message.push_str("\n\n");
+        message.push_str(AGENT_TRAILER);
+    }
+    message
+}
+
+/// Stages the manifest, the document files it lists and the files in `removed`,
+/// then commits unless nothing changed. Nothing else in the directory is staged.
+fn commit_mirror(repo: &Repository, manifest: &Manifest, removed: &[String], signature: &Signature, message: &str) -> Result<bool, git2::Error> {
+    let root = repo.workdir().ok_or_else(|| git2::Error::from_str("the mirror repository has no working tree"))?.to_path_buf();
+    let files = std::iter::once(MANIFEST_FILE)
+        .chain(manifest.documents.values().map(|doc| doc.file.as_str()).filter(|file| is_mirror_file(file)))
+        .chain(removed.iter().map(String::as_str));
+
+    let mut index = repo.index()?;
+    for file in files {
+        if root.join(file).is_file() {
+            index.add_path(Path::new(file))?;
+        } else if index.get_path(Path::new(file), 0).is_some() {
+            index.remove_path(Path::new(file))?;
+        }
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    if parent.as_ref().is_some_and(|parent| parent.tree_id() == tree.id()) {
+        return Ok(false);
+    }
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some("HEAD"), signature, signature, message, &tree, &parents)?;
+    Ok(true)
+}
+
+impl GitMirror {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the repository of a mirror, creating it when the directory is new
+    fn open(config: &GitMirrorConfig) -> Result<Repository, String> {
+        let root = PathBuf::from(&config.path);
+        std::fs::create_dir_all(&root).map_err(|e| format!("Failed to create {:?}: {}", root, e))?;
+        Repository::open(&root)
+            .or_else(|_| Repository::init(&root))
+            .map_err(|e| format!("Failed to open a git repository at {:?}: {}", root, e))
+    }
+
+    /// Writes every known document of the project and commits the result
+    fn sync_project(&self, session: &SessionManager, library: &Library, account: &str, config: &GitMirrorConfig) -> Result<(), String> {
+        let _guard = lock(&self.lock);
+        let repo = Self::open(config)?;
+        let root = PathBuf::from(&config.path);
+
+        let mut manifest = read_manifest(&root);
+        let mut removed = Vec::new();
+        manifest.project = library.projects(account)?.into_iter().find(|p| p.uuid == config.project_uuid);
+        for document in library.documents(account, &config.project_uuid)? {
+            write_document(&root, &mut manifest, &document, &mut removed)?;
+        }
+        write_manifest(&root, &manifest)?;
+
+        let name = manifest.project.as_ref().map(|p| p.name.as_str()).unwrap_or("project");
+        let signature = signature(session).map_err(|e| e.to_string())?;
+        commit_mirror(&repo, &manifest, &removed, &signature, &format!("Mirror {}", name)).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn commit_document(&self, session: &SessionManager, config: &GitMirrorConfig, document: &Document, source: VersionSource) -> Result<(), String> {
+        let _guard = lock(&self.lock);
+        let repo = Self::open(config)?;
+        let root = PathBuf::from(&config.path);
+
+        let mut manifest = read_manifest(&root);
+        let mut removed = Vec::new();
+        let previous_title = write_document(&root, &mut manifest, document, &mut removed)?;
+        write_manifest(&root, &manifest)?;
+
+        let message = commit_message(previous_title.as_deref(), &document.title, source);
+        let signature = signature(session).map_err(|e| e.to_string())?;
+        commit_mirror(&repo, &manifest, &removed, &signature, &message).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn remove_document(&self, session: &SessionManager, config: &GitMirrorConfig, document_uuid: &str) -> Result<(), String> {
+        let _guard = lock(&self.lock);
+        let root = PathBuf::from(&config.path);
+        let mut manifest = read_manifest(&root);
+        let Some(removed) = manifest.documents.remove(document_uuid) else { return Ok(()) };
+
+        let repo = Self::open(config)?;
+        let files = if is_mirror_file(&removed.file) {
+            let _ = std::fs::remove_file(root.join(&removed.file));
+            vec![removed.file.clone()]
+        } else {
+            Vec::new()
+        };
+        write_manifest(&root, &manifest)?;
+
+        let signature = signature(session).map_err(|e| e.to_string())?;
+        commit_mirror(&repo, &manifest, &files, &signature, &format!("Delete {}", removed.title)).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Commits a saved document to the git mirror of its project, if it has one
+pub fn commit_document(app: &AppHandle, account: &str, document: &Document, source: VersionSource) {
+    let library = app.state::<Library>();
+    let config = library
+        .document_project(account, &document.uuid)
+        .and_then(|project| match project {
+            Some(project) => mirror_config(&library, account, &project),
+            None => Ok(None),
+        });
+    let result = match config {
+        Ok(Some(config)) => app.state::<GitMirror>().commit_document(&session::manager(app), &config, document, source),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to commit document {} to its git mirror: {}", document.uuid, e);
+    }
+}
+
+/// Removes a deleted document from whichever mirror holds it
+pub fn remove_document(app: &AppHandle, account: &str, document_uuid: &str) {
+    let configs = match mirror_configs(&app.state::<Library>(), account) {
+        Ok(configs) => configs,
+        Err(e) => {
+            log::warn!("Failed to read git mirror settings: {}", e);
+            return;
+        }
+    };
+    let session = session::manager(app);
+    for config in configs {
+        if let Err(e) = app.state::<GitMirror>().remove_document(&session, &config, document_uuid) {
+            log::warn!("Failed to remove document {} from the git mirror at {}: {}", document_uuid, config.path, e);
+        }
+    }
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Tauri command to get the git mirror of a project; `None` when it is not mirrored
+#[tauri::command]
+pub async fn get_git_mirror(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    project_uuid: String,
+) -> Result<Option<GitMirrorConfig>, String> {
+    mirror_config(&library, &account(&session)?, &project_uuid)
+}
+
+/// Tauri command to mirror a project into a git repository at `path`, which
+/// must be a new or empty folder or an existing mirror. The repository is
+/// created if needed and the current documents are committed.
+#[tauri::command]
+pub async fn enable_git_mirror(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    mirror: State<'_, GitMirror>,
+    project_uuid: String,
+    path: String,
+) -> Result<GitMirrorConfig, String> {
+    let account = account(&session)?;
+    check_mirror_dir(Path::new(&path))?;
+
+    let config = GitMirrorConfig { project_uuid, path, created_at: timestamp::now_millis() };
+    mirror.sync_project(&session, &library, &account, &config)?;
+    library.with_db(&account, |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO git_mirrors (project_uuid, path, created_at) VALUES (?1, ?2, ?3)",
+            params![config.project_uuid, config.path, config.created_at as i64],
+        )
+    })?;
+    log::info!("Mirroring project {} into {}", config.project_uuid, config.path);
+    Ok(config)
+}
+
+/// Tauri command to stop mirroring a project; the repository is left in place
+#[tauri::command]
+pub async fn disable_git_mirror(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    project_uuid: String,
+) -> Result<(), String> {
+    library.with_db(&account(&session)?, |conn| {
+        conn.execute("DELETE FROM git_mirrors WHERE project_uuid = ?1", params![project_uuid])
+    })?;
+    log::info!("Stopped mirroring project {}", project_uuid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_types::DocumentStatus;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("editron-mirror-{}", rand::random::<u64>()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn document(uuid: &str, title: &str, content: &str) -> Document {
+        Document {
+            id: 1,
+            uuid: uuid.to_string(),
+            title: title.to_string(),
+            content: content.to_string(),
+            status: DocumentStatus::Ready,
+            created_at: "2026-01-05T09:30:00.000Z".to_string(),
+            updated_at: "2026-01-05T09:30:00.000Z".to_string(),
+        }
+    }
+
+    fn committed_files(repo: &Repository) -> Vec<String> {
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        let mut files: Vec<String> = tree.iter().map(|entry| entry.name().unwrap().to_string()).collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn slugs_are_lowercase_words_joined_by_dashes() {
+        assert_eq!(slug("Quarterly Report: Q3 (draft)"), "quarterly-report-q3-draft");
+        assert_eq!(slug("  --Hello,   World!!  "), "hello-world");
+        assert_eq!(slug("Überblick Straße"), "überblick-straße");
+        assert_eq!(slug("../../etc/passwd"), "etc-passwd");
+        assert_eq!(slug("!!!"), "untitled");
+        assert_eq!(slug(""), "untitled");
+        assert_eq!(slug(&"word ".repeat(40)).chars().count(), MAX_SLUG_LEN - 1);
+        assert!(!slug(&"word ".repeat(40)).ends_with('-'));
+    }
+
+    #[test]
+    fn file_names_get_the_uuid_when_another_document_has_the_title() {
+        let mut manifest = Manifest::default();
+        assert_eq!(file_name(&manifest, "aaaaaaaa-1111", "Notes"), "notes.html");
+
+        manifest.documents.insert("aaaaaaaa-1111".to_string(), ManifestDocument { title: "Notes".to_string(), file: "notes.html".to_string() });
+        assert_eq!(file_name(&manifest, "aaaaaaaa-1111", "Notes"), "notes.html");
+        assert_eq!(file_name(&manifest, "bbbbbbbb-2222", "Notes"), "notes-bbbbbbbb.html");
+    }
+
+    #[test]
+    fn commit_messages_describe_the_change_and_mark_agent_edits() {
+        assert_eq!(commit_message(None, "Plan", VersionSource::Save), "Add Plan");
+        assert_eq!(commit_message(Some("Plan"), "Plan", VersionSource::Save), "Update Plan");
+        assert_eq!(commit_message(Some("Draft"), "Plan", VersionSource::Save), "Rename Draft to Plan");
+        assert_eq!(
+            commit_message(Some("Plan"), "Plan", VersionSource::Agent),
+            format!("Update Plan\n\n{}", AGENT_TRAILER)
+        );
+    }
+
+    #[test]
+    fn mirrors_only_go_into_new_empty_or_existing_mirror_folders() {
+        let dir = TempDir::new();
+        assert!(check_mirror_dir(Path::new("relative/path")).is_err());
+        assert!(check_mirror_dir(&dir.0.join("new")).is_ok());
+        assert!(check_mirror_dir(&dir.0).is_ok());
+
+        std::fs::write(dir.0.join("taxes.pdf"), b"private").unwrap();
+        assert!(check_mirror_dir(&dir.0).is_err());
+        assert!(check_mirror_dir(&dir.0.join("taxes.pdf")).is_err());
+
+        std::fs::write(dir.0.join(MANIFEST_FILE), b"{}").unwrap();
+        assert!(check_mirror_dir(&dir.0).is_ok());
+    }
+
+    #[test]
+    fn manifest_entries_cannot_point_outside_the_mirror() {
+        assert!(is_mirror_file("notes.html"));
+        assert!(is_mirror_file("notes-bbbbbbbb.html"));
+        assert!(!is_mirror_file("../notes.html"));
+        assert!(!is_mirror_file("/etc/notes.html"));
+        assert!(!is_mirror_file(".bashrc.html"));
+        assert!(!is_mirror_file("notes.txt"));
+    }
+
+    #[test]
+    fn commits_stage_only_the_manifest_and_document_files() {
+        let dir = TempDir::new();
+        let repo = Repository::init(&dir.0).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        std::fs::write(dir.0.join("stray.txt"), b"not ours").unwrap();
+
+        let mut manifest = Manifest::default();
+        let mut removed = Vec::new();
+        let doc = document("aaaaaaaa-1111", "First draft", "<p>one</p>");
+        assert_eq!(write_document(&dir.0, &mut manifest, &doc, &mut removed).unwrap(), None);
+        write_manifest(&dir.0, &manifest).unwrap();
+        assert!(commit_mirror(&repo, &manifest, &removed, &signature, "Add First draft").unwrap());
+        assert_eq!(committed_files(&repo), vec![MANIFEST_FILE.to_string(), "first-draft.html".to_string()]);
+
+        // A rename moves the file and stages the removal of the old one
+        let renamed = document("aaaaaaaa-1111", "Final", "<p>one</p>");
+        let previous = write_document(&dir.0, &mut manifest, &renamed, &mut removed).unwrap();
+        assert_eq!(previous.as_deref(), Some("First draft"));
+        assert_eq!(removed, vec!["first-draft.html".to_string()]);
+        write_manifest(&dir.0, &manifest).unwrap();
+        assert!(commit_mirror(&repo, &manifest, &removed, &signature, "Rename").unwrap());
+        assert_eq!(committed_files(&repo), vec![MANIFEST_FILE.to_string(), "final.html".to_string()]);
+
+        // Nothing changed, so nothing is committed
+        assert!(!commit_mirror(&repo, &manifest, &[], &signature, "Again").unwrap());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::api_types::Document;
+use crate::git_mirror;
+use crate::library::Library;
+use crate::merge::{self, DiffBlock};
+use crate::outbox::{self, DocumentChange, Outbox, OutboxEntry};
+use crate::session::SessionManager;
+use crate::store_schema;
+use crate::timestamp;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+
+const HOUR_MILLIS: u64 = 60 * 60 * 1000;
+const DAY_MILLIS: u64 = 24 * HOUR_MILLIS;
+/// Versions younger than this are all kept
+const KEEP_ALL_MILLIS: u64 = HOUR_MILLIS;
+/// Versions younger than this are thinned to one per hour, older ones to one per day
+const KEEP_HOURLY_MILLIS: u64 = DAY_MILLIS;
+/// Versions older than this are dropped, except the latest one of a document
+const KEEP_DAILY_MILLIS: u64 = 30 * DAY_MILLIS;
+
+/// What produced a version
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionSource {
+    /// A save confirmed by the gateway
+    Save,
+    /// An agent edit the user accepted
+    Agent,
+}
+
+impl VersionSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VersionSource::Save => "save",
+            VersionSource::Agent => "agent",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DocumentVersion {
+    pub id: i64,
+    #[serde(rename = "documentUuid")]
+    pub document_uuid: String,
+    /// SHA-256 of the content, naming the compressed snapshot on disk
+    pub hash: String,
+    pub title: String,
+    pub source: VersionSource,
+    /// Content length in bytes
+    pub size: u64,
+    #[serde(rename = "createdAt")]
+    pub created_at: u64,
+}
+
+/// Local version history of documents. Snapshots are stored once per distinct
+/// content, gzip-compressed and named by their hash; the version list lives in
+/// the account's library database and is thinned by age after every snapshot.
+pub struct History {
+    app: AppHandle,
+}
+
+const VERSION_COLUMNS: &str = "id, document_uuid, hash, title, source, size, created_at";
+
+fn version_from_row(row: &Row) -> rusqlite::Result<DocumentVersion> {
+    let source: String = row.get(4)?;
+    Ok(DocumentVersion {
+        id: row.get(0)?,
+        document_uuid: row.get(1)?,
+        hash: row.get(2)?,
+        title: row.get(3)?,
+        source: if source == "agent" { VersionSource::Agent } else { VersionSource::Save },
+        size: row.get::<_, i64>(5)? as u64,
+        created_at: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+fn versions(conn: &Connection, document_uuid: &str) -> rusqlite::Result<Vec<DocumentVersion>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM versions WHERE document_uuid = ?1 ORDER BY created_at DESC, id DESC",
+        VERSION_COLUMNS
+    ))?;
+    let versions = stmt.query_map(params![document_uuid], version_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(versions)
+}
+
+/// Ids of versions to drop under the retention policy; `versions` is newest first
+fn expired(versions: &[DocumentVersion], now: u64) -> Vec<i64> {
+    let mut kept_buckets = HashSet::new();
+    let mut expired = Vec::new();
+    for (i, version) in versions.iter().enumerate() {
+        let age = now.saturating_sub(version.created_at);
+        let keep = if i == 0 || age < KEEP_ALL_MILLIS {
+            true
+        } else if age < KEEP_HOURLY_MILLIS {
+            kept_buckets.insert(("hour", version.created_at / HOUR_MILLIS))
+        } else if age < KEEP_DAILY_MILLIS {
+            kept_buckets.insert(("day", version.created_at / DAY_MILLIS))
+        } else {
+            false
+        };
+        if !keep {
+            expired.push(version.id);
+        }
+    }
+    expired
+}
+
+fn hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+impl History {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+
+    fn blob_path(&self, account: &str, hash: &str) -> Result<PathBuf, String> {
+        self.app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("history").join(account).join(&hash[..2]).join(format!("{}.gz", hash)))
+            .map_err(|e| e.to_string())
+    }
+
+    fn write_blob(&self, account: &str, hash: &str, content: &str) -> Result<(), String> {
+        let path = self.blob_path(account, hash)?;
+        if path.exists() {
+            return Ok(());
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(content.as_bytes())
+            .and_then(|_| encoder.finish())
+            .and_then(|bytes| store_schema::write_atomic(&path, &bytes))
+            .map_err(|e| format!("Failed to write snapshot {:?}: {}", path, e))
+    }
+
+    fn read_blob(&self, account: &str, hash: &str) -> Result<String, String> {
+        let path = self.blob_path(account, hash)?;
+        let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read snapshot {:?}: {}", path, e))?;
+        let mut content = String::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Failed to decompress snapshot {:?}: {}", path, e))?;
+        Ok(content)
+    }
+
+    /// Records the content of a document as a new version, unless it matches the
+    /// latest one, then applies the retention policy to the document's versions.
+    /// Returns the source of the version now holding the content.
+    pub fn record(&self, library: &Library, account: &str, document: &Document, source: VersionSource) -> Result<VersionSource, String> {
+        self.record_content(library, account, &document.uuid, &document.title, &document.content, source)
+    }
+
+    fn record_content(
+        &self,
+        library: &Library,
+        account: &str,
+        document_uuid: &str,
+        title: &str,
+        content: &str,
+        source: VersionSource,
+    ) -> Result<VersionSource, String> {
+        let hash = hash(content);
+        let latest = library.with_db(account, |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM versions WHERE document_uuid = ?1 ORDER BY created_at DESC, id DESC LIMIT 1", VERSION_COLUMNS),
+                params![document_uuid],
+                version_from_row,
+            )
+            .optional()
+        })?;
+        if let Some(latest) = latest.filter(|latest| latest.hash == hash) {
+            return Ok(latest.source);
+        }
+
+        self.write_blob(account, &hash, content)?;
+        let now = timestamp::now_millis();
+        let orphaned = library.with_db(account, |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO versions (document_uuid, hash, title, source, size, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![document_uuid, hash, title, source.as_str(), content.len() as i64, now as i64],
+            )?;
+
+            let mut orphaned = Vec::new();
+            for id in expired(&versions(&tx, document_uuid)?, now) {
+                let hash: String = tx.query_row("SELECT hash FROM versions WHERE id = ?1", params![id], |row| row.get(0))?;
+                tx.execute("DELETE FROM versions WHERE id = ?1", params![id])?;
+                let used: i64 = tx.query_row("SELECT COUNT(*) FROM versions WHERE hash = ?1", params![hash], |row| row.get(0))?;
+                if used == 0 {
+                    orphaned.push(hash);
+                }
+            }
+            tx.commit()?;
+            Ok(orphaned)
+        })?;
+
+        for hash in orphaned {
+            if let Ok(path) = self.blob_path(account, &hash) {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    log::warn!("Failed to remove snapshot {:?}: {}", path, e);
+                }
+            }
+        }
+        log::debug!("Recorded {} version of document {}", source.as_str(), document_uuid);
+        Ok(source)
+    }
+
+    /// Content of a version of a document
+    pub fn content(&self, library: &Library, account: &str, document_uuid: &str, version_id: i64) -> Result<String, String> {
+        let hash = library
+            .with_db(account, |conn| {
+                conn.query_row(
+                    "SELECT hash FROM versions WHERE id = ?1 AND document_uuid = ?2",
+                    params![version_id, document_uuid],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+            })?
+            .ok_or_else(|| format!("Version {} of document {} not found", version_id, document_uuid))?;
+        self.read_blob(account, &hash)
+    }
+}
+
+/// Records a version of a saved document and commits it to the project's git
+/// mirror, if there is one. Failures are logged; the save itself succeeded.
+pub fn record(app: &AppHandle, account: &str, document: &Document, source: VersionSource) {
+    match app.state::<History>().record(&app.state::<Library>(), account, document, source) {
+        // A save of an accepted agent edit keeps the agent as its source
+        Ok(source) => git_mirror::commit_document(app, account, document, source),
+        Err(e) => log::warn!("Failed to record a version of document {}: {}", document.uuid, e),
+    }
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Tauri command to list the local versions of a document, newest first
+#[tauri::command]
+pub async fn list_document_versions(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    document_uuid: String,
+) -> Result<Vec<DocumentVersion>, String> {
+    library.with_db(&account(&session)?, |conn| versions(conn, &document_uuid))
+}
+
+/// Tauri command to read the content of a version
+#[tauri::command]
+pub async fn get_document_version(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    history: State<'_, History>,
+    document_uuid: String,
+    version_id: i64,
+) -> Result<String, String> {
+    history.content(&library, &account(&session)?, &document_uuid, version_id)
+}
+
+/// Tauri command to diff two versions of a document at block level
+#[tauri::command]
+pub async fn diff_document_versions(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    history: State<'_, History>,
+    document_uuid: String,
+    from_version: i64,
+    to_version: i64,
+) -> Result<Vec<DiffBlock>, String> {
+    let account = account(&session)?;
+    let from = history.content(&library, &account, &document_uuid, from_version)?;
+    let to = history.content(&library, &account, &document_uuid, to_version)?;
+    Ok(merge::diff(&from, &to))
+}
+
+/// Tauri command to record an agent edit the user accepted
+#[tauri::command]
+pub async fn record_agent_edit(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    history: State<'_, History>,
+    document_uuid: String,
+    title: String,
+    content: String,
+) -> Result<(), String> {
+    history
+        .record_content(&library, &account(&session)?, &document_uuid, &title, &content, VersionSource::Agent)
+        .map(|_| ())
+}
+
+/// Tauri command to restore a version. The content is saved like an edit, through
+/// the outbox, against `baseVersion` (the `updatedAt` the editor holds).
+#[tauri::command]
+pub async fn restore_document_version(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    history: State<'_, History>,
+    outbox: State<'_, Outbox>,
+    project_uuid: String,
+    document_uuid: String,
+    version_id: i64,
+    base_version: String,
+) -> Result<OutboxEntry, String> {
+    let account = account(&session)?;
+    let content = history.content(&library, &account, &document_uuid, version_id)?;
+    log::info!("Restoring version {} of document {}", version_id, document_uuid);
+    outbox::queue(&library, &outbox, &account, &project_uuid, &document_uuid, &DocumentChange::Edit { content }, &base_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_MILLIS: u64 = 60 * 1000;
+    /// A day boundary, so hour and day buckets line up with the ages below
+    const NOW: u64 = 1000 * DAY_MILLIS;
+
+    /// Versions newest first, one per age, with ids counting up from 1
+    fn versions(ages: &[u64]) -> Vec<DocumentVersion> {
+        ages.iter()
+            .enumerate()
+            .map(|(i, age)| DocumentVersion {
+                id: i as i64 + 1,
+                document_uuid: "doc".to_string(),
+                hash: String::new(),
+                title: String::new(),
+                source: VersionSource::Save,
+                size: 0,
+                created_at: NOW - age,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn everything_from_the_last_hour_is_kept() {
+        let ages: Vec<u64> = (0..60).map(|minute| minute * MINUTE_MILLIS).collect();
+        assert!(expired(&versions(&ages), NOW).is_empty());
+        assert!(expired(&versions(&[0, KEEP_ALL_MILLIS - 1]), NOW).is_empty());
+    }
+
+    #[test]
+    fn one_version_per_hour_is_kept_for_a_day() {
+        let ages = [
+            0,
+            HOUR_MILLIS,                           // 2: first of its hour
+            HOUR_MILLIS + 30 * MINUTE_MILLIS,      // 3: first of the hour before
+            HOUR_MILLIS + 45 * MINUTE_MILLIS,      // 4: same hour as 3
+            2 * HOUR_MILLIS - 1,                   // 5: same hour as 3
+            2 * HOUR_MILLIS + 1,                   // 6: next hour down
+            DAY_MILLIS - 1,                        // 7: last hourly bucket
+        ];
+        assert_eq!(expired(&versions(&ages), NOW), vec![4, 5]);
+    }
+
+    #[test]
+    fn one_version_per_day_is_kept_for_a_month() {
+        let ages = [
+            0,
+            DAY_MILLIS,                            // 2: first daily version
+            DAY_MILLIS + 6 * HOUR_MILLIS,          // 3: the day before
+            DAY_MILLIS + 20 * HOUR_MILLIS,         // 4: same day as 3
+            KEEP_DAILY_MILLIS - 1,                 // 5: last daily bucket
+            KEEP_DAILY_MILLIS,                     // 6: too old
+            400 * DAY_MILLIS,                      // 7: too old
+        ];
+        assert_eq!(expired(&versions(&ages), NOW), vec![4, 6, 7]);
+    }
+
+    #[test]
+    fn buckets_follow_clock_hours_and_days() {
+        // A minute apart, but on either side of an hour boundary
+        let ages = [0, 3 * HOUR_MILLIS - 30_000, 3 * HOUR_MILLIS + 30_000];
+        assert!(expired(&versions(&ages), NOW).is_empty());
+        // A minute apart, but on either side of midnight
+        let ages = [0, 2 * DAY_MILLIS - 30_000, 2 * DAY_MILLIS + 30_000];
+        assert!(expired(&versions(&ages), NOW).is_empty());
+    }
+
+    #[test]
+    fn the_newest_version_is_always_kept() {
+        assert!(expired(&versions(&[400 * DAY_MILLIS]), NOW).is_empty());
+        assert_eq!(expired(&versions(&[400 * DAY_MILLIS, 401 * DAY_MILLIS]), NOW), vec![2]);
+        // Versions from the future, e.g. after a clock change, count as brand new
+        assert!(expired(&versions(&[0, 30 * MINUTE_MILLIS]), NOW - DAY_MILLIS).is_empty());
+    }
+}
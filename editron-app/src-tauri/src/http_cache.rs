@@ -0,0 +1,156 @@
+use crate::config::AppConfig;
+use crate::session;
+use crate::timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Entries younger than this are served without contacting the backend
+pub const FRESH_FOR_SECS: u64 = 15;
+
+/// A cached GET response together with its validators
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub path: String,
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: Value,
+    pub stored_at: u64,
+}
+
+impl CacheEntry {
+    pub fn age_secs(&self) -> u64 {
+        timestamp::now_secs().saturating_sub(self.stored_at)
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.age_secs() < FRESH_FOR_SECS
+    }
+}
+
+/// On-disk response cache for a single account.
+/// Each entry is one JSON file named after the SHA-256 of its URL.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Opens the cache of the logged-in account, if there is one
+    pub fn for_current_account(app: &AppHandle) -> Option<Self> {
+        let account = session::manager(app).current_account_key()?;
+        let base = app.path().app_cache_dir().ok()?;
+        Some(Self::new(base.join("http-cache").join(account)))
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let hash = Sha256::digest(url.as_bytes());
+        self.dir.join(format!("{:x}.json", hash))
+    }
+
+    pub fn get(&self, url: &url::Url) -> Option<CacheEntry> {
+        let data = std::fs::read(self.entry_path(url.as_str())).ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Discarding unreadable cache entry for {}: {}", url.path(), e);
+                let _ = std::fs::remove_file(self.entry_path(url.as_str()));
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, url: &url::Url, etag: Option<String>, last_modified: Option<String>, body: Value) {
+        let entry = CacheEntry {
+            path: url.path().to_string(),
+            url: url.to_string(),
+            etag,
+            last_modified,
+            body,
+            stored_at: timestamp::now_secs(),
+        };
+        if let Err(e) = self.write(&entry) {
+            log::warn!("Failed to write cache entry for {}: {}", url.path(), e);
+        }
+    }
+
+    /// Marks an entry as freshly validated after a 304 response
+    pub fn touch(&self, mut entry: CacheEntry) {
+        entry.stored_at = timestamp::now_secs();
+        if let Err(e) = self.write(&entry) {
+            log::warn!("Failed to refresh cache entry for {}: {}", entry.path, e);
+        }
+    }
+
+    fn write(&self, entry: &CacheEntry) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(&entry.url);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(entry)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Removes every entry whose path starts with `path_prefix`; `None` clears the cache.
+    /// Returns the number of removed entries.
+    pub fn invalidate(&self, path_prefix: Option<&str>) -> usize {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let file = entry.path();
+            let matches = match path_prefix {
+                None => true,
+                Some(prefix) => std::fs::read(&file)
+                    .ok()
+                    .and_then(|data| serde_json::from_slice::<CacheEntry>(&data).ok())
+                    .map(|cached| cached.path.starts_with(prefix))
+                    .unwrap_or(true),
+            };
+            if matches && std::fs::remove_file(&file).is_ok() {
+                removed += 1;
+            }
+        }
+
+        log::info!("Invalidated {} cache entries (prefix: {:?})", removed, path_prefix);
+        removed
+    }
+}
+
+/// Drops the cached responses a document write affects: the document listings
+/// and, when the project is known, everything under that project
+pub fn invalidate_document_writes(app: &AppHandle, config: &AppConfig, project_uuid: Option<&str>) {
+    let Some(cache) = HttpCache::for_current_account(app) else { return };
+    cache.invalidate(Some(&resource_prefix(config, "/documents")));
+    if let Some(project_uuid) = project_uuid {
+        cache.invalidate(Some(&format!("{}/projects/{}/", config.backend_api_path_prefix(), project_uuid)));
+    }
+}
+
+/// Returns the resource prefix a mutation affects, e.g.
+/// `/api/v1/documents/abc` -> `/api/v1/documents`
+pub fn resource_prefix(config: &AppConfig, path: &str) -> String {
+    let api_prefix = config.backend_api_path_prefix();
+    let rest = path.strip_prefix(&api_prefix).unwrap_or(path);
+    match rest.trim_start_matches('/').split('/').next() {
+        Some(resource) if !resource.is_empty() => format!("{}/{}", api_prefix, resource),
+        _ => api_prefix,
+    }
+}
+
+/// Tauri command to drop cached responses, e.g. after a change made elsewhere
+#[tauri::command]
+pub async fn invalidate_cache(app: AppHandle, path_prefix: Option<String>) -> Result<usize, String> {
+    match HttpCache::for_current_account(&app) {
+        Some(cache) => Ok(cache.invalidate(path_prefix.as_deref())),
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,341 @@
+use crate::locks::lock;
+use crate::store_schema;
+use crate::timestamp;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::AppHandle;
+
+/// Number of requests kept in the ring buffer
+const CAPACITY: usize = 200;
+/// Captured bodies are truncated to this many bytes
+const MAX_CAPTURED_BODY: usize = 64 * 1024;
+
+/// Query parameters whose values are kept in recorded URLs; all others are redacted
+const SAFE_QUERY_KEYS: &[&str] = &["projectUuid", "documentUuid", "mode", "page", "limit"];
+/// Paths whose bodies are never captured because they carry codes or tokens
+const SENSITIVE_PATHS: &[&str] = &["/auth/", "/google-api/exchange-code"];
+
+/// One backend request. Never contains headers, tokens or (unless body
+/// capture is enabled for debugging) bodies.
+#[derive(Serialize, Clone, Debug)]
+pub struct RequestRecord {
+    pub id: u64,
+    #[serde(rename = "startedAt")]
+    pub started_at: u64,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u16>,
+    /// Time until the response headers arrived
+    #[serde(rename = "latencyMs")]
+    pub latency_ms: u64,
+    #[serde(rename = "requestBytes")]
+    pub request_bytes: Option<u64>,
+    /// `Content-Type` of the request body
+    #[serde(rename = "requestMimeType")]
+    pub request_mime_type: Option<String>,
+    #[serde(rename = "responseBytes")]
+    pub response_bytes: Option<u64>,
+    #[serde(rename = "mimeType")]
+    pub mime_type: Option<String>,
+    #[serde(rename = "errorKind")]
+    pub error_kind: Option<String>,
+    #[serde(rename = "requestBody", skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    #[serde(rename = "responseBody", skip_serializing_if = "Option::is_none")]
+    pub response_body: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDS: Mutex<VecDeque<RequestRecord>> = Mutex::new(VecDeque::with_capacity(CAPACITY));
+    static ref NEXT_ID: Mutex<u64> = Mutex::new(0);
+    static ref CAPTURE_BODIES: Mutex<bool> = Mutex::new(false);
+}
+
+/// Sends requests through the inspector so they show up in the request log
+pub trait RecordedSend {
+    fn send_recorded(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl RecordedSend for RequestBuilder {
+    fn send_recorded(self) -> impl Future<Output = reqwest::Result<Response>> + Send {
+        send(self)
+    }
+}
+
+/// Strips credentials and the values of non-allowlisted query parameters
+pub fn redact_url(url: &url::Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if SAFE_QUERY_KEYS.contains(&key.as_ref()) {
+                value.into_owned()
+            } else {
+                "REDACTED".to_string()
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+fn error_kind(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        "timeout"
+    } else if e.is_connect() {
+        "connect"
+    } else if e.is_redirect() {
+        "redirect"
+    } else if e.is_body() {
+        "body"
+    } else if e.is_decode() {
+        "decode"
+    } else if e.is_builder() {
+        "builder"
+    } else {
+        "request"
+    }
+}
+
+fn captured_text(bytes: &[u8]) -> String {
+    let end = bytes.len().min(MAX_CAPTURED_BODY);
+    let mut text = String::from_utf8_lossy(&bytes[..end]).into_owned();
+    if bytes.len() > MAX_CAPTURED_BODY {
+        text.push_str("…[truncated]");
+    }
+    text
+}
+
+fn push(record: RequestRecord) {
+    let mut records = lock(&RECORDS);
+    if records.len() == CAPACITY {
+        records.pop_front();
+    }
+    records.push_back(record);
+}
+
+fn next_id() -> u64 {
+    let mut next = lock(&NEXT_ID);
+    *next += 1;
+    *next
+}
+
+pub fn capture_enabled() -> bool {
+    *lock(&CAPTURE_BODIES)
+}
+
+/// Reads a non-streaming response body for the log and hands back an equivalent response
+async fn capture_response(res: Response, record: &mut RequestRecord) -> reqwest::Result<Response> {
+    let status = res.status();
+    let version = res.version();
+    let headers = res.headers().clone();
+    let url = res.url().clone();
+    let bytes = res.bytes().await?;
+
+    record.response_bytes = Some(bytes.len() as u64);
+    record.response_body = Some(captured_text(&bytes));
+
+    // The URL travels in the response extensions, so `Response::url` still reports it
+    let mut builder = tauri::http::Response::builder().status(status).version(version).url(url);
+    if let Some(map) = builder.headers_mut() {
+        *map = headers;
+    }
+    let rebuilt = builder
+        .body(bytes.to_vec())
+        .expect("response parts come from a valid response");
+    Ok(Response::from(rebuilt))
+}
+
+/// Sends `builder` and records the request in the ring buffer
+pub async fn send(builder: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = builder.build_split();
+    let request = request?;
+
+    let capture = capture_enabled() && !SENSITIVE_PATHS.iter().any(|p| request.url().path().contains(p));
+    let request_body = request.body().and_then(|b| b.as_bytes());
+    let mut record = RequestRecord {
+        id: next_id(),
+        started_at: timestamp::now_millis(),
+        method: request.method().to_string(),
+        url: redact_url(request.url()),
+        status: None,
+        latency_ms: 0,
+        request_bytes: request_body.map(|b| b.len() as u64),
+        request_mime_type: request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        response_bytes: None,
+        mime_type: None,
+        error_kind: None,
+        request_body: if capture { request_body.map(captured_text) } else { None },
+        response_body: None,
+    };
+
+    let started = Instant::now();
+    let result = client.execute(request).await;
+    record.latency_ms = started.elapsed().as_millis() as u64;
+
+    let res = match result {
+        Ok(res) => res,
+        Err(e) => {
+            record.error_kind = Some(error_kind(&e).to_string());
+            push(record);
+            return Err(e);
+        }
+    };
+
+    let status = res.status();
+    record.status = Some(status.as_u16());
+    record.response_bytes = res.content_length();
+    record.mime_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    if status.is_client_error() || status.is_server_error() {
+        record.error_kind = Some("status".to_string());
+    }
+
+    // Streams (SSE chat) are left alone; their body is consumed incrementally by the caller
+    let streaming = record.mime_type.as_deref().is_some_and(|m| m.starts_with("text/event-stream"));
+    let result = if capture && !streaming {
+        capture_response(res, &mut record).await.inspect_err(|e| {
+            record.error_kind = Some(error_kind(e).to_string());
+        })
+    } else {
+        Ok(res)
+    };
+
+    push(record);
+    result
+}
+
+/// Returns the recorded requests, oldest first
+pub fn records() -> Vec<RequestRecord> {
+    lock(&RECORDS).iter().cloned().collect()
+}
+
+/// Builds a HAR 1.2 style document from the recorded requests
+pub fn to_har() -> Value {
+    let entries: Vec<Value> = records()
+        .into_iter()
+        .map(|r| {
+            let mut request = json!({
+                "method": r.method,
+                "url": r.url,
+                "httpVersion": "HTTP/1.1",
+                "headers": [],
+                "queryString": [],
+                "cookies": [],
+                "headersSize": -1,
+                "bodySize": r.request_bytes.map(|b| b as i64).unwrap_or(-1),
+            });
+            if let Some(body) = &r.request_body {
+                request["postData"] = json!({ "mimeType": r.request_mime_type.clone().unwrap_or_default(), "text": body });
+            }
+
+            let mut content = json!({
+                "size": r.response_bytes.map(|b| b as i64).unwrap_or(-1),
+                "mimeType": r.mime_type.clone().unwrap_or_default(),
+            });
+            if let Some(body) = &r.response_body {
+                content["text"] = json!(body);
+            }
+
+            json!({
+                "startedDateTime": timestamp::iso8601_millis(r.started_at),
+                "time": r.latency_ms,
+                "request": request,
+                "response": {
+                    "status": r.status.unwrap_or(0),
+                    "statusText": "",
+                    "httpVersion": "HTTP/1.1",
+                    "headers": [],
+                    "cookies": [],
+                    "content": content,
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": r.response_bytes.map(|b| b as i64).unwrap_or(-1),
+                },
+                "cache": {},
+                "timings": { "send": 0, "wait": r.latency_ms, "receive": 0 },
+                "_errorKind": r.error_kind,
+            })
+        })
+        .collect();
+
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "Editron", "version": env!("CARGO_PKG_VERSION") },
+            "entries": entries,
+        }
+    })
+}
+
+/// Loads the body capture setting; called during setup
+pub fn load_settings(app: &AppHandle) {
+    match store_schema::load::<bool>(app, &store_schema::CAPTURE_BODIES) {
+        Ok(enabled) => {
+            let enabled = enabled.unwrap_or(false);
+            *lock(&CAPTURE_BODIES) = enabled;
+            if enabled {
+                log::warn!("Request body capture is enabled");
+            }
+        }
+        Err(e) => log::error!("Failed to load the body capture setting: {}", e),
+    }
+}
+
+/// Tauri command to get the recorded backend requests
+#[tauri::command]
+pub async fn get_request_log() -> Result<Vec<RequestRecord>, String> {
+    Ok(records())
+}
+
+/// Tauri command to clear the recorded backend requests
+#[tauri::command]
+pub async fn clear_request_log() -> Result<(), String> {
+    lock(&RECORDS).clear();
+    Ok(())
+}
+
+/// Tauri command to export the recorded backend requests as HAR-like JSON
+#[tauri::command]
+pub async fn export_request_log() -> Result<Value, String> {
+    Ok(to_har())
+}
+
+/// Tauri command to get whether request and response bodies are captured
+#[tauri::command]
+pub async fn get_request_body_capture() -> Result<bool, String> {
+    Ok(capture_enabled())
+}
+
+/// Tauri command to enable body capture for debugging. Bodies of auth
+/// endpoints are never captured.
+#[tauri::command]
+pub async fn set_request_body_capture(app: AppHandle, enabled: bool) -> Result<(), String> {
+    store_schema::save_with(&app, &store_schema::CAPTURE_BODIES, || enabled)?;
+
+    *lock(&CAPTURE_BODIES) = enabled;
+    log::info!("Request body capture {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
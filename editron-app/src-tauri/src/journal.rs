@@ -0,0 +1,256 @@
+use crate::api_client;
+use crate::api_types::Document;
+use crate::library::Library;
+use crate::locks::lock;
+use crate::merge::{self, DiffBlock};
+use crate::outbox;
+use crate::session::{self, SessionManager};
+use crate::shutdown::Shutdown;
+use crate::store_schema;
+use crate::timestamp;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Length of the plain-text preview of a recoverable document
+const PREVIEW_CHARS: usize = 200;
+
+lazy_static::lazy_static! {
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+/// The latest editor contents of a document, written before they reach the gateway
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    #[serde(rename = "documentUuid")]
+    pub document_uuid: String,
+    #[serde(rename = "projectUuid")]
+    pub project_uuid: Option<String>,
+    pub title: Option<String>,
+    pub content: String,
+    /// `updatedAt` of the server copy the editor started from
+    #[serde(rename = "baseVersion")]
+    pub base_version: String,
+    #[serde(rename = "savedAt")]
+    pub saved_at: u64,
+}
+
+/// A journaled document whose contents never reached the gateway
+#[derive(Serialize, Clone, Debug)]
+pub struct RecoverableDocument {
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+    /// Plain-text start of the journaled contents
+    pub preview: String,
+    /// `updatedAt` of the server copy the diff was made against, if there is one
+    #[serde(rename = "serverVersion")]
+    pub server_version: Option<String>,
+    /// Block-level diff from the server copy to the journaled contents
+    pub diff: Vec<DiffBlock>,
+}
+
+/// Write-ahead journal of open documents. The editor streams debounced
+/// snapshots which are flushed to disk, one file per document, so edits
+/// survive a crash or a rejected save. A snapshot is dropped once a save
+/// with the same contents is confirmed by the gateway.
+pub struct Journal {
+    app: AppHandle,
+    lock: Mutex<()>,
+}
+
+fn preview(html: &str) -> String {
+    let text = TAG.replace_all(html, " ");
+    let text = WHITESPACE.replace_all(text.trim(), " ");
+    text.chars().take(PREVIEW_CHARS).collect()
+}
+
+impl Journal {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app, lock: Mutex::new(()) }
+    }
+
+    fn dir(&self, account: &str) -> Result<PathBuf, String> {
+        self.app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("journal").join(account))
+            .map_err(|e| e.to_string())
+    }
+
+    fn path(&self, account: &str, document_uuid: &str) -> Result<PathBuf, String> {
+        // Document UUIDs come from the webview; keep them inside the journal directory
+        if document_uuid.is_empty() || !document_uuid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("Invalid document id {:?}", document_uuid));
+        }
+        Ok(self.dir(account)?.join(format!("{}.json", document_uuid)))
+    }
+
+    /// Writes a snapshot and flushes it to disk before returning
+    pub fn write(&self, account: &str, entry: &JournalEntry) -> Result<(), String> {
+        let path = self.path(account, &entry.document_uuid)?;
+        let bytes = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+        let _guard = lock(&self.lock);
+        store_schema::write_atomic(&path, &bytes).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+
+    pub fn entries(&self, account: &str) -> Result<Vec<JournalEntry>, String> {
+        let dir = self.dir(account)?;
+        let _guard = lock(&self.lock);
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+        };
+
+        let mut entries = Vec::new();
+        for path in read_dir.flatten().map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match std::fs::read(&path).map_err(|e| e.to_string()).and_then(|b| serde_json::from_slice(&b).map_err(|e| e.to_string())) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping unreadable journal file {:?}: {}", path, e),
+            }
+        }
+        entries.sort_by_key(|e: &JournalEntry| std::cmp::Reverse(e.saved_at));
+        Ok(entries)
+    }
+
+    pub fn remove(&self, account: &str, document_uuid: &str) -> Result<(), String> {
+        let path = self.path(account, document_uuid)?;
+        let _guard = lock(&self.lock);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove {:?}: {}", path, e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops the snapshot of a document once the gateway holds the same contents
+    pub fn confirm(&self, account: &str, document: &Document) {
+        let Ok(path) = self.path(account, &document.uuid) else { return };
+        let _guard = lock(&self.lock);
+        let saved = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<JournalEntry>(&bytes).ok());
+        if saved.is_some_and(|entry| entry.content == document.content) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to remove journal of document {}: {}", document.uuid, e);
+            }
+        }
+    }
+}
+
+/// Journal entries whose contents are neither on the gateway nor queued for it,
+/// as known to the local library
+fn unsaved_entries(journal: &Journal, library: &Library, account: &str) -> Result<Vec<(JournalEntry, Option<Document>)>, String> {
+    let mut unsaved = Vec::new();
+    for entry in journal.entries(account)? {
+        let stored = library.document(account, &entry.document_uuid)?;
+        // Queued edits count as saved; the outbox delivers them
+        let expected = match &stored {
+            Some(document) => outbox::apply_pending(library, account, vec![document.clone()])?.pop(),
+            None => None,
+        };
+        if expected.is_some_and(|document| document.content == entry.content) {
+            journal.remove(account, &entry.document_uuid)?;
+            continue;
+        }
+        unsaved.push((entry, stored));
+    }
+    Ok(unsaved)
+}
+
+/// Looks for journaled edits left over from the last session and tells the UI
+pub fn start_recovery_check(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let Some(account) = session::manager(&app).current_account_key() else { return };
+        match unsaved_entries(&app.state::<Journal>(), &app.state::<Library>(), &account) {
+            Ok(unsaved) if unsaved.is_empty() => {}
+            Ok(unsaved) => {
+                log::warn!("Found {} documents with unsaved edits from the last session", unsaved.len());
+                if let Err(e) = app.emit("documents_recoverable", unsaved.len()) {
+                    log::error!("Failed to emit documents_recoverable event: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to check the edit journal: {}", e),
+        }
+    });
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Tauri command to journal the current editor contents of a document
+#[tauri::command]
+pub async fn journal_document(
+    session: State<'_, SessionManager>,
+    shutdown: State<'_, Shutdown>,
+    journal: State<'_, Journal>,
+    project_uuid: Option<String>,
+    document_uuid: String,
+    title: Option<String>,
+    content: String,
+    base_version: String,
+) -> Result<(), String> {
+    let _work = shutdown.track();
+    let entry = JournalEntry {
+        document_uuid,
+        project_uuid,
+        title,
+        content,
+        base_version,
+        saved_at: timestamp::now_millis(),
+    };
+    journal.write(&account(&session)?, &entry)
+}
+
+/// Tauri command to list journaled documents with edits that were never saved,
+/// each with a preview and a diff against the server copy
+#[tauri::command]
+pub async fn recover_documents(
+    app: AppHandle,
+    session: State<'_, SessionManager>,
+    journal: State<'_, Journal>,
+    library: State<'_, Library>,
+) -> Result<Vec<RecoverableDocument>, String> {
+    let account = account(&session)?;
+    let mut recoverable = Vec::new();
+    for (entry, stored) in unsaved_entries(&journal, &library, &account)? {
+        // Prefer the live server copy; the library copy stands in when offline
+        let server = match &entry.project_uuid {
+            Some(project_uuid) => api_client::with_session(&app, |client| {
+                let project_uuid = project_uuid.clone();
+                let document_uuid = entry.document_uuid.clone();
+                async move { client.get_document(&document_uuid, &project_uuid).await }
+            })
+            .await
+            .inspect_err(|e| log::debug!("Using the library copy of {} for recovery: {}", entry.document_uuid, e))
+            .ok()
+            .or(stored),
+            None => stored,
+        };
+
+        recoverable.push(RecoverableDocument {
+            preview: preview(&entry.content),
+            server_version: server.as_ref().map(|d| d.updated_at.clone()),
+            diff: merge::diff(server.as_ref().map(|d| d.content.as_str()).unwrap_or(""), &entry.content),
+            entry,
+        });
+    }
+    Ok(recoverable)
+}
+
+/// Tauri command to drop the journaled edits of a document
+#[tauri::command]
+pub async fn discard_recovered_document(
+    session: State<'_, SessionManager>,
+    journal: State<'_, Journal>,
+    document_uuid: String,
+) -> Result<(), String> {
+    log::info!("Discarding journaled edits of document {}", document_uuid);
+    journal.remove(&account(&session)?, &document_uuid)
+}
@@ -1,15 +1,51 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
+pub mod api_client;
+pub mod api_types;
 mod auth;
-mod config;
+mod backend;
+mod capabilities;
+mod chat;
+pub mod config;
+mod connectivity;
+mod demo_gateway;
+mod diagnostics;
+mod documents;
+mod docx;
+mod git_mirror;
+mod history;
+mod http_cache;
 mod http_client;
+mod inspector;
+mod journal;
+mod library;
+mod locks;
+mod logging;
+mod markdown;
+mod merge;
+mod outbox;
+mod requests;
+pub mod session;
+mod shutdown;
+mod sse;
+mod store_schema;
+mod timestamp;
 
-use tauri::RunEvent;
+use config::AppConfig;
+use diagnostics::StartupReport;
+use git_mirror::GitMirror;
+use history::History;
+use journal::Journal;
+use library::Library;
+use outbox::Outbox;
+use session::{FileStorage, HttpAuthBackend, SessionManager};
+use shutdown::Shutdown;
+use tauri::{Manager, RunEvent, WindowEvent};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Initialize logging
-    env_logger::init();
+    logging::init();
     log::info!("Starting Editron application");
 
     tauri::Builder::default()
@@ -20,41 +56,131 @@ pub fn run() {
             auth::check_login,
             auth::get_profile,
             auth::logout,
-            auth::get_access_token,
             auth::start_gmail_api_connect_flow,
-            auth::open_url
+            auth::open_url,
+            backend::backend_request,
+            capabilities::get_backend_capabilities,
+            chat::chat_query,
+            chat::cancel_chat,
+            chat::get_chat_streams,
+            chat::attach_chat_stream,
+            documents::upload_document,
+            documents::agent_edit,
+            documents::download_pdf,
+            docx::preview_docx,
+            docx::import_docx,
+            docx::export_docx,
+            markdown::import_markdown,
+            markdown::export_markdown,
+            markdown::import_markdown_folder,
+            requests::cancel_request,
+            connectivity::get_connectivity,
+            connectivity::check_connectivity,
+            http_cache::invalidate_cache,
+            inspector::get_request_log,
+            inspector::clear_request_log,
+            inspector::export_request_log,
+            inspector::get_request_body_capture,
+            inspector::set_request_body_capture,
+            git_mirror::get_git_mirror,
+            git_mirror::enable_git_mirror,
+            git_mirror::disable_git_mirror,
+            history::list_document_versions,
+            history::get_document_version,
+            history::diff_document_versions,
+            history::record_agent_edit,
+            history::restore_document_version,
+            journal::journal_document,
+            journal::recover_documents,
+            journal::discard_recovered_document,
+            library::list_cached_projects,
+            library::list_cached_documents,
+            library::get_cached_document,
+            merge::merge_document_versions,
+            outbox::queue_document_change,
+            outbox::get_outbox,
+            outbox::resolve_sync_conflict,
+            logging::get_log_level,
+            logging::set_log_level,
+            diagnostics::export_diagnostics,
+            diagnostics::run_diagnostics,
+            diagnostics::get_startup_report
         ])
         .setup(|app| {
             let handle = app.handle().clone();
+            logging::attach_log_dir(&handle);
             
             log::info!("Setting up application");
             
+            // Failed steps are reported with the startup checks instead of only being logged
+            let mut setup_errors = Vec::new();
+
+            let config = AppConfig::load();
+            if config.demo.enabled {
+                log::info!("Demo mode enabled - using the built-in fake gateway");
+                demo_gateway::start(config.demo.port, config.backend.api_version.clone());
+            }
+
+            // Session state is shared with commands through Tauri's managed state
+            app.manage(SessionManager::new(
+                config.clone(),
+                FileStorage::new(handle.clone(), config.demo.enabled),
+                HttpAuthBackend::new(config),
+            ));
+            app.manage(Library::new(handle.clone()));
+            app.manage(Journal::new(handle.clone()));
+            app.manage(History::new(handle.clone()));
+            app.manage(GitMirror::new());
+            app.manage(Outbox::new());
+            app.manage(Shutdown::new());
+            app.manage(StartupReport::new());
+            let session = session::manager(&handle);
+
             // Initialize stores
             if let Err(e) = auth::initialize_stores(&handle) {
-                log::error!("Failed to initialize stores: {}", e);
+                setup_errors.push(diagnostics::setup_failure("initialize_stores", e));
             }
             
             // Load existing servers and tokens
-            if let Err(e) = auth::load_servers(&handle) {
-                log::error!("Failed to load servers: {}", e);
+            if let Err(e) = session.load_servers() {
+                setup_errors.push(diagnostics::setup_failure("load_servers", e));
             }
             
-            if let Err(e) = auth::load_servers_token(&handle) {
-                log::error!("Failed to load server tokens: {}", e);
+            if let Err(e) = session.load_tokens() {
+                setup_errors.push(diagnostics::setup_failure("load_tokens", e));
             }
 
+            inspector::load_settings(&handle);
+
+            connectivity::start_monitor(handle.clone());
+            capabilities::start_discovery(handle.clone());
+            outbox::start_sync(handle.clone());
+            journal::start_recovery_check(handle.clone());
+            diagnostics::start_startup_checks(handle.clone(), setup_errors);
+
             log::info!("Application setup completed");
             Ok(())
         })
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
-        .run(|_app_handle, event| {
+        .run(|app_handle, event| {
             match event {
                 RunEvent::Ready => {
                     log::info!("Application is ready");
                 }
-                RunEvent::ExitRequested { .. } => {
+                // Closing the window keeps it open until pending work is saved
+                RunEvent::WindowEvent { event: WindowEvent::CloseRequested { api, .. }, .. } => {
+                    if !app_handle.state::<Shutdown>().is_finished() {
+                        api.prevent_close();
+                        shutdown::begin(app_handle, 0);
+                    }
+                }
+                RunEvent::ExitRequested { code, api, .. } => {
                     log::info!("Application exit requested");
+                    if !app_handle.state::<Shutdown>().is_finished() {
+                        api.prevent_exit();
+                        shutdown::begin(app_handle, code.unwrap_or(0));
+                    }
                 }
                 _ => {}
             }
@@ -0,0 +1,419 @@
+use crate::api_client;
+use crate::api_types::{Document, DocumentStatus, Project};
+use crate::git_mirror;
+use crate::history::{self, VersionSource};
+use crate::journal::Journal;
+use crate::locks::lock;
+use crate::outbox;
+use crate::session::{self, SessionManager};
+use crate::timestamp;
+use reqwest::Method;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+/// `MIGRATIONS[i]` upgrades a database from `user_version` i to i + 1
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE projects (
+        uuid TEXT PRIMARY KEY,
+        id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        custom_instructions TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        synced_at INTEGER NOT NULL
+    );
+    CREATE TABLE documents (
+        uuid TEXT PRIMARY KEY,
+        id INTEGER NOT NULL,
+        project_uuid TEXT,
+        title TEXT NOT NULL,
+        content TEXT NOT NULL,
+        status TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        synced_at INTEGER NOT NULL
+    );
+    CREATE INDEX documents_by_project ON documents (project_uuid);",
+    "CREATE TABLE outbox (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_uuid TEXT NOT NULL,
+        project_uuid TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        payload TEXT,
+        base_version TEXT NOT NULL,
+        base_content TEXT,
+        state TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        server_version TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX outbox_by_document ON outbox (document_uuid);
+    CREATE TABLE pushed_versions (
+        document_uuid TEXT NOT NULL,
+        from_version TEXT NOT NULL,
+        to_version TEXT NOT NULL,
+        PRIMARY KEY (document_uuid, from_version)
+    );",
+    "CREATE TABLE versions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_uuid TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        title TEXT NOT NULL,
+        source TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX versions_by_document ON versions (document_uuid, created_at);
+    CREATE INDEX versions_by_hash ON versions (hash);",
+    "CREATE TABLE git_mirrors (
+        project_uuid TEXT PRIMARY KEY,
+        path TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );",
+];
+
+const PROJECT_COLUMNS: &str = "id, uuid, name, description, custom_instructions, created_at, updated_at";
+const DOCUMENT_COLUMNS: &str = "id, uuid, title, content, status, created_at, updated_at";
+
+struct OpenDatabase {
+    account: String,
+    conn: Connection,
+}
+
+/// Local copy of the projects and documents of the signed-in account, so they
+/// can be read without the gateway. Each account has its own SQLite database
+/// in the app data directory; the connection is reopened when the account changes.
+pub struct Library {
+    app: AppHandle,
+    db: Mutex<Option<OpenDatabase>>,
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", (from + 1) as i64)?;
+        tx.commit()?;
+        log::info!("Migrated library database to version {}", from + 1);
+    }
+    Ok(())
+}
+
+fn status_name(status: DocumentStatus) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn parse_status(name: String) -> DocumentStatus {
+    serde_json::from_value(Value::String(name)).unwrap_or(DocumentStatus::Ready)
+}
+
+fn project_from_row(row: &Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        uuid: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        custom_instructions: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn document_from_row(row: &Row) -> rusqlite::Result<Document> {
+    Ok(Document {
+        id: row.get(0)?,
+        uuid: row.get(1)?,
+        title: row.get(2)?,
+        content: row.get(3)?,
+        status: parse_status(row.get(4)?),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn upsert_project(conn: &Connection, project: &Project, synced_at: u64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO projects (uuid, id, name, description, custom_instructions, created_at, updated_at, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT (uuid) DO UPDATE SET
+            id = excluded.id, name = excluded.name, description = excluded.description,
+            custom_instructions = excluded.custom_instructions, created_at = excluded.created_at,
+            updated_at = excluded.updated_at, synced_at = excluded.synced_at",
+        params![
+            project.uuid,
+            project.id,
+            project.name,
+            project.description,
+            project.custom_instructions,
+            project.created_at,
+            project.updated_at,
+            synced_at as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Inserts or updates a document; a missing `project_uuid` keeps the stored one
+fn upsert_document(conn: &Connection, project_uuid: Option<&str>, document: &Document, synced_at: u64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO documents (uuid, id, project_uuid, title, content, status, created_at, updated_at, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (uuid) DO UPDATE SET
+            id = excluded.id, project_uuid = COALESCE(excluded.project_uuid, documents.project_uuid),
+            title = excluded.title, content = excluded.content, status = excluded.status,
+            created_at = excluded.created_at, updated_at = excluded.updated_at, synced_at = excluded.synced_at",
+        params![
+            document.uuid,
+            document.id,
+            project_uuid,
+            document.title,
+            document.content,
+            status_name(document.status),
+            document.created_at,
+            document.updated_at,
+            synced_at as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+impl Library {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app, db: Mutex::new(None) }
+    }
+
+    /// Runs `f` on the database of `account`, opening and migrating it first if needed
+    pub fn with_db<T>(&self, account: &str, f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+        let mut db = lock(&self.db);
+        if db.as_ref().map(|open| open.account.as_str()) != Some(account) {
+            let dir = self.app.path().app_data_dir().map_err(|e| e.to_string())?.join("library");
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+            let path = dir.join(format!("{}.sqlite3", account));
+            let mut conn = Connection::open(&path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+            conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+            migrate(&mut conn).map_err(|e| format!("Failed to migrate {:?}: {}", path, e))?;
+            log::info!("Opened library database {:?}", path);
+
+            *db = Some(OpenDatabase { account: account.to_string(), conn });
+        }
+
+        let open = db.as_mut().expect("database was opened above");
+        f(&mut open.conn).map_err(|e| e.to_string())
+    }
+
+    /// Stores projects; with `complete` the list replaces all projects, and the
+    /// documents of projects that are gone are dropped too
+    pub fn store_projects(&self, account: &str, projects: &[Project], complete: bool) -> Result<(), String> {
+        let synced_at = timestamp::now_millis();
+        self.with_db(account, |conn| {
+            let tx = conn.transaction()?;
+            for project in projects {
+                upsert_project(&tx, project, synced_at)?;
+            }
+            if complete {
+                tx.execute("DELETE FROM projects WHERE synced_at <> ?1", params![synced_at as i64])?;
+                tx.execute(
+                    "DELETE FROM documents WHERE project_uuid IS NOT NULL AND project_uuid NOT IN (SELECT uuid FROM projects)",
+                    [],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    pub fn remove_project(&self, account: &str, project_uuid: &str) -> Result<(), String> {
+        self.with_db(account, |conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM documents WHERE project_uuid = ?1", params![project_uuid])?;
+            tx.execute("DELETE FROM projects WHERE uuid = ?1", params![project_uuid])?;
+            tx.commit()
+        })
+    }
+
+    /// Stores documents; with `complete` they replace all documents of the project
+    pub fn store_documents(&self, account: &str, project_uuid: Option<&str>, documents: &[Document], complete: bool) -> Result<(), String> {
+        let synced_at = timestamp::now_millis();
+        self.with_db(account, |conn| {
+            let tx = conn.transaction()?;
+            for document in documents {
+                upsert_document(&tx, project_uuid, document, synced_at)?;
+            }
+            if let (true, Some(project_uuid)) = (complete, project_uuid) {
+                tx.execute(
+                    "DELETE FROM documents WHERE project_uuid = ?1 AND synced_at <> ?2",
+                    params![project_uuid, synced_at as i64],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    pub fn remove_document(&self, account: &str, document_uuid: &str) -> Result<(), String> {
+        self.with_db(account, |conn| {
+            conn.execute("DELETE FROM documents WHERE uuid = ?1", params![document_uuid])?;
+            Ok(())
+        })
+    }
+
+    pub fn projects(&self, account: &str) -> Result<Vec<Project>, String> {
+        self.with_db(account, |conn| {
+            let mut stmt = conn.prepare(&format!("SELECT {} FROM projects ORDER BY updated_at DESC", PROJECT_COLUMNS))?;
+            let projects = stmt.query_map([], project_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(projects)
+        })
+    }
+
+    pub fn documents(&self, account: &str, project_uuid: &str) -> Result<Vec<Document>, String> {
+        self.with_db(account, |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM documents WHERE project_uuid = ?1 ORDER BY updated_at DESC",
+                DOCUMENT_COLUMNS
+            ))?;
+            let documents = stmt.query_map(params![project_uuid], document_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(documents)
+        })
+    }
+
+    /// The project a stored document belongs to
+    pub fn document_project(&self, account: &str, document_uuid: &str) -> Result<Option<String>, String> {
+        self.with_db(account, |conn| {
+            conn.query_row(
+                "SELECT project_uuid FROM documents WHERE uuid = ?1",
+                params![document_uuid],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()
+            .map(Option::flatten)
+        })
+    }
+
+    pub fn document(&self, account: &str, document_uuid: &str) -> Result<Option<Document>, String> {
+        self.with_db(account, |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM documents WHERE uuid = ?1", DOCUMENT_COLUMNS),
+                params![document_uuid],
+                document_from_row,
+            )
+            .optional()
+        })
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &Value) -> Result<T, String> {
+    serde_json::from_value(body.clone()).map_err(|e| e.to_string())
+}
+
+/// Mirrors a successful gateway response for projects or documents into the library
+pub fn record_response(app: &AppHandle, method: &Method, url: &url::Url, body: &Value) {
+    let session = session::manager(app);
+    let Some(account) = session.current_account_key() else { return };
+    let prefix = session.config().backend_api_path_prefix();
+    let Some(rest) = url.path().strip_prefix(&prefix) else { return };
+    let segments: Vec<&str> = rest.trim_matches('/').split('/').collect();
+    let project_uuid = url
+        .query_pairs()
+        .find(|(key, _)| key == "projectUuid")
+        .map(|(_, value)| value.into_owned());
+
+    let library = app.state::<Library>();
+    let result = match (method.as_str(), segments.as_slice()) {
+        ("GET", ["projects"]) => parse::<Vec<Project>>(body).and_then(|p| library.store_projects(&account, &p, true)),
+        ("POST", ["projects"]) | ("GET", ["projects", _, "details"]) | ("PATCH", ["projects", _, "update"]) => {
+            parse::<Project>(body).and_then(|p| library.store_projects(&account, &[p], false))
+        }
+        ("DELETE", ["projects", uuid, "delete"]) => library.remove_project(&account, uuid),
+        // Only the project-scoped listing is complete enough to replace what is stored
+        ("GET", ["documents"]) if project_uuid.is_some() => parse::<Vec<Document>>(body)
+            .and_then(|d| library.store_documents(&account, project_uuid.as_deref(), &d, true)),
+        ("GET" | "PATCH", ["documents", _]) => parse::<Document>(body).and_then(|d| {
+            library.store_documents(&account, project_uuid.as_deref(), std::slice::from_ref(&d), false)?;
+            if *method == Method::PATCH {
+                app.state::<Journal>().confirm(&account, &d);
+                history::record(app, &account, &d, VersionSource::Save);
+            }
+            Ok(())
+        }),
+        ("DELETE", ["documents", uuid]) => {
+            git_mirror::remove_document(app, &account, uuid);
+            library.remove_document(&account, uuid)
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        log::warn!("Failed to update the local library from {}: {}", url.path(), e);
+    }
+}
+
+/// Adds a document returned by a typed API call to the library
+pub fn remember_document(app: &AppHandle, project_uuid: &str, document: &Document) {
+    let Some(account) = session::manager(app).current_account_key() else { return };
+    if let Err(e) = app.state::<Library>().store_documents(&account, Some(project_uuid), std::slice::from_ref(document), false) {
+        log::warn!("Failed to add document {} to the local library: {}", document.uuid, e);
+    }
+}
+
+/// The latest known contents of a document: the library copy with queued
+/// changes applied, or the gateway copy for documents never opened here
+pub async fn current_document(app: &AppHandle, account: &str, project_uuid: &str, document_uuid: &str) -> Result<Document, String> {
+    let library = app.state::<Library>();
+    if let Some(document) = library.document(account, document_uuid)? {
+        if let Some(document) = outbox::apply_pending(&library, account, vec![document])?.pop() {
+            return Ok(document);
+        }
+    }
+    api_client::with_session(app, |client| {
+        let project_uuid = project_uuid.to_string();
+        let document_uuid = document_uuid.to_string();
+        async move { client.get_document(&document_uuid, &project_uuid).await }
+    })
+    .await
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Tauri command to list the projects stored locally for the signed-in account
+#[tauri::command]
+pub async fn list_cached_projects(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+) -> Result<Vec<Project>, String> {
+    library.projects(&account(&session)?)
+}
+
+/// Tauri command to list the documents of a project stored locally, with queued changes applied
+#[tauri::command]
+pub async fn list_cached_documents(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    project_uuid: String,
+) -> Result<Vec<Document>, String> {
+    let account = account(&session)?;
+    let documents = library.documents(&account, &project_uuid)?;
+    outbox::apply_pending(&library, &account, documents)
+}
+
+/// Tauri command to read a document from the local library with queued changes applied;
+/// `None` when it was never fetched or a delete is queued
+#[tauri::command]
+pub async fn get_cached_document(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    document_uuid: String,
+) -> Result<Option<Document>, String> {
+    let account = account(&session)?;
+    let document = library.document(&account, &document_uuid)?;
+    Ok(outbox::apply_pending(&library, &account, document.into_iter().collect())?.pop())
+}
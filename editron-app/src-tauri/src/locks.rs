@@ -0,0 +1,8 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks a mutex, recovering the data if a previous holder panicked.
+/// Shared state is kept consistent between statements, so a panic in one
+/// command must not take every later caller down with it.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
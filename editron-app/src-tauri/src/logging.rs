@@ -0,0 +1,370 @@
+use crate::locks::lock;
+use crate::store_schema;
+use crate::timestamp;
+use log::{LevelFilter, Log, Metadata, Record};
+use regex::{Captures, Regex};
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// Name of the active log file in the app log directory
+pub const LOG_FILE_NAME: &str = "editron.log";
+/// The active file is rotated once it grows past this size
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Number of rotated files kept next to the active one
+const KEEP_FILES: usize = 5;
+/// Lines logged before the log directory is known are kept up to this count
+const MAX_PENDING_LINES: usize = 1000;
+/// Dependencies are capped at this level so they don't drown out the app's own logs
+const DEPENDENCY_MAX_LEVEL: LevelFilter = LevelFilter::Warn;
+
+lazy_static::lazy_static! {
+    static ref BEARER: Regex = Regex::new(r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/=-]+").unwrap();
+    static ref AUTHORIZATION: Regex = Regex::new(r#"(?i)(authorization["']?\s*[:=]\s*["']?)(?:[a-z]+\s+)?[^"',}\s]+"#).unwrap();
+    static ref JWT: Regex = Regex::new(r"\beyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*").unwrap();
+    /// `key: value` and `key=value` pairs whose key names a token, secret or password,
+    /// including prefixed keys such as `csrf_token`
+    static ref SECRET_PARAM: Regex = Regex::new(
+        r#"(?i)\b((?:[a-z0-9]+_)*(?:access_?token|refresh_?token|id_?token|token|code_?verifier|client_?secret|secret|password)["']?\s*[:=]\s*["']?)[^"'&,}\s]+"#
+    ).unwrap();
+    /// OAuth `code` and `state` only count as secrets inside a query string
+    static ref QUERY_CODE: Regex = Regex::new(r"(?i)([?&](?:code|state)=)[^&#\s]+").unwrap();
+    static ref EMAIL: Regex = Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+
+    static ref OUTPUT: Mutex<LogOutput> = Mutex::new(LogOutput {
+        dir: None,
+        file: None,
+        size: 0,
+        pending: Vec::new(),
+    });
+    static ref LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+    /// Per-target levels from `RUST_LOG`, e.g. `reqwest=warn`; they take precedence over `LEVEL`
+    static ref TARGET_LEVELS: Mutex<Vec<(String, LevelFilter)>> = Mutex::new(Vec::new());
+}
+
+/// Scrubs tokens, authorization codes, Authorization headers and email
+/// addresses from a log message
+pub fn redact(message: &str) -> String {
+    let message = BEARER.replace_all(message, "$1 [REDACTED]");
+    let message = AUTHORIZATION.replace_all(&message, "${1}[REDACTED]");
+    let message = JWT.replace_all(&message, "[REDACTED_JWT]");
+    let message = SECRET_PARAM.replace_all(&message, |caps: &Captures| format!("{}[REDACTED]", &caps[1]));
+    let message = QUERY_CODE.replace_all(&message, "${1}[REDACTED]");
+    EMAIL.replace_all(&message, "[REDACTED_EMAIL]").into_owned()
+}
+
+struct LogOutput {
+    dir: Option<PathBuf>,
+    file: Option<File>,
+    size: u64,
+    pending: Vec<String>,
+}
+
+impl LogOutput {
+    fn write_line(&mut self, line: &str) {
+        if self.file.is_none() {
+            if self.pending.len() < MAX_PENDING_LINES {
+                self.pending.push(line.to_string());
+            }
+            return;
+        }
+
+        if self.size + line.len() as u64 + 1 > MAX_FILE_BYTES {
+            self.rotate();
+        }
+        if let Some(file) = self.file.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                self.size += line.len() as u64 + 1;
+            }
+        }
+    }
+
+    /// Shifts `editron.log` -> `editron.1.log` -> ... and starts a new file
+    fn rotate(&mut self) {
+        let Some(dir) = self.dir.clone() else { return };
+        self.file = None;
+
+        let _ = std::fs::remove_file(rotated_path(&dir, KEEP_FILES));
+        for index in (1..KEEP_FILES).rev() {
+            let _ = std::fs::rename(rotated_path(&dir, index), rotated_path(&dir, index + 1));
+        }
+        let _ = std::fs::rename(dir.join(LOG_FILE_NAME), rotated_path(&dir, 1));
+
+        self.open(&dir);
+    }
+
+    fn open(&mut self, dir: &Path) {
+        match OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE_NAME)) {
+            Ok(file) => {
+                self.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+                self.file = Some(file);
+            }
+            Err(e) => eprintln!("Failed to open log file in {:?}: {}", dir, e),
+        }
+    }
+}
+
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("editron.{}.log", index))
+}
+
+/// Returns the active and rotated log files, newest first
+pub fn log_files(dir: &Path) -> Vec<PathBuf> {
+    std::iter::once(dir.join(LOG_FILE_NAME))
+        .chain((1..=KEEP_FILES).map(|index| rotated_path(dir, index)))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+fn is_app_target(target: &str) -> bool {
+    target.starts_with("editron") || target == "demo_gateway"
+}
+
+struct FileLogger;
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if let Some(level) = target_level(&lock(&TARGET_LEVELS), metadata.target()) {
+            return metadata.level() <= level;
+        }
+        let level = *lock(&LEVEL);
+        if is_app_target(metadata.target()) {
+            metadata.level() <= level
+        } else {
+            metadata.level() <= level.min(DEPENDENCY_MAX_LEVEL)
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let ts = timestamp::now_iso8601();
+        let message = redact(&record.args().to_string());
+        eprintln!("[{} {:<5} {}] {}", ts, record.level(), record.target(), message);
+
+        let line = json!({
+            "ts": ts,
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "msg": message,
+            "file": record.file(),
+            "line": record.line(),
+        });
+        lock(&OUTPUT).write_line(&line.to_string());
+    }
+
+    fn flush(&self) {
+        if let Some(file) = lock(&OUTPUT).file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+static LOGGER: FileLogger = FileLogger;
+
+fn parse_level(level: &str) -> Option<LevelFilter> {
+    level.trim().parse().ok()
+}
+
+/// Levels parsed from a `RUST_LOG` value such as `info,reqwest=warn,editron_lib::sync=debug`
+#[derive(Debug, Default, PartialEq)]
+struct Directives {
+    /// Level for targets without an override
+    default: Option<LevelFilter>,
+    /// Per-target levels; a bare target name enables everything for it
+    targets: Vec<(String, LevelFilter)>,
+    /// Directives that could not be understood
+    invalid: Vec<String>,
+}
+
+fn parse_directives(spec: &str) -> Directives {
+    let mut directives = Directives::default();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('=') {
+            Some((target, level)) => match parse_level(level) {
+                Some(level) if !target.trim().is_empty() => directives.targets.push((target.trim().to_string(), level)),
+                _ => directives.invalid.push(part.to_string()),
+            },
+            None => match parse_level(part) {
+                Some(level) => directives.default = Some(level),
+                None if part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':') => {
+                    directives.targets.push((part.to_string(), LevelFilter::Trace))
+                }
+                None => directives.invalid.push(part.to_string()),
+            },
+        }
+    }
+    directives
+}
+
+/// The level of the most specific override matching `target`, if any
+fn target_level(targets: &[(String, LevelFilter)], target: &str) -> Option<LevelFilter> {
+    targets
+        .iter()
+        .filter(|(prefix, _)| target == prefix || target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with("::")))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, level)| *level)
+}
+
+fn apply_level(level: LevelFilter) {
+    *lock(&LEVEL) = level;
+    let max_override = lock(&TARGET_LEVELS).iter().map(|(_, level)| *level).max();
+    log::set_max_level(max_override.map_or(level, |max| max.max(level)));
+}
+
+/// Installs the logger. Lines go to stderr right away and to the log file once
+/// `attach_log_dir` has run. The initial levels come from `RUST_LOG`.
+pub fn init() {
+    let directives = std::env::var("RUST_LOG")
+        .map(|v| parse_directives(&v))
+        .unwrap_or_default();
+
+    if log::set_logger(&LOGGER).is_ok() {
+        *lock(&TARGET_LEVELS) = directives.targets;
+        apply_level(directives.default.unwrap_or(LevelFilter::Info));
+        for invalid in directives.invalid {
+            log::warn!("Ignoring unrecognized RUST_LOG directive {:?}", invalid);
+        }
+    }
+}
+
+/// Opens the log file in the app log directory, writes lines logged during
+/// startup and restores the saved log level
+pub fn attach_log_dir(app: &AppHandle) {
+    match app.path().app_log_dir() {
+        Ok(dir) => {
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                log::error!("Failed to create log directory {:?}: {}", dir, e);
+                return;
+            }
+
+            {
+                let mut output = lock(&OUTPUT);
+                output.open(&dir);
+                output.dir = Some(dir.clone());
+                for line in std::mem::take(&mut output.pending) {
+                    output.write_line(&line);
+                }
+            }
+            log::info!("Writing logs to {:?}", dir.join(LOG_FILE_NAME));
+        }
+        Err(e) => log::error!("Failed to resolve app log directory: {}", e),
+    }
+
+    // The saved level applies unless `RUST_LOG` sets a default level itself
+    let env_default = std::env::var("RUST_LOG").ok().and_then(|v| parse_directives(&v).default);
+    if env_default.is_none() {
+        let saved = store_schema::load::<String>(app, &store_schema::LOG_LEVEL)
+            .unwrap_or_else(|e| {
+                log::error!("Failed to load the saved log level: {}", e);
+                None
+            })
+            .and_then(|level| parse_level(&level));
+        if let Some(level) = saved {
+            apply_level(level);
+        }
+    }
+}
+
+/// Returns the directory logs are written to, once known
+pub fn log_dir() -> Option<PathBuf> {
+    lock(&OUTPUT).dir.clone()
+}
+
+/// Tauri command to get the current log level
+#[tauri::command]
+pub async fn get_log_level() -> Result<String, String> {
+    Ok(lock(&LEVEL).as_str().to_lowercase())
+}
+
+/// Tauri command to change the log level at runtime; the level is kept across restarts
+#[tauri::command]
+pub async fn set_log_level(app: AppHandle, level: String) -> Result<(), String> {
+    let filter = parse_level(&level).ok_or_else(|| format!("Unknown log level: {}", level))?;
+    apply_level(filter);
+
+    store_schema::save_with(&app, &store_schema::LOG_LEVEL, || filter.as_str().to_lowercase())?;
+
+    log::info!("Log level set to {}", filter);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_tokens_and_credentials() {
+        assert_eq!(redact("Authorization: Bearer abc.def-123"), "Authorization: [REDACTED]");
+        assert_eq!(redact("sent with bearer abc.def-123"), "sent with bearer [REDACTED]");
+        assert_eq!(redact(r#"{"refresh_token":"r-123","ok":true}"#), r#"{"refresh_token":"[REDACTED]","ok":true}"#);
+        assert_eq!(redact("csrf_token=abc&page=2"), "csrf_token=[REDACTED]&page=2");
+        assert_eq!(redact("password: hunter2"), "password: [REDACTED]");
+        assert_eq!(redact("client_secret=s3cr3t"), "client_secret=[REDACTED]");
+        assert_eq!(
+            redact("token eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiI3In0.c2ln"),
+            "token [REDACTED_JWT]"
+        );
+        assert_eq!(redact("Signed in as jane.doe@example.com"), "Signed in as [REDACTED_EMAIL]");
+    }
+
+    #[test]
+    fn redacts_oauth_code_and_state_in_query_strings() {
+        assert_eq!(
+            redact("GET /auth/callback?code=4/0Ab-xyz&state=k9f2 HTTP/1.1"),
+            "GET /auth/callback?code=[REDACTED]&state=[REDACTED] HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn keeps_messages_without_secrets() {
+        for message in [
+            "API request failed: status code: 404",
+            "exit code=1",
+            "Loaded 3 tokens: ok",
+            "Restored state: idle",
+            "GET /api/v1/documents?page=2",
+        ] {
+            assert_eq!(redact(message), message);
+        }
+    }
+
+    #[test]
+    fn parses_a_default_level_with_target_overrides() {
+        let directives = parse_directives("info, reqwest=warn,editron_lib::sync=debug");
+        assert_eq!(directives.default, Some(LevelFilter::Info));
+        assert_eq!(
+            directives.targets,
+            vec![
+                ("reqwest".to_string(), LevelFilter::Warn),
+                ("editron_lib::sync".to_string(), LevelFilter::Debug),
+            ]
+        );
+        assert!(directives.invalid.is_empty());
+    }
+
+    #[test]
+    fn bare_targets_enable_all_levels_and_unknown_directives_are_reported() {
+        let directives = parse_directives("hyper,reqwest=loud,=info,warn=");
+        assert_eq!(directives.default, None);
+        assert_eq!(directives.targets, vec![("hyper".to_string(), LevelFilter::Trace)]);
+        assert_eq!(directives.invalid, vec!["reqwest=loud", "=info", "warn="]);
+    }
+
+    #[test]
+    fn the_most_specific_target_wins() {
+        let targets = vec![
+            ("editron_lib".to_string(), LevelFilter::Info),
+            ("editron_lib::sync".to_string(), LevelFilter::Trace),
+        ];
+        assert_eq!(target_level(&targets, "editron_lib::sync::outbox"), Some(LevelFilter::Trace));
+        assert_eq!(target_level(&targets, "editron_lib::auth"), Some(LevelFilter::Info));
+        assert_eq!(target_level(&targets, "editron_lib_extra"), None);
+        assert_eq!(target_level(&targets, "reqwest"), None);
+    }
+}
@@ -0,0 +1,845 @@
+use crate::api_client;
+use crate::api_types::{Document, Project, UpdateDocumentRequest};
+use crate::capabilities::{self, Feature};
+use crate::docx::{self, DocxMetadata};
+use crate::http_cache::{self, HttpCache};
+use crate::library::{self, Library};
+use crate::requests::{self, RequestOutcome};
+use crate::session::{self, SessionManager};
+use crate::timestamp;
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use scraper::{ElementRef, Html, Node as HtmlNode};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value as YamlValue};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+/// File extensions picked up by a folder import
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+lazy_static::lazy_static! {
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+    /// Starts of a paragraph that Markdown would read as a heading, quote, list or rule
+    static ref BLOCK_START: Regex = Regex::new(r"^(#{1,6}(\s|$)|>|[-+*](\s|$)|\d{1,9}[.)](\s|$)|={3,}|-{3,})").unwrap();
+    static ref BACKTICKS: Regex = Regex::new(r"`+").unwrap();
+}
+
+/// YAML front matter of a Markdown file. The keys Editron knows are mapped to
+/// document and project metadata; any others are kept as they are.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Name of the project the document belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(rename = "projectUuid", skip_serializing_if = "Option::is_none")]
+    pub project_uuid: Option<String>,
+    #[serde(rename = "documentUuid", skip_serializing_if = "Option::is_none")]
+    pub document_uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, YamlValue>,
+}
+
+/// A Markdown file converted to editor HTML
+#[derive(Serialize, Clone, Debug)]
+pub struct MarkdownImport {
+    pub title: String,
+    pub html: String,
+    #[serde(rename = "frontMatter")]
+    pub front_matter: FrontMatter,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FailedImport {
+    pub file: String,
+    pub error: String,
+}
+
+/// A file that was imported, but not quite as written
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportWarning {
+    pub file: String,
+    pub message: String,
+}
+
+/// Outcome of importing a folder; one file failing does not stop the others
+#[derive(Serialize, Clone, Debug)]
+pub struct MarkdownFolderImport {
+    pub documents: Vec<Document>,
+    pub failed: Vec<FailedImport>,
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Splits leading `---` fenced YAML from the Markdown body
+fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let text = text.trim_start_matches('\u{feff}');
+    let Some(rest) = text.strip_prefix("---").and_then(|r| r.strip_prefix('\n').or_else(|| r.strip_prefix("\r\n"))) else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    // Never closed: not front matter after all
+    (None, text)
+}
+
+fn scalar(value: YamlValue) -> Option<String> {
+    match value {
+        YamlValue::String(s) => Some(s),
+        YamlValue::Number(n) => Some(n.to_string()),
+        YamlValue::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_front_matter(yaml: &str) -> Result<FrontMatter, String> {
+    let mapping = match serde_yaml::from_str::<YamlValue>(yaml).map_err(|e| format!("Invalid front matter: {}", e))? {
+        YamlValue::Mapping(mapping) => mapping,
+        YamlValue::Null => Mapping::new(),
+        _ => return Err("Front matter is not a mapping".to_string()),
+    };
+
+    let mut front_matter = FrontMatter::default();
+    for (key, value) in mapping {
+        let Some(key) = scalar(key) else { continue };
+        let field = match key.as_str() {
+            "title" => &mut front_matter.title,
+            "project" => &mut front_matter.project,
+            "projectUuid" | "project_uuid" => &mut front_matter.project_uuid,
+            "documentUuid" | "document_uuid" | "uuid" => &mut front_matter.document_uuid,
+            "created" | "createdAt" | "date" => &mut front_matter.created,
+            "updated" | "updatedAt" => &mut front_matter.updated,
+            _ => {
+                front_matter.extra.insert(key, value);
+                continue;
+            }
+        };
+        *field = scalar(value);
+    }
+    Ok(front_matter)
+}
+
+/// Whether the item starting at `events[item]` has a task checkbox, and its state
+fn task_marker(events: &[Event], item: usize) -> Option<bool> {
+    // Loose items hold the marker inside their first paragraph
+    let marker = match events.get(item + 1)? {
+        Event::Start(Tag::Paragraph) => events.get(item + 2)?,
+        event => event,
+    };
+    match marker {
+        Event::TaskListMarker(checked) => Some(*checked),
+        _ => None,
+    }
+}
+
+/// Converts CommonMark with the GFM extensions (tables, task lists,
+/// strikethrough, footnotes) to HTML. Task lists use the editor's markup.
+pub fn to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events: Vec<Event> = Parser::new_ext(markdown, options).collect();
+
+    // Whether each open list is a task list
+    let mut task_lists = Vec::new();
+    let mut converted = Vec::with_capacity(events.len());
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::List(first)) => {
+                let tasks = first.is_none() && matches!(events.get(i + 1), Some(Event::Start(Tag::Item))) && task_marker(&events, i + 1).is_some();
+                task_lists.push(tasks);
+                if tasks {
+                    converted.push(Event::Html(CowStr::from("<ul data-type=\"taskList\">")));
+                    continue;
+                }
+            }
+            Event::End(TagEnd::List(_)) => {
+                task_lists.pop();
+            }
+            Event::Start(Tag::Item) if task_lists.last() == Some(&true) => {
+                let checked = task_marker(&events, i).unwrap_or(false);
+                converted.push(Event::Html(CowStr::from(format!(
+                    "<li data-type=\"taskItem\" data-checked=\"{}\">",
+                    checked
+                ))));
+                continue;
+            }
+            Event::TaskListMarker(_) => continue,
+            _ => {}
+        }
+        converted.push(event.clone());
+    }
+
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, converted.into_iter());
+    html
+}
+
+/// Converts a Markdown file to editor HTML. The title comes from the front
+/// matter, then the first heading, then the file name.
+pub fn import(file_name: &str, markdown: &str) -> Result<MarkdownImport, String> {
+    let (yaml, body) = split_front_matter(markdown);
+    let front_matter = yaml.map(parse_front_matter).transpose()?.unwrap_or_default();
+    let html = to_html(body);
+
+    let heading = || {
+        body.lines()
+            .find_map(|line| line.strip_prefix("# "))
+            .map(|title| title.trim().trim_end_matches('#').trim().to_string())
+            .filter(|title| !title.is_empty())
+    };
+    let title = front_matter
+        .title
+        .clone()
+        .or_else(heading)
+        .unwrap_or_else(|| {
+            Path::new(file_name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+    Ok(MarkdownImport { title, html, front_matter })
+}
+
+/// Converts editor HTML to Markdown, with GFM tables, task lists and footnotes
+pub fn from_html(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut writer = MarkdownWriter::default();
+    let mut markdown = writer.blocks(fragment.root_element()).join("\n\n");
+    if !writer.footnotes.is_empty() {
+        markdown.push_str("\n\n");
+        markdown.push_str(&writer.footnotes.join("\n"));
+    }
+    markdown.push('\n');
+    markdown
+}
+
+/// Front matter and body of an exported document
+pub fn export(document: &Document, project: Option<&Project>) -> Result<String, String> {
+    let front_matter = FrontMatter {
+        title: Some(document.title.clone()),
+        project: project.map(|p| p.name.clone()),
+        project_uuid: project.map(|p| p.uuid.clone()),
+        document_uuid: Some(document.uuid.clone()),
+        created: Some(document.created_at.clone()).filter(|d| !d.is_empty()),
+        updated: Some(document.updated_at.clone()).filter(|d| !d.is_empty()),
+        extra: BTreeMap::new(),
+    };
+    let yaml = serde_yaml::to_string(&front_matter).map_err(|e| e.to_string())?;
+    Ok(format!("---\n{}---\n\n{}", yaml, from_html(&document.content)))
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '~' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Wraps inline Markdown in a delimiter, keeping surrounding spaces outside it
+fn delimit(inner: &str, delimiter: &str) -> String {
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        return inner.to_string();
+    }
+    let leading = &inner[..inner.len() - inner.trim_start().len()];
+    let trailing = &inner[inner.trim_end().len()..];
+    format!("{}{}{}{}{}", leading, delimiter, trimmed, delimiter, trailing)
+}
+
+/// A backtick fence longer than any run of backticks in `text`
+fn fence_for(text: &str, minimum: usize) -> String {
+    let longest = BACKTICKS.find_iter(text).map(|m| m.len()).max().unwrap_or(0);
+    "`".repeat(minimum.max(longest + 1))
+}
+
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() { prefix.trim_end().to_string() } else { format!("{}{}", prefix, line) }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Footnote label from a `#label` link or an id
+fn footnote_label(value: &str) -> String {
+    value.trim_start_matches('#').to_string()
+}
+
+fn has_class(element: &ElementRef, class: &str) -> bool {
+    element.value().classes().any(|c| c == class)
+}
+
+#[derive(Default)]
+struct MarkdownWriter {
+    footnotes: Vec<String>,
+}
+
+impl MarkdownWriter {
+    /// Markdown of each block of a container; loose inline content becomes a paragraph
+    fn blocks(&mut self, parent: ElementRef) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut loose = String::new();
+
+        for node in parent.children() {
+            let element = match node.value() {
+                HtmlNode::Text(text) => {
+                    loose.push_str(&WHITESPACE.replace_all(&escape_text(text), " "));
+                    continue;
+                }
+                HtmlNode::Element(_) => ElementRef::wrap(node).expect("element nodes wrap"),
+                _ => continue,
+            };
+            let name = element.value().name();
+            if !is_block(name) {
+                loose.push_str(&self.inline(element));
+                continue;
+            }
+            push_paragraph(&mut blocks, &std::mem::take(&mut loose));
+
+            match name {
+                "p" => push_paragraph(&mut blocks, &self.inline_children(element)),
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    let level = name[1..].parse().unwrap_or(1);
+                    blocks.push(format!("{} {}", "#".repeat(level), self.inline_children(element).trim()));
+                }
+                "ul" | "ol" => blocks.push(self.list(element)),
+                "blockquote" => {
+                    let inner = self.blocks(element).join("\n\n");
+                    blocks.push(indent(&inner, "> ", "> "));
+                }
+                "pre" => {
+                    let text: String = element.text().collect();
+                    let language = element
+                        .children()
+                        .filter_map(ElementRef::wrap)
+                        .find(|e| e.value().name() == "code")
+                        .and_then(|code| code.value().classes().find_map(|c| c.strip_prefix("language-").map(String::from)))
+                        .unwrap_or_default();
+                    let fence = fence_for(&text, 3);
+                    blocks.push(format!("{}{}\n{}\n{}", fence, language, text.trim_end_matches('\n'), fence));
+                }
+                "table" => blocks.push(self.table(element)),
+                "hr" => blocks.push("---".to_string()),
+                "div" if has_class(&element, "footnote-definition") => {
+                    let label = element.value().attr("id").map(footnote_label).unwrap_or_default();
+                    let text = element
+                        .children()
+                        .filter_map(ElementRef::wrap)
+                        .filter(|e| !has_class(e, "footnote-definition-label"))
+                        .map(|e| if e.value().name() == "p" { self.inline_children(e) } else { self.inline(e) })
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    self.footnotes.push(format!("[^{}]: {}", label, text.trim()));
+                }
+                _ => blocks.extend(self.blocks(element)),
+            }
+        }
+        push_paragraph(&mut blocks, &loose);
+        blocks
+    }
+
+    fn list(&mut self, list: ElementRef) -> String {
+        let ordered = list.value().name() == "ol";
+        let tasks = list.value().attr("data-type") == Some("taskList");
+        let start: u64 = list.value().attr("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+        let list_items = list.children().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "li");
+
+        let mut items = Vec::new();
+        for (number, item) in (start..).zip(list_items) {
+            let checkbox = item
+                .children()
+                .filter_map(ElementRef::wrap)
+                .find(|e| e.value().name() == "input" && e.value().attr("type") == Some("checkbox"));
+            let checked = match item.value().attr("data-checked") {
+                Some(checked) => Some(checked == "true"),
+                None => checkbox.map(|c| c.value().attr("checked").is_some()),
+            };
+
+            let mut marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+            if let Some(checked) = checked.filter(|_| tasks || checkbox.is_some()) {
+                marker.push_str(if checked { "[x] " } else { "[ ] " });
+            }
+            // Items without paragraphs stay tight, nested lists included
+            let loose = item.children().filter_map(ElementRef::wrap).any(|e| e.value().name() == "p");
+            let content = self.blocks(item).join(if loose { "\n\n" } else { "\n" });
+            let continuation = " ".repeat(if ordered { marker.len().min(4) } else { 2 });
+            items.push(indent(if content.is_empty() { " " } else { &content }, &marker, &continuation).trim_end().to_string());
+        }
+        items.join("\n")
+    }
+
+    fn table(&mut self, table: ElementRef) -> String {
+        let mut rows = Vec::new();
+        for child in table.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "tr" => rows.push(child),
+                "thead" | "tbody" | "tfoot" => {
+                    rows.extend(child.children().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "tr"));
+                }
+                _ => {}
+            }
+        }
+
+        let first_row: Vec<ElementRef> = rows
+            .first()
+            .map(|row| row.children().filter_map(ElementRef::wrap).filter(|e| matches!(e.value().name(), "td" | "th")).collect())
+            .unwrap_or_default();
+        let cells: Vec<Vec<String>> = rows
+            .into_iter()
+            .map(|row| {
+                let mut cells = Vec::new();
+                for cell in row.children().filter_map(ElementRef::wrap).filter(|e| matches!(e.value().name(), "td" | "th")) {
+                    // GFM cells are single lines; blocks are joined with line breaks
+                    let text = self.blocks(cell).join("<br>").replace('\n', " ");
+                    cells.push(text);
+                    // Spanned columns are padded so the rows stay aligned
+                    let span: usize = cell.value().attr("colspan").and_then(|s| s.parse().ok()).unwrap_or(1);
+                    cells.extend(std::iter::repeat_n(String::new(), span.clamp(1, 63) - 1));
+                }
+                cells
+            })
+            .collect();
+        let columns = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let alignments: Vec<&str> = (0..columns).map(|i| alignment(first_row.get(i).copied())).collect();
+
+        let line = |cells: &[String]| {
+            let padded: Vec<&str> = (0..columns).map(|i| cells.get(i).map_or("", |c| c.as_str())).collect();
+            format!("| {} |", padded.join(" | "))
+        };
+        let mut lines = Vec::new();
+        let (header, body) = cells.split_first().map_or((&[][..], &[][..]), |(h, b)| (h.as_slice(), b));
+        lines.push(line(header));
+        lines.push(format!("| {} |", alignments.join(" | ")));
+        lines.extend(body.iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    fn inline_children(&mut self, parent: ElementRef) -> String {
+        let mut markdown = String::new();
+        for node in parent.children() {
+            match node.value() {
+                HtmlNode::Text(text) => markdown.push_str(&WHITESPACE.replace_all(&escape_text(text), " ")),
+                HtmlNode::Element(_) => markdown.push_str(&self.inline(ElementRef::wrap(node).expect("element nodes wrap"))),
+                _ => {}
+            }
+        }
+        markdown
+    }
+
+    fn inline(&mut self, element: ElementRef) -> String {
+        match element.value().name() {
+            "strong" | "b" => delimit(&self.inline_children(element), "**"),
+            "em" | "i" => delimit(&self.inline_children(element), "*"),
+            "s" | "strike" | "del" => delimit(&self.inline_children(element), "~~"),
+            "u" => format!("<u>{}</u>", self.inline_children(element)),
+            "code" => {
+                let text: String = element.text().collect();
+                let fence = fence_for(&text, 1);
+                let padding = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+                format!("{0}{1}{2}{1}{0}", fence, padding, text)
+            }
+            "br" => "\\\n".to_string(),
+            "img" => {
+                let alt = element.value().attr("alt").unwrap_or_default();
+                let src = element.value().attr("src").unwrap_or_default();
+                format!("![{}]({})", escape_text(alt), link_destination(src))
+            }
+            "sup" if has_class(&element, "footnote-reference") => {
+                let label = element
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .find_map(|a| a.value().attr("href"))
+                    .map(footnote_label)
+                    .unwrap_or_else(|| element.text().collect());
+                format!("[^{}]", label)
+            }
+            "a" => {
+                let text = self.inline_children(element);
+                match element.value().attr("href") {
+                    Some(href) => match element.value().attr("title") {
+                        Some(title) => format!("[{}]({} \"{}\")", text, link_destination(href), title.replace('"', "\\\"")),
+                        None => format!("[{}]({})", text, link_destination(href)),
+                    },
+                    None => text,
+                }
+            }
+            // Checkboxes are written as task list markers
+            "input" => String::new(),
+            _ => self.inline_children(element),
+        }
+    }
+}
+
+/// Delimiter row cell for a column, from the `text-align` of its first cell
+fn alignment(cell: Option<ElementRef>) -> &'static str {
+    let style = cell.and_then(|c| c.value().attr("style")).unwrap_or_default().replace(' ', "");
+    if style.contains("text-align:center") {
+        ":---:"
+    } else if style.contains("text-align:right") {
+        "---:"
+    } else if style.contains("text-align:left") {
+        ":---"
+    } else {
+        "---"
+    }
+}
+
+fn link_destination(url: &str) -> String {
+    if url.contains([' ', '(', ')']) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    }
+}
+
+fn push_paragraph(blocks: &mut Vec<String>, markdown: &str) {
+    // Hard breaks are followed by the source line break, collapsed to a space
+    let markdown = markdown.trim().replace("\\\n ", "\\\n");
+    let markdown = markdown.as_str();
+    if markdown.is_empty() {
+        return;
+    }
+    // Keep paragraph text from reading as another kind of block
+    match BLOCK_START.is_match(markdown) {
+        true => blocks.push(format!("\\{}", markdown)),
+        false => blocks.push(markdown.to_string()),
+    }
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "blockquote" | "pre" | "table" | "hr"
+            | "div" | "section" | "article" | "header" | "footer" | "main" | "aside" | "nav" | "figure"
+    )
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+/// Creates a document from one Markdown file. The gateway only creates
+/// documents from uploads, so the file goes up as DOCX and its converted HTML
+/// is saved over the gateway's own conversion right after. When that save
+/// fails the upload is deleted again, so a retry does not leave a duplicate;
+/// if it cannot be deleted either, it is kept and returned with a warning.
+async fn import_file(app: &AppHandle, projects: &[Project], project_uuid: &str, path: &Path) -> Result<(Document, Option<String>), String> {
+    let markdown = tokio::fs::read_to_string(path).await.map_err(|e| e.to_string())?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let converted = import(&file_name, &markdown)?;
+
+    // Front matter may name another project of this account
+    let front_matter = &converted.front_matter;
+    let project_uuid = projects
+        .iter()
+        .find(|p| front_matter.project_uuid.as_deref() == Some(p.uuid.as_str()) || front_matter.project.as_deref() == Some(p.name.as_str()))
+        .map_or(project_uuid, |p| p.uuid.as_str())
+        .to_string();
+
+    let now = timestamp::now_iso8601();
+    let metadata = DocxMetadata {
+        title: &converted.title,
+        subject: None,
+        description: None,
+        creator: None,
+        created: converted.front_matter.created.as_deref().unwrap_or(&now),
+        modified: converted.front_matter.updated.as_deref().unwrap_or(&now),
+    };
+    let bytes = docx::export(&converted.html, &metadata, &HashMap::new())?;
+    let upload_name = format!("{}.docx", path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
+    docx::validate(&upload_name, &bytes)?;
+
+    let uploaded = api_client::with_session(app, |client| {
+        let project_uuid = project_uuid.clone();
+        let upload_name = upload_name.clone();
+        let bytes = bytes.clone();
+        async move { client.upload_document(&project_uuid, &upload_name, bytes).await }
+    })
+    .await?;
+    let request = UpdateDocumentRequest {
+        content: Some(converted.html),
+        title: Some(converted.title),
+    };
+    let updated = api_client::with_session(app, |client| {
+        let project_uuid = project_uuid.clone();
+        let document_uuid = uploaded.uuid.clone();
+        let request = request.clone();
+        async move { client.update_document(&document_uuid, &project_uuid, &request).await }
+    })
+    .await;
+    let (document, warning) = match updated {
+        Ok(document) => (document, None),
+        Err(error) => {
+            let deleted = api_client::with_session(app, |client| {
+                let project_uuid = project_uuid.clone();
+                let document_uuid = uploaded.uuid.clone();
+                async move { client.delete_document(&document_uuid, &project_uuid).await }
+            })
+            .await;
+            match deleted {
+                Ok(()) => return Err(error),
+                Err(delete_error) => {
+                    log::warn!("Failed to delete document {} after a failed import: {}", uploaded.uuid, delete_error);
+                    let warning = format!("Imported with the server's conversion; saving the Markdown conversion failed: {}", error);
+                    (uploaded, Some(warning))
+                }
+            }
+        }
+    };
+
+    library::remember_document(app, &project_uuid, &document);
+    Ok((document, warning))
+}
+
+/// Tauri command to convert a Markdown file to editor HTML locally, with its
+/// front matter
+#[tauri::command]
+pub async fn import_markdown(file_name: String, contents: String) -> Result<MarkdownImport, String> {
+    log::info!("Converting {} ({} bytes) from Markdown", file_name, contents.len());
+    import(&file_name, &contents)
+}
+
+/// Tauri command to export a document as Markdown with YAML front matter into
+/// the Downloads folder, named after the document, and return its path
+#[tauri::command]
+pub async fn export_markdown(
+    app: AppHandle,
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    request_id: String,
+    project_uuid: String,
+    document_uuid: String,
+) -> Result<RequestOutcome<String>, String> {
+    log::info!("Exporting document {} as Markdown", document_uuid);
+    let account = account(&session)?;
+
+    requests::run_cancellable(&request_id, async {
+        let document = library::current_document(&app, &account, &project_uuid, &document_uuid).await?;
+        let project = library.projects(&account)?.into_iter().find(|p| p.uuid == project_uuid);
+        let markdown = export(&document, project.as_ref())?;
+        let destination = requests::download_destination(&app, &document.title, "md")?;
+
+        requests::write_file(&destination, markdown.into_bytes()).await.map_err(|e| {
+            log::error!("Failed to save Markdown: {}", e);
+            e
+        })?;
+
+        log::info!("Markdown saved to {:?}", destination);
+        Ok(destination.to_string_lossy().into_owned())
+    })
+    .await
+}
+
+/// Tauri command to create one document per Markdown file in `folder`. Files
+/// are imported in name order into the project, or into the project their
+/// front matter names.
+#[tauri::command]
+pub async fn import_markdown_folder(
+    app: AppHandle,
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    request_id: String,
+    project_uuid: String,
+    folder: String,
+) -> Result<RequestOutcome<MarkdownFolderImport>, String> {
+    capabilities::require(Feature::Uploads)?;
+    let account = account(&session)?;
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&folder)
+        .map_err(|e| format!("Failed to read {}: {}", folder, e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| MARKDOWN_EXTENSIONS.iter().any(|m| e.eq_ignore_ascii_case(m)))
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("No Markdown files in {}", folder));
+    }
+    log::info!("Importing {} Markdown files from {}", files.len(), folder);
+
+    requests::run_cancellable(&request_id, async {
+        let projects = library.projects(&account)?;
+        let mut documents = Vec::new();
+        let mut failed = Vec::new();
+        let mut warnings = Vec::new();
+        for path in files {
+            let file = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            match import_file(&app, &projects, &project_uuid, &path).await {
+                Ok((document, warning)) => {
+                    documents.push(document);
+                    if let Some(message) = warning {
+                        log::warn!("Imported {:?} with a warning: {}", path, message);
+                        warnings.push(ImportWarning { file, message });
+                    }
+                }
+                Err(error) => {
+                    log::warn!("Failed to import {:?}: {}", path, error);
+                    failed.push(FailedImport { file, error });
+                }
+            }
+        }
+
+        if !documents.is_empty() {
+            if let Some(cache) = HttpCache::for_current_account(&app) {
+                cache.invalidate(Some(&http_cache::resource_prefix(session::manager(&app).config(), "/documents")));
+            }
+        }
+        log::info!("Imported {} Markdown files, {} failed", documents.len(), failed.len());
+        Ok(MarkdownFolderImport { documents, failed, warnings })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_types::DocumentStatus;
+
+    fn round_trip(markdown: &str) -> String {
+        from_html(&to_html(markdown))
+    }
+
+    #[test]
+    fn markdown_survives_a_round_trip_through_html() {
+        for markdown in [
+            "# Title\n\nSome *em*, **strong** and ~~struck~~ text with `code`.\n",
+            "1. one\n2. two\n   - nested\n",
+            "> quoted\n\n```rust\nfn main() {}\n```\n",
+            "A [link](https://example.com \"Example\") and ![alt](image.png).\n",
+            "Literal \\*stars\\*, \\_underscores\\_ and a \\| pipe.\n",
+            "\\# not a heading\n",
+        ] {
+            assert_eq!(round_trip(markdown), markdown);
+        }
+    }
+
+    #[test]
+    fn task_lists_use_the_editor_markup() {
+        let html = to_html("- [x] done\n- [ ] todo\n");
+        assert!(html.starts_with("<ul data-type=\"taskList\">"));
+        assert!(html.contains("<li data-type=\"taskItem\" data-checked=\"true\">done</li>"));
+        assert!(html.contains("<li data-type=\"taskItem\" data-checked=\"false\">todo</li>"));
+        assert!(!html.contains("<input"));
+        assert_eq!(from_html(&html), "- [x] done\n- [ ] todo\n");
+
+        // Loose items carry the marker inside their paragraph
+        let loose = to_html("- [x] done\n\n- [ ] todo\n");
+        assert!(loose.contains("data-checked=\"true\""));
+        assert!(loose.contains("data-checked=\"false\""));
+
+        // Ordinary lists are left alone
+        assert_eq!(to_html("- plain\n"), "<ul>\n<li>plain</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn footnotes_are_written_after_the_body() {
+        let markdown = "Text[^note] and more[^2].\n\n[^note]: The note.\n[^2]: Another.\n";
+        let html = to_html(markdown);
+        assert!(html.contains("class=\"footnote-reference\""));
+        assert!(html.contains("class=\"footnote-definition\" id=\"note\""));
+        assert_eq!(from_html(&html), markdown);
+    }
+
+    #[test]
+    fn table_cells_escape_pipes_and_emphasis() {
+        let html = "<table><tr><th>a|b</th><th style=\"text-align: right\">*c*</th></tr><tr><td>1</td><td><strong>2</strong></td></tr></table>";
+        let markdown = from_html(html);
+        assert_eq!(markdown, "| a\\|b | \\*c\\* |\n| --- | ---: |\n| 1 | **2** |\n");
+
+        let back = to_html(&markdown);
+        assert!(back.contains("<th>a|b</th>"));
+        assert!(back.contains("<th style=\"text-align: right\">*c*</th>"));
+        assert!(back.contains("<strong>2</strong>"));
+    }
+
+    #[test]
+    fn front_matter_needs_a_closing_fence() {
+        let text = "---\ntitle: Draft\n\n# Heading\n";
+        assert_eq!(split_front_matter(text), (None, text));
+
+        let imported = import("notes.md", text).unwrap();
+        assert!(imported.front_matter.title.is_none());
+        assert!(imported.html.contains("<hr />"));
+        assert_eq!(imported.title, "Heading");
+
+        let closed = "\u{feff}---\r\ntitle: Draft\r\n...\r\nBody\n";
+        assert_eq!(split_front_matter(closed), (Some("title: Draft\r\n"), "Body\n"));
+    }
+
+    #[test]
+    fn front_matter_accepts_common_aliases() {
+        let imported = import(
+            "notes.md",
+            "---\ntitle: Plan\nuuid: 1234\ndate: 2024-05-01\nupdatedAt: 2024-06-01\nproject_uuid: p-1\ntags: [a, b]\n---\n\nBody\n",
+        )
+        .unwrap();
+        let front_matter = imported.front_matter;
+        assert_eq!(imported.title, "Plan");
+        assert_eq!(front_matter.document_uuid.as_deref(), Some("1234"));
+        assert_eq!(front_matter.created.as_deref(), Some("2024-05-01"));
+        assert_eq!(front_matter.updated.as_deref(), Some("2024-06-01"));
+        assert_eq!(front_matter.project_uuid.as_deref(), Some("p-1"));
+        assert!(front_matter.extra.contains_key("tags"));
+
+        assert!(import("notes.md", "---\n- a list\n---\nBody\n").is_err());
+        assert_eq!(import("notes.md", "Body\n").unwrap().title, "notes");
+    }
+
+    #[test]
+    fn exported_documents_import_with_their_metadata() {
+        let document = Document {
+            id: 1,
+            uuid: "doc-1".to_string(),
+            title: "Plan: Q3".to_string(),
+            content: "<h2>Goals</h2><p>Ship <em>it</em>.</p><ul data-type=\"taskList\"><li data-type=\"taskItem\" data-checked=\"true\"><p>Write</p></li></ul>".to_string(),
+            status: DocumentStatus::Ready,
+            created_at: "2024-05-01T10:00:00.000Z".to_string(),
+            updated_at: String::new(),
+        };
+        let project = Project {
+            id: 2,
+            uuid: "project-1".to_string(),
+            name: "Roadmap".to_string(),
+            description: None,
+            custom_instructions: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+
+        let markdown = export(&document, Some(&project)).unwrap();
+        assert!(markdown.starts_with("---\ntitle: 'Plan: Q3'\n"));
+        assert!(!markdown.contains("updated:"));
+
+        let imported = import("plan.md", &markdown).unwrap();
+        assert_eq!(imported.title, "Plan: Q3");
+        assert_eq!(imported.front_matter.project.as_deref(), Some("Roadmap"));
+        assert_eq!(imported.front_matter.project_uuid.as_deref(), Some("project-1"));
+        assert_eq!(imported.front_matter.document_uuid.as_deref(), Some("doc-1"));
+        assert_eq!(imported.front_matter.created.as_deref(), Some("2024-05-01T10:00:00.000Z"));
+        assert_eq!(from_html(&imported.html), from_html(&document.content));
+    }
+}
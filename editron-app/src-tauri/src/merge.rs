@@ -0,0 +1,488 @@
+use regex::Regex;
+use serde::Serialize;
+use std::ops::Range;
+
+lazy_static::lazy_static! {
+    static ref TAG: Regex = Regex::new(r"<(/?)([A-Za-z][A-Za-z0-9]*)\b[^>]*?(/?)>").unwrap();
+}
+
+/// Elements without a closing tag
+const VOID_ELEMENTS: &[&str] = &["area", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+/// Elements whose children are merged as separate blocks (list items, table rows)
+const CONTAINER_ELEMENTS: &[&str] = &["ul", "ol", "table", "thead", "tbody", "tfoot"];
+
+/// An element enclosing a block, kept so merged blocks can be wrapped again
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Wrapper {
+    name: String,
+    open: String,
+}
+
+/// A top-level paragraph, heading, list item or table row, with its enclosing lists or tables
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Block {
+    wrappers: Vec<Wrapper>,
+    html: String,
+}
+
+/// A region changed differently on both sides
+#[derive(Serialize, Clone, Debug)]
+pub struct MergeConflict {
+    pub id: usize,
+    pub base: String,
+    pub local: String,
+    pub remote: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MergeResult {
+    /// The merged document. Each conflict is rendered in place as
+    /// `<div data-merge-conflict="id">` holding a `data-merge-side="local"`
+    /// and a `data-merge-side="remote"` div.
+    pub html: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+    fn unchanged(html: &str) -> Self {
+        Self { html: html.to_string(), conflicts: Vec::new() }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+fn push_text(blocks: &mut Vec<Block>, wrappers: &[Wrapper], text: &str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        blocks.push(Block { wrappers: wrappers.to_vec(), html: text.to_string() });
+    }
+}
+
+/// Splits HTML into blocks. Children of lists and tables become blocks of
+/// their own; any other element is kept whole, including nested lists.
+fn split_blocks(html: &str, wrappers: &[Wrapper], blocks: &mut Vec<Block>) {
+    let mut depth = 0usize;
+    let mut text_start = 0;
+    // Name, start and end of the opening tag of the current top-level element
+    let mut current: Option<(String, usize, usize)> = None;
+
+    for cap in TAG.captures_iter(html) {
+        let tag = cap.get(0).expect("group 0 always matches");
+        let closing = &cap[1] == "/";
+        let name = cap[2].to_ascii_lowercase();
+        let self_closing = &cap[3] == "/" || VOID_ELEMENTS.contains(&name.as_str());
+
+        if depth == 0 {
+            push_text(blocks, wrappers, &html[text_start..tag.start()]);
+            text_start = tag.end();
+            if closing {
+                // Stray closing tag; drop it
+                continue;
+            }
+            if self_closing {
+                blocks.push(Block { wrappers: wrappers.to_vec(), html: tag.as_str().to_string() });
+                continue;
+            }
+            current = Some((name, tag.start(), tag.end()));
+            depth = 1;
+        } else if !self_closing {
+            if closing {
+                depth -= 1;
+            } else {
+                depth += 1;
+            }
+            if depth == 0 {
+                let (name, start, open_end) = current.take().expect("an element is open at depth > 0");
+                text_start = tag.end();
+                if CONTAINER_ELEMENTS.contains(&name.as_str()) {
+                    let mut inner = wrappers.to_vec();
+                    inner.push(Wrapper { name, open: html[start..open_end].to_string() });
+                    split_blocks(&html[open_end..tag.start()], &inner, blocks);
+                } else {
+                    blocks.push(Block { wrappers: wrappers.to_vec(), html: html[start..tag.end()].to_string() });
+                }
+            }
+        }
+    }
+
+    match current {
+        // Unclosed element: keep the rest as one block
+        Some((_, start, _)) => push_text(blocks, wrappers, &html[start..]),
+        None => push_text(blocks, wrappers, &html[text_start..]),
+    }
+}
+
+fn parse(html: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    split_blocks(html, &[], &mut blocks);
+    blocks
+}
+
+/// Serializes blocks, reopening lists and tables only where the wrappers change
+fn render(blocks: &[Block]) -> String {
+    let mut html = String::new();
+    let mut open: Vec<&Wrapper> = Vec::new();
+    for block in blocks {
+        let common = open
+            .iter()
+            .zip(&block.wrappers)
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        while open.len() > common {
+            let wrapper = open.pop().expect("length checked above");
+            html.push_str(&format!("</{}>", wrapper.name));
+        }
+        for wrapper in &block.wrappers[common..] {
+            html.push_str(&wrapper.open);
+            open.push(wrapper);
+        }
+        html.push_str(&block.html);
+    }
+    while let Some(wrapper) = open.pop() {
+        html.push_str(&format!("</{}>", wrapper.name));
+    }
+    html
+}
+
+/// Longest common subsequence of two block lists, as a map from `a` indices to `b` indices
+fn matching(a: &[Block], b: &[Block]) -> Vec<Option<usize>> {
+    let mut map = vec![None; a.len()];
+
+    // Common prefix and suffix need no table
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for (i, slot) in map.iter_mut().enumerate().take(prefix) {
+        *slot = Some(i);
+    }
+    for k in 0..suffix {
+        map[a.len() - 1 - k] = Some(b.len() - 1 - k);
+    }
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n == 0 || m == 0 {
+        return map;
+    }
+
+    // lengths[i][j] = LCS length of a_mid[i..] and b_mid[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            map[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    map
+}
+
+/// A change of one side against the base: `base` blocks replaced by `other` blocks
+struct Hunk {
+    base: Range<usize>,
+    other: Range<usize>,
+}
+
+/// The changed regions of one side, from its matching with the base
+fn hunks(map: &[Option<usize>], other_len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut o, mut x) = (0, 0);
+    for (i, j) in map.iter().enumerate() {
+        let Some(j) = *j else { continue };
+        if o < i || x < j {
+            hunks.push(Hunk { base: o..i, other: x..j });
+        }
+        (o, x) = (i + 1, j + 1);
+    }
+    if o < map.len() || x < other_len {
+        hunks.push(Hunk { base: o..map.len(), other: x..other_len });
+    }
+    hunks
+}
+
+/// Whether two changes touch the same base blocks. Insertions only overlap
+/// an insertion at the same place or a change around them.
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    (a.start < b.end && b.start < a.end) || (a.is_empty() && b.is_empty() && a.start == b.start)
+}
+
+/// `base[range]` with the given hunks of one side applied
+fn apply(base: &[Block], other: &[Block], hunks: &[&Hunk], range: Range<usize>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut pos = range.start;
+    for hunk in hunks {
+        blocks.extend_from_slice(&base[pos..hunk.base.start]);
+        blocks.extend_from_slice(&other[hunk.other.clone()]);
+        pos = hunk.base.end;
+    }
+    blocks.extend_from_slice(&base[pos..range.end]);
+    blocks
+}
+
+/// A merged region: blocks taken as they are, or a conflict
+enum Region {
+    Blocks(Vec<Block>),
+    Conflict { base: Vec<Block>, local: Vec<Block>, remote: Vec<Block> },
+}
+
+/// Resolves a region changed by either side. A side that did not change
+/// yields to the other; identical changes on both sides are taken once.
+fn resolve(base: &[Block], local: Vec<Block>, remote: Vec<Block>) -> Region {
+    if local == base {
+        Region::Blocks(remote)
+    } else if remote == base || local == remote {
+        Region::Blocks(local)
+    } else {
+        Region::Conflict { base: base.to_vec(), local, remote }
+    }
+}
+
+/// Three-way merge of document HTML at block level (paragraphs, headings,
+/// list items, table rows). Changes of both sides against the base are
+/// applied together; changes touching the same blocks become conflicts.
+pub fn merge(base: &str, local: &str, remote: &str) -> MergeResult {
+    if local == base || local == remote {
+        return MergeResult::unchanged(remote);
+    }
+    if remote == base {
+        return MergeResult::unchanged(local);
+    }
+    merge_blocks(&parse(base), &parse(local), &parse(remote))
+}
+
+/// The block-level merge behind `merge`, without its shortcuts for identical inputs
+fn merge_blocks(base: &[Block], local: &[Block], remote: &[Block]) -> MergeResult {
+    let local_hunks = hunks(&matching(base, local), local.len());
+    let remote_hunks = hunks(&matching(base, remote), remote.len());
+
+    // (is_local, hunk), ordered by base position
+    let mut changes: Vec<(bool, &Hunk)> = local_hunks
+        .iter()
+        .map(|h| (true, h))
+        .chain(remote_hunks.iter().map(|h| (false, h)))
+        .collect();
+    changes.sort_by_key(|(_, h)| (h.base.start, h.base.end));
+
+    let mut regions = Vec::new();
+    let mut pos = 0;
+    let mut k = 0;
+    while k < changes.len() {
+        // Group changes that overlap, directly or through another change
+        let mut range = changes[k].1.base.clone();
+        let first = k;
+        k += 1;
+        while k < changes.len() && overlaps(&changes[k].1.base, &range) {
+            range.end = range.end.max(changes[k].1.base.end);
+            k += 1;
+        }
+        let group = &changes[first..k];
+        let side = |local_side: bool| group.iter().filter(|(l, _)| *l == local_side).map(|(_, h)| *h).collect::<Vec<_>>();
+
+        regions.push(Region::Blocks(base[pos..range.start].to_vec()));
+        regions.push(resolve(
+            &base[range.clone()],
+            apply(base, local, &side(true), range.clone()),
+            apply(base, remote, &side(false), range.clone()),
+        ));
+        pos = range.end;
+    }
+    regions.push(Region::Blocks(base[pos..].to_vec()));
+
+    let mut html = String::new();
+    let mut pending: Vec<Block> = Vec::new();
+    let mut conflicts = Vec::new();
+    for region in regions {
+        match region {
+            Region::Blocks(blocks) => pending.extend(blocks),
+            Region::Conflict { base, local, remote } => {
+                html.push_str(&render(&pending));
+                pending.clear();
+
+                let conflict = MergeConflict {
+                    id: conflicts.len(),
+                    base: render(&base),
+                    local: render(&local),
+                    remote: render(&remote),
+                };
+                html.push_str(&format!(
+                    "<div data-merge-conflict=\"{}\"><div data-merge-side=\"local\">{}</div><div data-merge-side=\"remote\">{}</div></div>",
+                    conflict.id, conflict.local, conflict.remote
+                ));
+                conflicts.push(conflict);
+            }
+        }
+    }
+    html.push_str(&render(&pending));
+
+    MergeResult { html, conflicts }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// A block of a two-way diff, rendered with its enclosing list or table
+#[derive(Serialize, Clone, Debug)]
+pub struct DiffBlock {
+    pub kind: DiffKind,
+    pub html: String,
+}
+
+/// Block-level diff from `old` to `new`
+pub fn diff(old: &str, new: &str) -> Vec<DiffBlock> {
+    let (old, new) = (parse(old), parse(new));
+    let block = |kind, block: &Block| DiffBlock { kind, html: render(std::slice::from_ref(block)) };
+
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+    for hunk in hunks(&matching(&old, &new), new.len()) {
+        blocks.extend(old[pos..hunk.base.start].iter().map(|b| block(DiffKind::Unchanged, b)));
+        blocks.extend(old[hunk.base.clone()].iter().map(|b| block(DiffKind::Removed, b)));
+        blocks.extend(new[hunk.other.clone()].iter().map(|b| block(DiffKind::Added, b)));
+        pos = hunk.base.end;
+    }
+    blocks.extend(old[pos..].iter().map(|b| block(DiffKind::Unchanged, b)));
+    blocks
+}
+
+/// Tauri command to merge two versions of a document that both changed since `base`
+#[tauri::command]
+pub async fn merge_document_versions(base: String, local: String, remote: String) -> Result<MergeResult, String> {
+    let result = merge(&base, &local, &remote);
+    log::info!("Merged document versions with {} conflicts", result.conflicts.len());
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn wrapper(name: &str) -> Wrapper {
+        Wrapper { name: name.to_string(), open: format!("<{}>", name) }
+    }
+
+    /// Paragraphs and list items from a small alphabet, so that repeated
+    /// blocks give the matching more than one choice
+    fn block() -> impl Strategy<Value = Block> {
+        (0..3u8, 0..4u8).prop_map(|(kind, n)| match kind {
+            0 => Block { wrappers: Vec::new(), html: format!("<p>{}</p>", n) },
+            1 => Block { wrappers: vec![wrapper("ul")], html: format!("<li>{}</li>", n) },
+            _ => Block { wrappers: vec![wrapper("ol")], html: format!("<li>{}</li>", n) },
+        })
+    }
+
+    fn blocks() -> impl Strategy<Value = Vec<Block>> {
+        prop::collection::vec(block(), 0..12)
+    }
+
+    fn paragraph(text: String) -> Block {
+        Block { wrappers: Vec::new(), html: format!("<p>{}</p>", text) }
+    }
+
+    /// `blocks` with those not in `keep` removed and new ones, labelled `side`, inserted
+    fn edit(blocks: &[Block], keep: &[bool], inserts: &[usize], side: &str) -> Vec<Block> {
+        let mut edited: Vec<Block> = blocks.iter().zip(keep).filter(|(_, keep)| **keep).map(|(b, _)| b.clone()).collect();
+        for (n, at) in inserts.iter().enumerate() {
+            let at = at % (edited.len() + 1);
+            edited.insert(at, paragraph(format!("{}{}", side, n)));
+        }
+        edited
+    }
+
+    proptest! {
+        #[test]
+        fn same_change_on_both_sides_is_taken_once(base in blocks(), changed in blocks()) {
+            let result = merge_blocks(&base, &changed, &changed);
+            prop_assert!(result.is_clean());
+            prop_assert_eq!(result.html, render(&changed));
+        }
+
+        #[test]
+        fn unchanged_local_takes_remote(base in blocks(), remote in blocks()) {
+            let result = merge_blocks(&base, &base, &remote);
+            prop_assert!(result.is_clean());
+            prop_assert_eq!(result.html, render(&remote));
+        }
+
+        #[test]
+        fn unchanged_remote_takes_local(base in blocks(), local in blocks()) {
+            let result = merge_blocks(&base, &local, &base);
+            prop_assert!(result.is_clean());
+            prop_assert_eq!(result.html, render(&local));
+        }
+
+        #[test]
+        fn edits_to_separate_blocks_are_both_kept(
+            len in 1..10usize,
+            split in any::<prop::sample::Index>(),
+            keep_local in prop::collection::vec(any::<bool>(), 10),
+            keep_remote in prop::collection::vec(any::<bool>(), 10),
+            insert_local in prop::collection::vec(any::<usize>(), 0..4),
+            insert_remote in prop::collection::vec(any::<usize>(), 0..4),
+        ) {
+            // Unique blocks, with one left alone between the part each side edits
+            let base: Vec<Block> = (0..len).map(|n| paragraph(format!("base{}", n))).collect();
+            let split = split.index(len);
+            let (before, rest) = base.split_at(split);
+            let (separator, after) = rest.split_first().expect("split is within base");
+
+            let local_before = edit(before, &keep_local, &insert_local, "local");
+            let remote_after = edit(after, &keep_remote, &insert_remote, "remote");
+            let local = [local_before.as_slice(), std::slice::from_ref(separator), after].concat();
+            let remote = [before, std::slice::from_ref(separator), remote_after.as_slice()].concat();
+            let expected = [local_before.as_slice(), std::slice::from_ref(separator), remote_after.as_slice()].concat();
+
+            let result = merge_blocks(&base, &local, &remote);
+            prop_assert!(result.is_clean());
+            prop_assert_eq!(result.html, render(&expected));
+        }
+    }
+
+    #[test]
+    fn list_items_merge_separately() {
+        let base = "<h1>Notes</h1><ul><li>one</li><li>two</li></ul>";
+        let local = "<h1>Meeting notes</h1><ul><li>one</li><li>two</li></ul>";
+        let remote = "<h1>Notes</h1><ul><li>one</li><li>two</li><li>three</li></ul>";
+
+        let result = merge(base, local, remote);
+        assert!(result.is_clean());
+        assert_eq!(result.html, "<h1>Meeting notes</h1><ul><li>one</li><li>two</li><li>three</li></ul>");
+    }
+
+    #[test]
+    fn changes_to_the_same_block_conflict() {
+        let result = merge("<p>a</p><p>b</p>", "<p>a</p><p>local</p>", "<p>a</p><p>remote</p>");
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].base, "<p>b</p>");
+        assert_eq!(result.conflicts[0].local, "<p>local</p>");
+        assert_eq!(result.conflicts[0].remote, "<p>remote</p>");
+        assert!(result.html.starts_with("<p>a</p><div data-merge-conflict=\"0\">"));
+    }
+}
@@ -0,0 +1,613 @@
+use crate::api_client::{self, ApiClient, ApiError};
+use crate::api_types::{Document, UpdateDocumentRequest};
+use crate::connectivity;
+use crate::http_cache;
+use crate::git_mirror;
+use crate::history::{self, VersionSource};
+use crate::journal::Journal;
+use crate::library::Library;
+use crate::merge;
+use crate::session::{self, SessionManager};
+use crate::shutdown::Shutdown;
+use crate::timestamp;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+/// How often the queue is retried while entries are waiting
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Pushed versions are followed at most this many steps when rebasing a new change
+const MAX_VERSION_HOPS: usize = 32;
+/// Replays rejected this many times park the entry as failed until the user retries it
+const MAX_ATTEMPTS: u32 = 5;
+
+/// A change to a document waiting to be sent to the gateway
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DocumentChange {
+    Edit { content: String },
+    Rename { title: String },
+    Delete,
+}
+
+impl DocumentChange {
+    fn kind(&self) -> &'static str {
+        match self {
+            DocumentChange::Edit { .. } => "edit",
+            DocumentChange::Rename { .. } => "rename",
+            DocumentChange::Delete => "delete",
+        }
+    }
+
+    fn payload(&self) -> Option<&str> {
+        match self {
+            DocumentChange::Edit { content } => Some(content),
+            DocumentChange::Rename { title } => Some(title),
+            DocumentChange::Delete => None,
+        }
+    }
+
+    fn from_columns(kind: &str, payload: Option<String>) -> Option<Self> {
+        match (kind, payload) {
+            ("edit", Some(content)) => Some(DocumentChange::Edit { content }),
+            ("rename", Some(title)) => Some(DocumentChange::Rename { title }),
+            ("delete", _) => Some(DocumentChange::Delete),
+            _ => None,
+        }
+    }
+
+    /// Whether the server copy already contains this change
+    fn is_applied_to(&self, document: &Document) -> bool {
+        match self {
+            DocumentChange::Edit { content } => &document.content == content,
+            DocumentChange::Rename { title } => &document.title == title,
+            DocumentChange::Delete => false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryState {
+    /// Waiting to be replayed
+    Pending,
+    /// The server copy changed since the base version; waits for the user
+    Conflict,
+    /// The gateway kept rejecting the change; waits for the user
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OutboxEntry {
+    pub id: i64,
+    #[serde(rename = "documentUuid")]
+    pub document_uuid: String,
+    #[serde(rename = "projectUuid")]
+    pub project_uuid: String,
+    pub change: DocumentChange,
+    /// `updatedAt` of the server copy the change was made against
+    #[serde(rename = "baseVersion")]
+    pub base_version: String,
+    /// Content of the document at the base version, when it was known locally
+    #[serde(rename = "baseContent")]
+    pub base_content: Option<String>,
+    pub state: EntryState,
+    pub attempts: u32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: u64,
+}
+
+/// Payload of the `sync_conflict` event
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncConflict {
+    pub entry: OutboxEntry,
+    /// The server copy, or `None` when the document was deleted on the server
+    pub server: Option<Document>,
+}
+
+/// How the user resolved a conflict
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ConflictResolution {
+    /// Drop the local change and keep the server copy
+    Discard,
+    /// Replay the local change on top of the server copy, optionally with merged content
+    Overwrite { content: Option<String> },
+    /// Queue a failed change again with a fresh attempt count
+    Retry,
+}
+
+/// Durable queue of document edits, renames and deletes. Entries live in the
+/// account's library database and are replayed in order whenever the backend
+/// is reachable; the managed state only coordinates the replay task.
+#[derive(Default)]
+pub struct Outbox {
+    wake: Notify,
+    flushing: tokio::sync::Mutex<()>,
+}
+
+const ENTRY_COLUMNS: &str =
+    "id, document_uuid, project_uuid, kind, payload, base_version, base_content, state, attempts, last_error, created_at";
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<OutboxEntry> {
+    let kind: String = row.get(3)?;
+    let change = DocumentChange::from_columns(&kind, row.get(4)?).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, format!("invalid change {}", kind).into())
+    })?;
+    let state: String = row.get(7)?;
+    Ok(OutboxEntry {
+        id: row.get(0)?,
+        document_uuid: row.get(1)?,
+        project_uuid: row.get(2)?,
+        change,
+        base_version: row.get(5)?,
+        base_content: row.get(6)?,
+        state: match state.as_str() {
+            "conflict" => EntryState::Conflict,
+            "failed" => EntryState::Failed,
+            _ => EntryState::Pending,
+        },
+        attempts: row.get(8)?,
+        last_error: row.get(9)?,
+        created_at: row.get::<_, i64>(10)? as u64,
+    })
+}
+
+fn entries(conn: &Connection, filter: &str, args: impl rusqlite::Params) -> rusqlite::Result<Vec<OutboxEntry>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM outbox {} ORDER BY id", ENTRY_COLUMNS, filter))?;
+    let entries = stmt.query_map(args, entry_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+fn entry(conn: &Connection, id: i64) -> rusqlite::Result<Option<OutboxEntry>> {
+    conn.query_row(
+        &format!("SELECT {} FROM outbox WHERE id = ?1", ENTRY_COLUMNS),
+        params![id],
+        entry_from_row,
+    )
+    .optional()
+}
+
+/// Follows versions this client pushed itself, so a change made against a copy
+/// we already updated is not reported as a conflict with our own save
+fn latest_pushed_version(conn: &Connection, document_uuid: &str, base_version: &str) -> rusqlite::Result<String> {
+    let mut version = base_version.to_string();
+    for _ in 0..MAX_VERSION_HOPS {
+        let next: Option<String> = conn
+            .query_row(
+                "SELECT to_version FROM pushed_versions WHERE document_uuid = ?1 AND from_version = ?2",
+                params![document_uuid, version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match next {
+            Some(next) if next != version => version = next,
+            _ => break,
+        }
+    }
+    Ok(version)
+}
+
+/// Adds a change to the queue. Consecutive edits or renames of a document against
+/// the same base are coalesced into one entry, since each carries the full value.
+fn enqueue(conn: &mut Connection, project_uuid: &str, document_uuid: &str, change: &DocumentChange, base_version: &str) -> rusqlite::Result<OutboxEntry> {
+    let tx = conn.transaction()?;
+    let base_version = latest_pushed_version(&tx, document_uuid, base_version)?;
+
+    let last = entries(&tx, "WHERE document_uuid = ?1 AND id = (SELECT MAX(id) FROM outbox WHERE document_uuid = ?1)", params![document_uuid])?
+        .pop();
+    let coalesce = last.as_ref().filter(|last| {
+        last.state == EntryState::Pending
+            && last.attempts == 0
+            && last.base_version == base_version
+            && last.change.kind() == change.kind()
+            && *change != DocumentChange::Delete
+    });
+
+    let id = match coalesce {
+        Some(last) => {
+            tx.execute("UPDATE outbox SET payload = ?1 WHERE id = ?2", params![change.payload(), last.id])?;
+            last.id
+        }
+        None => {
+            let base_content: Option<String> = match &last {
+                // A change queued behind another one is based on that change
+                Some(last) if last.base_version == base_version => last.base_content.clone(),
+                _ => tx
+                    .query_row(
+                        "SELECT content FROM documents WHERE uuid = ?1 AND updated_at = ?2",
+                        params![document_uuid, base_version],
+                        |row| row.get(0),
+                    )
+                    .optional()?,
+            };
+            tx.execute(
+                "INSERT INTO outbox (document_uuid, project_uuid, kind, payload, base_version, base_content, state, attempts, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'pending', 0, ?7)",
+                params![
+                    document_uuid,
+                    project_uuid,
+                    change.kind(),
+                    change.payload(),
+                    base_version,
+                    base_content,
+                    timestamp::now_millis() as i64,
+                ],
+            )?;
+            tx.last_insert_rowid()
+        }
+    };
+    tx.commit()?;
+    entry(conn, id).map(|e| e.expect("entry was written above"))
+}
+
+/// Applies queued changes on top of locally stored documents, so offline reads
+/// show what the user wrote. Documents with a queued delete are dropped.
+pub fn apply_pending(library: &Library, account: &str, documents: Vec<Document>) -> Result<Vec<Document>, String> {
+    library.with_db(account, |conn| {
+        let mut result = Vec::with_capacity(documents.len());
+        for mut document in documents {
+            let changes = entries(conn, "WHERE document_uuid = ?1", params![document.uuid])?;
+            let mut deleted = false;
+            for entry in changes {
+                match entry.change {
+                    DocumentChange::Edit { content } => document.content = content,
+                    DocumentChange::Rename { title } => document.title = title,
+                    DocumentChange::Delete => deleted = true,
+                }
+            }
+            if !deleted {
+                result.push(document);
+            }
+        }
+        Ok(result)
+    })
+}
+
+/// Outcome of replaying one entry
+enum Replay {
+    /// The server accepted the change; holds the new server copy unless it was a delete
+    Applied(Option<Document>),
+    /// The change was merged with newer server changes; holds the merged server copy
+    Merged(Document),
+    /// The server copy changed since the base version
+    Conflict(Option<Document>),
+}
+
+async fn replay(client: &ApiClient, entry: &OutboxEntry) -> Result<Replay, ApiError> {
+    let server = match client.get_document(&entry.document_uuid, &entry.project_uuid).await {
+        Ok(document) => Some(document),
+        Err(ApiError::Status { status: 404, .. }) => None,
+        Err(e) => return Err(e),
+    };
+
+    let server = match (server, &entry.change) {
+        (None, DocumentChange::Delete) => return Ok(Replay::Applied(None)),
+        (None, _) => return Ok(Replay::Conflict(None)),
+        (Some(server), change) if server.updated_at != entry.base_version => {
+            if change.is_applied_to(&server) {
+                return Ok(Replay::Applied(Some(server)));
+            }
+            // Edits merge cleanly when they do not touch the blocks changed on the server
+            if let (DocumentChange::Edit { content }, Some(base)) = (change, &entry.base_content) {
+                let merged = merge::merge(base, content, &server.content);
+                if merged.is_clean() {
+                    let request = UpdateDocumentRequest { content: Some(merged.html), title: None };
+                    let updated = client.update_document(&server.uuid, &entry.project_uuid, &request).await?;
+                    return Ok(Replay::Merged(updated));
+                }
+            }
+            return Ok(Replay::Conflict(Some(server)));
+        }
+        (Some(server), _) => server,
+    };
+
+    let request = match &entry.change {
+        DocumentChange::Edit { content } => UpdateDocumentRequest { content: Some(content.clone()), title: None },
+        DocumentChange::Rename { title } => UpdateDocumentRequest { content: None, title: Some(title.clone()) },
+        DocumentChange::Delete => {
+            client.delete_document(&server.uuid, &entry.project_uuid).await?;
+            return Ok(Replay::Applied(None));
+        }
+    };
+    let updated = client.update_document(&server.uuid, &entry.project_uuid, &request).await?;
+    Ok(Replay::Applied(Some(updated)))
+}
+
+/// Records a replayed entry: the entry is removed, the library updated and later
+/// entries of the same document rebased onto the new server version. After a
+/// merge the editor no longer holds the server copy, so later entries keep
+/// their base and are merged in turn.
+fn complete(conn: &mut Connection, entry: &OutboxEntry, server: &Option<Document>, merged: bool) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM outbox WHERE id = ?1", params![entry.id])?;
+    match server {
+        Some(document) => {
+            tx.execute(
+                "UPDATE documents SET title = ?1, content = ?2, updated_at = ?3, synced_at = ?4 WHERE uuid = ?5",
+                params![document.title, document.content, document.updated_at, timestamp::now_millis() as i64, document.uuid],
+            )?;
+            if merged {
+                return tx.commit();
+            }
+            tx.execute(
+                "UPDATE outbox SET base_version = ?1, base_content = ?2 WHERE document_uuid = ?3 AND base_version = ?4",
+                params![document.updated_at, document.content, entry.document_uuid, entry.base_version],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO pushed_versions (document_uuid, from_version, to_version) VALUES (?1, ?2, ?3)",
+                params![entry.document_uuid, entry.base_version, document.updated_at],
+            )?;
+        }
+        None => {
+            tx.execute("DELETE FROM documents WHERE uuid = ?1", params![entry.document_uuid])?;
+            tx.execute("DELETE FROM pushed_versions WHERE document_uuid = ?1", params![entry.document_uuid])?;
+        }
+    }
+    tx.commit()
+}
+
+fn mark_conflict(conn: &Connection, entry: &OutboxEntry, server: &Option<Document>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE outbox SET state = 'conflict', server_version = ?1 WHERE id = ?2",
+        params![server.as_ref().map(|d| d.updated_at.clone()), entry.id],
+    )?;
+    Ok(())
+}
+
+/// Counts a rejected replay and parks the entry as failed once it reaches
+/// `MAX_ATTEMPTS`. Returns the entry as stored.
+fn record_failure(conn: &Connection, entry: &OutboxEntry, error: &str) -> rusqlite::Result<OutboxEntry> {
+    let mut failed = entry.clone();
+    failed.attempts += 1;
+    failed.last_error = Some(error.to_string());
+    if failed.attempts >= MAX_ATTEMPTS {
+        failed.state = EntryState::Failed;
+    }
+    conn.execute(
+        "UPDATE outbox SET attempts = ?1, last_error = ?2, state = ?3 WHERE id = ?4",
+        params![failed.attempts, error, if failed.state == EntryState::Failed { "failed" } else { "pending" }, entry.id],
+    )?;
+    Ok(failed)
+}
+
+/// Drops the cached document and project responses once a write has landed, so
+/// reads never serve the copy from before it even if a later entry fails
+fn invalidate_documents(app: &AppHandle, session: &SessionManager, project_uuid: &str) {
+    http_cache::invalidate_document_writes(app, session.config(), Some(project_uuid));
+}
+
+fn account(session: &SessionManager) -> Result<String, String> {
+    session.current_account_key().ok_or_else(|| "Not signed in".to_string())
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the replay task to run now
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Replays pending entries in order until the queue is empty or the backend
+    /// becomes unreachable. A conflicted or failing entry holds back the later
+    /// entries of its document only; an entry rejected `MAX_ATTEMPTS` times is
+    /// reported with `sync_failed` and no longer retried on its own. Returns the
+    /// number of entries still queued.
+    pub async fn flush(&self, app: &AppHandle) -> Result<usize, String> {
+        let _flushing = self.flushing.lock().await;
+        let session = session::manager(app);
+        let Some(account) = session.current_account_key() else { return Ok(0) };
+        let library = app.state::<Library>();
+        let journal = app.state::<Journal>();
+
+        let queued = library.with_db(&account, |conn| entries(conn, "", []))?;
+        if queued.is_empty() {
+            return Ok(0);
+        }
+        connectivity::ensure_online().await?;
+        log::info!("Replaying {} queued document changes", queued.len());
+
+        let mut held: HashSet<String> = queued
+            .iter()
+            .filter(|e| e.state != EntryState::Pending)
+            .map(|e| e.document_uuid.clone())
+            .collect();
+        let mut remaining = 0;
+
+        for entry in queued.iter().filter(|e| e.state == EntryState::Pending) {
+            if held.contains(&entry.document_uuid) {
+                remaining += 1;
+                continue;
+            }
+
+            let result = api_client::with_session_typed(app, |client| async move { replay(&client, entry).await }).await;
+            match result {
+                Ok(Replay::Applied(server)) => {
+                    log::info!("Synced queued {} of document {}", entry.change.kind(), entry.document_uuid);
+                    invalidate_documents(app, &session, &entry.project_uuid);
+                    library.with_db(&account, |conn| complete(conn, entry, &server, false))?;
+                    match &server {
+                        Some(document) => {
+                            journal.confirm(&account, document);
+                            history::record(app, &account, document, VersionSource::Save);
+                        }
+                        None => git_mirror::remove_document(app, &account, &entry.document_uuid),
+                    }
+                }
+                Ok(Replay::Merged(server)) => {
+                    log::info!("Merged queued edit of document {} with server changes", entry.document_uuid);
+                    let server = Some(server);
+                    invalidate_documents(app, &session, &entry.project_uuid);
+                    library.with_db(&account, |conn| complete(conn, entry, &server, true))?;
+                    // The merged copy holds the journaled edits too
+                    journal.remove(&account, &entry.document_uuid)?;
+                    if let Some(document) = &server {
+                        history::record(app, &account, document, VersionSource::Save);
+                    }
+                    if let Err(e) = app.emit("document_merged", server) {
+                        log::error!("Failed to emit document_merged event: {}", e);
+                    }
+                }
+                Ok(Replay::Conflict(server)) => {
+                    log::warn!("Queued {} of document {} conflicts with the server copy", entry.change.kind(), entry.document_uuid);
+                    library.with_db(&account, |conn| mark_conflict(conn, entry, &server))?;
+                    held.insert(entry.document_uuid.clone());
+                    remaining += 1;
+
+                    let mut conflicted = entry.clone();
+                    conflicted.state = EntryState::Conflict;
+                    if let Err(e) = app.emit("sync_conflict", SyncConflict { entry: conflicted, server }) {
+                        log::error!("Failed to emit sync_conflict event: {}", e);
+                    }
+                }
+                // Nothing reached the gateway, or it needs a new sign-in; the entry is not at fault
+                Err(e @ (ApiError::Network(_) | ApiError::Unavailable(_) | ApiError::Unauthorized)) => {
+                    return Err(e.to_string());
+                }
+                Err(e) => {
+                    log::warn!("Queued change {} of document {} failed: {}", entry.id, entry.document_uuid, e);
+                    let failed = library.with_db(&account, |conn| record_failure(conn, entry, &e.to_string()))?;
+                    held.insert(entry.document_uuid.clone());
+                    remaining += 1;
+
+                    if failed.state == EntryState::Failed {
+                        log::error!("Giving up on queued change {} of document {} after {} attempts", entry.id, entry.document_uuid, failed.attempts);
+                        if let Err(e) = app.emit("sync_failed", failed) {
+                            log::error!("Failed to emit sync_failed event: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(remaining)
+    }
+}
+
+/// Replays the queue in the background whenever it is woken, the backend comes
+/// back online, or the retry interval passes
+pub fn start_sync(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let outbox = app.state::<Outbox>();
+        loop {
+            if connectivity::wait_until_online(RETRY_INTERVAL).await {
+                match outbox.flush(&app).await {
+                    Ok(0) => {}
+                    Ok(remaining) => log::info!("{} document changes are still queued", remaining),
+                    Err(e) => log::warn!("Document sync stopped: {}", e),
+                }
+            }
+            tokio::select! {
+                _ = outbox.wake.notified() => {}
+                _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// Stores a change in the queue of `account` and wakes the replay task
+pub fn queue(
+    library: &Library,
+    outbox: &Outbox,
+    account: &str,
+    project_uuid: &str,
+    document_uuid: &str,
+    change: &DocumentChange,
+    base_version: &str,
+) -> Result<OutboxEntry, String> {
+    let entry = library.with_db(account, |conn| enqueue(conn, project_uuid, document_uuid, change, base_version))?;
+    log::info!("Queued {} of document {} (entry {})", change.kind(), document_uuid, entry.id);
+    outbox.wake();
+    Ok(entry)
+}
+
+/// Tauri command to queue an edit, rename or delete of a document. The change is
+/// stored durably first and replayed against `baseVersion` (the `updatedAt` the
+/// editor loaded) as soon as the backend is reachable.
+#[tauri::command]
+pub async fn queue_document_change(
+    session: State<'_, SessionManager>,
+    shutdown: State<'_, Shutdown>,
+    library: State<'_, Library>,
+    outbox: State<'_, Outbox>,
+    project_uuid: String,
+    document_uuid: String,
+    change: DocumentChange,
+    base_version: String,
+) -> Result<OutboxEntry, String> {
+    let _work = shutdown.track();
+    queue(&library, &outbox, &account(&session)?, &project_uuid, &document_uuid, &change, &base_version)
+}
+
+/// Tauri command to list the queued changes of the signed-in account
+#[tauri::command]
+pub async fn get_outbox(session: State<'_, SessionManager>, library: State<'_, Library>) -> Result<Vec<OutboxEntry>, String> {
+    library.with_db(&account(&session)?, |conn| entries(conn, "", []))
+}
+
+/// Tauri command to resolve a conflicted entry reported by `sync_conflict` or a
+/// failed one reported by `sync_failed`
+#[tauri::command]
+pub async fn resolve_sync_conflict(
+    session: State<'_, SessionManager>,
+    library: State<'_, Library>,
+    outbox: State<'_, Outbox>,
+    entry_id: i64,
+    resolution: ConflictResolution,
+) -> Result<(), String> {
+    let account = account(&session)?;
+    library.with_db(&account, |conn| {
+        let held: Option<(String, Option<String>)> = conn
+            .query_row(
+                "SELECT state, server_version FROM outbox WHERE id = ?1 AND state IN ('conflict', 'failed')",
+                params![entry_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((state, server_version)) = held else { return Ok(Err(format!("No conflict with id {}", entry_id))) };
+
+        match (resolution, server_version) {
+            (ConflictResolution::Discard, _) => {
+                conn.execute("DELETE FROM outbox WHERE id = ?1", params![entry_id])?;
+            }
+            (ConflictResolution::Retry, _) => {
+                conn.execute(
+                    "UPDATE outbox SET state = 'pending', attempts = 0, last_error = NULL WHERE id = ?1",
+                    params![entry_id],
+                )?;
+            }
+            (ConflictResolution::Overwrite { .. }, _) if state == "failed" => {
+                return Ok(Err(format!("Change {} did not conflict; retry or discard it", entry_id)));
+            }
+            (ConflictResolution::Overwrite { .. }, None) => {
+                // The server copy is gone: a delete is done, anything else cannot be applied
+                let kind: String = conn.query_row("SELECT kind FROM outbox WHERE id = ?1", params![entry_id], |row| row.get(0))?;
+                if kind != "delete" {
+                    return Ok(Err("The document was deleted on the server".to_string()));
+                }
+                conn.execute("DELETE FROM outbox WHERE id = ?1", params![entry_id])?;
+            }
+            (ConflictResolution::Overwrite { content }, Some(server_version)) => {
+                conn.execute(
+                    "UPDATE outbox SET state = 'pending', base_version = ?1, base_content = NULL, server_version = NULL,
+                        attempts = 0, last_error = NULL, payload = CASE WHEN kind = 'edit' THEN COALESCE(?2, payload) ELSE payload END
+                     WHERE id = ?3",
+                    params![server_version, content, entry_id],
+                )?;
+            }
+        }
+        Ok(Ok(()))
+    })??;
+
+    log::info!("Resolved sync conflict {}", entry_id);
+    outbox.wake();
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use crate::locks::lock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+lazy_static::lazy_static! {
+    static ref IN_FLIGHT: Mutex<HashMap<String, watch::Sender<bool>>> = Mutex::new(HashMap::new());
+}
+
+/// Result of a cancellable command, serialized as `{ "status": "completed", "data": ... }`
+/// or `{ "status": "cancelled" }` so the webview handles every command the same way.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum RequestOutcome<T> {
+    Completed { data: T },
+    Cancelled,
+}
+
+/// Keeps a request registered while it is in flight and unregisters it on drop
+pub struct RequestGuard {
+    id: String,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        lock(&IN_FLIGHT).remove(&self.id);
+    }
+}
+
+/// Resolves once the request has been cancelled
+pub struct CancelSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl CancelSignal {
+    pub async fn cancelled(&mut self) {
+        // An Err means the sender was dropped, which only happens on unregister
+        let _ = self.rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Registers a client-provided request id so it can be cancelled with `cancel_request`
+pub fn register(request_id: &str) -> Result<(RequestGuard, CancelSignal), String> {
+    let mut in_flight = lock(&IN_FLIGHT);
+    if in_flight.contains_key(request_id) {
+        return Err(format!("Request {} is already running", request_id));
+    }
+    let (tx, rx) = watch::channel(false);
+    in_flight.insert(request_id.to_string(), tx);
+    Ok((RequestGuard { id: request_id.to_string() }, CancelSignal { rx }))
+}
+
+/// Cancels an in-flight request. Returns false if no such request is running.
+pub fn cancel(request_id: &str) -> bool {
+    match lock(&IN_FLIGHT).get(request_id) {
+        Some(tx) => {
+            let _ = tx.send(true);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Runs `fut` under `request_id`. On cancellation the future is dropped, which
+/// aborts any reqwest call it was awaiting and runs its cleanup guards.
+pub async fn run_cancellable<T, F>(request_id: &str, fut: F) -> Result<RequestOutcome<T>, String>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    let (_guard, mut signal) = register(request_id)?;
+    tokio::select! {
+        result = fut => result.map(|data| RequestOutcome::Completed { data }),
+        _ = signal.cancelled() => {
+            log::info!("Request {} cancelled", request_id);
+            Ok(RequestOutcome::Cancelled)
+        }
+    }
+}
+
+/// Longest file name stem kept when naming a saved file after a document
+const MAX_STEM_CHARS: usize = 120;
+
+/// Turns a document title into a file name stem that cannot leave the target
+/// directory: separators, reserved and control characters become `_`, and
+/// leading or trailing dots and spaces are dropped
+pub fn sanitize_file_stem(title: &str, fallback: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .take(MAX_STEM_CHARS)
+        .collect();
+    let trimmed = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if trimmed.is_empty() {
+        fallback.to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Picks `<stem>.<extension>` in `dir`, or `<stem> (2).<extension>` and so on
+/// when that name is taken, so saving never replaces an existing file
+pub fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).{}", stem, n, extension));
+        n += 1;
+    }
+    path
+}
+
+/// Where a file saved by the app goes: the user's Downloads folder, named after
+/// `title`. The webview never chooses the path, so injected script cannot use
+/// a save to overwrite arbitrary files.
+pub fn download_destination(app: &AppHandle, title: &str, extension: &str) -> Result<PathBuf, String> {
+    let dir = app.path().download_dir().map_err(|e| format!("Failed to resolve the Downloads folder: {}", e))?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    Ok(unique_path(&dir, &sanitize_file_stem(title, "document"), extension))
+}
+
+/// A temporary file that is removed on drop unless it was persisted.
+/// Used so cancelled or failed downloads never leave partial files behind.
+pub struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Creates a temporary path next to `destination` so the final rename is atomic
+    pub fn beside(destination: &Path) -> Self {
+        let mut name = destination
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_default();
+        name.push(".part");
+        Self {
+            path: destination.with_file_name(name),
+            persisted: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the temporary file to `destination`
+    pub fn persist(mut self, destination: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, destination)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted && self.path.exists() {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                log::warn!("Failed to remove temporary file {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+/// Writes `bytes` to `destination` through a `TempFile`. The write and rename run
+/// on the blocking pool, which owns the temporary file, so a cancelled caller
+/// cannot drop it halfway through a write and leave a `.part` file behind.
+pub async fn write_file(destination: &Path, bytes: Vec<u8>) -> Result<(), String> {
+    let destination = destination.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let temp = TempFile::beside(&destination);
+        std::fs::write(temp.path(), &bytes)?;
+        temp.persist(&destination)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// Tauri command to cancel any in-flight request started with a request id
+#[tauri::command]
+pub async fn cancel_request(request_id: String) -> Result<bool, String> {
+    log::info!("Cancelling request {}", request_id);
+    Ok(cancel(&request_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_stems_cannot_escape_the_directory() {
+        assert_eq!(sanitize_file_stem("Q3 report", "document"), "Q3 report");
+        assert_eq!(sanitize_file_stem("../../.bashrc", "document"), "_.._.bashrc");
+        assert_eq!(sanitize_file_stem("C:\\Windows\\win.ini", "document"), "C__Windows_win.ini");
+        assert_eq!(sanitize_file_stem("a/b:c*d?e\"f<g>h|i\nj", "document"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_file_stem(" .. ", "document"), "document");
+        assert_eq!(sanitize_file_stem("", "document"), "document");
+        assert_eq!(sanitize_file_stem(&"x".repeat(500), "document").len(), MAX_STEM_CHARS);
+    }
+
+    #[test]
+    fn unique_paths_never_reuse_an_existing_file() {
+        let dir = std::env::temp_dir().join(format!("editron-requests-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let first = unique_path(&dir, "Notes", "pdf");
+        assert_eq!(first, dir.join("Notes.pdf"));
+        std::fs::write(&first, b"1").unwrap();
+        let second = unique_path(&dir, "Notes", "pdf");
+        assert_eq!(second, dir.join("Notes (2).pdf"));
+        std::fs::write(&second, b"2").unwrap();
+        assert_eq!(unique_path(&dir, "Notes", "pdf"), dir.join("Notes (3).pdf"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn written_files_leave_no_part_file() {
+        let dir = std::env::temp_dir().join(format!("editron-requests-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let destination = dir.join("Notes.md");
+        write_file(&destination, b"# Notes".to_vec()).await.unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"# Notes");
+        assert!(!TempFile::beside(&destination).path().exists());
+
+        assert!(write_file(&dir.join("missing").join("Notes.md"), Vec::new()).await.is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
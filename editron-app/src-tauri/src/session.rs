@@ -0,0 +1,774 @@
+use crate::config::AppConfig;
+use crate::http_client;
+use crate::inspector::RecordedSend;
+use crate::locks::lock;
+use crate::store_schema::{self, StoreSchema};
+use crate::timestamp;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+/// Lifetime given to access tokens issued by the backend
+const TOKEN_LIFETIME_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Server {
+    pub id: String,
+    pub profile: Option<UserProfile>,
+    pub available: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerAccessToken {
+    pub server_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: u64,
+}
+
+impl ServerAccessToken {
+    pub fn new(server_id: String, access_token: String, refresh_token: String, expires_at: u64) -> Self {
+        Self {
+            server_id,
+            access_token,
+            refresh_token,
+            expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserProfile {
+    pub id: i32,
+    pub email: String,
+    pub name: String,
+    #[serde(rename = "profilePicture")]
+    pub profile_picture: Option<String>,
+    #[serde(rename = "authProvider")]
+    pub auth_provider: String,
+    #[serde(rename = "isGoogleApiConnected")]
+    pub is_google_api_connected: Option<bool>,
+}
+
+/// Token pair returned by the code exchange
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TokenPair {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthUrlResponse {
+    url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenExchangeRequest {
+    code: String,
+    #[serde(rename = "codeVerifier")]
+    code_verifier: String,
+    provider: String,
+    #[serde(rename = "tauriRedirectUri")]
+    tauri_redirect_uri: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenRequest {
+    #[serde(rename = "refreshToken")]
+    refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenResponse {
+    #[serde(rename = "accessToken")]
+    access_token: String,
+}
+
+pub type Tokens = HashMap<String, ServerAccessToken>;
+
+/// Generate a random state parameter for OAuth security
+fn generate_state() -> String {
+    use base64::{engine::general_purpose, Engine as _};
+
+    general_purpose::URL_SAFE_NO_PAD.encode(
+        (0..32).map(|_| rand::random::<u8>()).collect::<Vec<u8>>()
+    )
+}
+
+/// Where servers and tokens are kept between sessions.
+/// `save_*` receive a snapshot function so the implementation can take the
+/// snapshot under its own write lock and never write a stale state.
+pub trait SessionStorage: Send + Sync {
+    fn load_servers(&self) -> Result<Option<Vec<Server>>, String>;
+    fn load_tokens(&self) -> Result<Option<Tokens>, String>;
+    fn save_servers(&self, snapshot: &dyn Fn() -> Vec<Server>) -> Result<(), String>;
+    fn save_tokens(&self, snapshot: &dyn Fn() -> Tokens) -> Result<(), String>;
+}
+
+/// Stores servers and tokens in versioned files in the app data directory
+pub struct FileStorage {
+    app: AppHandle,
+    servers: &'static StoreSchema,
+    tokens: &'static StoreSchema,
+}
+
+impl FileStorage {
+    pub fn new(app: AppHandle, demo: bool) -> Self {
+        let [servers, tokens] = store_schema::session_stores(demo);
+        Self { app, servers, tokens }
+    }
+}
+
+impl SessionStorage for FileStorage {
+    fn load_servers(&self) -> Result<Option<Vec<Server>>, String> {
+        store_schema::load(&self.app, self.servers)
+    }
+
+    fn load_tokens(&self) -> Result<Option<Tokens>, String> {
+        store_schema::load(&self.app, self.tokens)
+    }
+
+    fn save_servers(&self, snapshot: &dyn Fn() -> Vec<Server>) -> Result<(), String> {
+        store_schema::save_with(&self.app, self.servers, snapshot)
+    }
+
+    fn save_tokens(&self, snapshot: &dyn Fn() -> Tokens) -> Result<(), String> {
+        store_schema::save_with(&self.app, self.tokens, snapshot)
+    }
+}
+
+/// Keeps servers and tokens in memory only, for tests and throwaway sessions
+#[derive(Default)]
+pub struct MemoryStorage {
+    servers: Mutex<Option<Vec<Server>>>,
+    tokens: Mutex<Option<Tokens>>,
+}
+
+impl SessionStorage for MemoryStorage {
+    fn load_servers(&self) -> Result<Option<Vec<Server>>, String> {
+        Ok(lock(&self.servers).clone())
+    }
+
+    fn load_tokens(&self) -> Result<Option<Tokens>, String> {
+        Ok(lock(&self.tokens).clone())
+    }
+
+    fn save_servers(&self, snapshot: &dyn Fn() -> Vec<Server>) -> Result<(), String> {
+        let mut servers = lock(&self.servers);
+        *servers = Some(snapshot());
+        Ok(())
+    }
+
+    fn save_tokens(&self, snapshot: &dyn Fn() -> Tokens) -> Result<(), String> {
+        let mut tokens = lock(&self.tokens);
+        *tokens = Some(snapshot());
+        Ok(())
+    }
+}
+
+/// The backend endpoints the login flows talk to.
+/// Profile errors for a rejected token start with "Unauthorized".
+pub trait AuthBackend: Send + Sync {
+    /// Returns the Google consent URL that redirects to `redirect_uri`.
+    /// `state` is handed back unchanged on the redirect.
+    fn login_url<'a>(&'a self, redirect_uri: &'a str, state: &'a str) -> BoxFuture<'a, Result<String, String>>;
+    fn exchange_code<'a>(&'a self, code: &'a str, redirect_uri: &'a str) -> BoxFuture<'a, Result<TokenPair, String>>;
+    /// Returns a new access token for the refresh token
+    fn refresh<'a>(&'a self, refresh_token: &'a str) -> BoxFuture<'a, Result<String, String>>;
+    fn profile<'a>(&'a self, access_token: &'a str) -> BoxFuture<'a, Result<UserProfile, String>>;
+}
+
+/// Talks to the real gateway over HTTP
+pub struct HttpAuthBackend {
+    config: AppConfig,
+}
+
+impl HttpAuthBackend {
+    pub fn new(config: AppConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl AuthBackend for HttpAuthBackend {
+    fn login_url<'a>(&'a self, redirect_uri: &'a str, state: &'a str) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let auth_url_endpoint = format!("{}?redirect_uri={}&state={}",
+                self.config.google_login_url(),
+                url::form_urlencoded::byte_serialize(redirect_uri.as_bytes()).collect::<String>(),
+                url::form_urlencoded::byte_serialize(state.as_bytes()).collect::<String>());
+
+            let res = http_client::get_client()
+                .get(&auth_url_endpoint)
+                .send_recorded()
+                .await
+                .map_err(|e| {
+                    log::error!("Failed to get auth URL from backend: {}", e);
+                    format!("Backend request failed: {}", e)
+                })?;
+
+            if !res.status().is_success() {
+                let error_body = res.text().await.unwrap_or_default();
+                log::error!("Backend auth URL request failed: {}", error_body);
+                return Err("Failed to get auth URL from backend".into());
+            }
+
+            let auth_response: AuthUrlResponse = res.json().await.map_err(|e| {
+                log::error!("Failed to parse auth URL response: {}", e);
+                format!("Failed to parse backend response: {}", e)
+            })?;
+            Ok(auth_response.url)
+        })
+    }
+
+    fn exchange_code<'a>(&'a self, code: &'a str, redirect_uri: &'a str) -> BoxFuture<'a, Result<TokenPair, String>> {
+        Box::pin(async move {
+            let exchange_request = TokenExchangeRequest {
+                code: code.to_string(),
+                code_verifier: String::new(), // Not using PKCE
+                provider: "google-oauth2".to_string(),
+                tauri_redirect_uri: redirect_uri.to_string(),
+            };
+
+            let res = http_client::get_client()
+                .post(&self.config.token_exchange_url())
+                .json(&exchange_request)
+                .send_recorded()
+                .await
+                .map_err(|e| {
+                    log::error!("Token exchange request failed: {}", e);
+                    e.to_string()
+                })?;
+
+            if !res.status().is_success() {
+                let error_body = res.text().await.unwrap_or_default();
+                log::error!("Token exchange failed: {}", error_body);
+                return Err("Token exchange failed".into());
+            }
+
+            res.json().await.map_err(|e| {
+                log::error!("Failed to parse token response: {}", e);
+                e.to_string()
+            })
+        })
+    }
+
+    fn refresh<'a>(&'a self, refresh_token: &'a str) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let res = http_client::get_client()
+                .post(&self.config.token_refresh_url())
+                .json(&RefreshTokenRequest { refresh_token: refresh_token.to_string() })
+                .send_recorded()
+                .await
+                .map_err(|e| {
+                    log::error!("Token refresh request failed: {}", e);
+                    e.to_string()
+                })?;
+
+            if !res.status().is_success() {
+                let status = res.status();
+                log::warn!("Token refresh failed with status: {}", status);
+                return Err(format!("Token refresh failed with status: {}", status));
+            }
+
+            let refresh_response: RefreshTokenResponse = res.json().await.map_err(|e| {
+                log::error!("Failed to parse token refresh response: {}", e);
+                e.to_string()
+            })?;
+            Ok(refresh_response.access_token)
+        })
+    }
+
+    /// Desktop apps use JWT tokens in Authorization headers, NOT cookies
+    fn profile<'a>(&'a self, access_token: &'a str) -> BoxFuture<'a, Result<UserProfile, String>> {
+        Box::pin(async move {
+            let res = http_client::get_client()
+                .get(&self.config.user_profile_url())
+                .header("Authorization", format!("Bearer {}", access_token))
+                .send_recorded()
+                .await
+                .map_err(|e| {
+                    log::error!("Error fetching profile with JWT token: {}", e);
+                    e.to_string()
+                })?;
+
+            if res.status().is_success() {
+                res.json().await.map_err(|e| {
+                    log::error!("Error parsing profile JSON: {}", e);
+                    e.to_string()
+                })
+            } else if res.status() == reqwest::StatusCode::UNAUTHORIZED {
+                log::warn!("Profile request returned 401 Unauthorized - token expired");
+                Err("Unauthorized: Token expired or invalid".to_string())
+            } else {
+                let status = res.status();
+                log::error!("Profile request failed with status: {}", status);
+                Err(format!("Request failed with status: {}", status))
+            }
+        })
+    }
+}
+
+/// Owns the configuration, the known servers and the access tokens of the
+/// running app. Registered with `app.manage` during setup and injected into
+/// commands; storage and backend are traits so the flows run without Tauri.
+pub struct SessionManager {
+    config: AppConfig,
+    servers: Mutex<Vec<Server>>,
+    tokens: Mutex<Tokens>,
+    oauth_state: Mutex<Option<String>>,
+    storage: Box<dyn SessionStorage>,
+    backend: Box<dyn AuthBackend>,
+}
+
+impl SessionManager {
+    pub fn new(config: AppConfig, storage: impl SessionStorage + 'static, backend: impl AuthBackend + 'static) -> Self {
+        Self {
+            config,
+            servers: Mutex::new(Vec::new()),
+            tokens: Mutex::new(HashMap::new()),
+            oauth_state: Mutex::new(None),
+            storage: Box::new(storage),
+            backend: Box::new(backend),
+        }
+    }
+
+    /// Returns the application configuration
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    fn server_id(&self) -> &str {
+        &self.config.server.default_server_id
+    }
+
+    /// Returns the stored token pair of the default server, if the user is logged in
+    pub fn current_token(&self) -> Option<ServerAccessToken> {
+        lock(&self.tokens).get(self.server_id()).cloned()
+    }
+
+    /// Returns the access token of the default server, if the user is logged in
+    pub fn current_access_token(&self) -> Option<String> {
+        lock(&self.tokens).get(self.server_id()).map(|t| t.access_token.clone())
+    }
+
+    /// Returns a stable key for the logged-in account, used to separate local data per user
+    pub fn current_account_key(&self) -> Option<String> {
+        let profile = self.server(self.server_id())?.profile?;
+        Some(format!("{}-{}", self.server_id(), profile.id))
+    }
+
+    /// Returns the profile of the logged-in user, if it is known
+    pub fn current_profile(&self) -> Option<UserProfile> {
+        self.server(self.server_id())?.profile
+    }
+
+    /// Gets a server by ID
+    pub fn server(&self, id: &str) -> Option<Server> {
+        lock(&self.servers).iter().find(|s| s.id == id).cloned()
+    }
+
+    /// Saves or updates a server
+    pub fn save_server(&self, server: &Server) {
+        let mut servers = lock(&self.servers);
+        if let Some(idx) = servers.iter().position(|s| s.id == server.id) {
+            servers[idx] = server.clone();
+        } else {
+            servers.push(server.clone());
+        }
+    }
+
+    /// Saves the access token of a server
+    pub fn save_access_token(&self, token: ServerAccessToken) {
+        lock(&self.tokens).insert(token.server_id.clone(), token);
+    }
+
+    /// Removes the access token of a server (used during logout)
+    pub fn remove_access_token(&self, server_id: &str) {
+        lock(&self.tokens).remove(server_id);
+    }
+
+    /// Checks if a server has an access token
+    pub fn has_access_token(&self, server_id: &str) -> bool {
+        lock(&self.tokens).contains_key(server_id)
+    }
+
+    /// Loads servers from storage
+    pub fn load_servers(&self) -> Result<(), String> {
+        if let Some(loaded) = self.storage.load_servers()? {
+            *lock(&self.servers) = loaded;
+            log::info!("Servers loaded from storage");
+        } else {
+            log::info!("No servers found in storage - starting fresh");
+        }
+        Ok(())
+    }
+
+    /// Loads access tokens from storage
+    pub fn load_tokens(&self) -> Result<(), String> {
+        if let Some(loaded) = self.storage.load_tokens()? {
+            *lock(&self.tokens) = loaded;
+            log::info!("Access tokens loaded from storage");
+        } else {
+            log::info!("No tokens found in storage - starting fresh");
+        }
+        Ok(())
+    }
+
+    /// Persists servers to storage
+    pub fn persist_servers(&self) -> Result<(), String> {
+        self.storage.save_servers(&|| lock(&self.servers).clone())?;
+        log::info!("Servers persisted to storage");
+        Ok(())
+    }
+
+    /// Persists access tokens to storage
+    pub fn persist_tokens(&self) -> Result<(), String> {
+        self.storage.save_tokens(&|| lock(&self.tokens).clone())?;
+        log::info!("Access tokens persisted to storage");
+        Ok(())
+    }
+
+    /// Starts a new OAuth attempt and asks the backend for its Google consent URL.
+    /// The attempt's state parameter must come back with the callback.
+    pub async fn begin_login(&self, redirect_uri: &str) -> Result<String, String> {
+        let state = generate_state();
+        *lock(&self.oauth_state) = Some(state.clone());
+        self.backend.login_url(redirect_uri, &state).await
+    }
+
+    /// Fetches the profile of the logged-in user from the backend
+    pub async fn fetch_profile(&self) -> Result<UserProfile, String> {
+        log::info!("Fetching user profile from backend using JWT token");
+        let token = self
+            .current_access_token()
+            .ok_or_else(|| "No JWT access token found".to_string())?;
+        let profile = self.backend.profile(&token).await?;
+        log::info!("Successfully fetched user profile for user {}", profile.id);
+        Ok(profile)
+    }
+
+    /// Exchanges the OAuth code for tokens, stores them and the user's profile,
+    /// and returns the profile. The callback's `state` must match the attempt
+    /// started by `begin_login`; each attempt can be finalized once.
+    pub async fn finalize_login(&self, code: &str, state: Option<&str>, redirect_uri: &str) -> Result<UserProfile, String> {
+        let server_id = self.server_id().to_string();
+
+        let expected = lock(&self.oauth_state).take();
+        if expected.is_none() || state != expected.as_deref() {
+            log::error!("OAuth callback state does not match the login attempt - rejecting it");
+            return Err("Login rejected: the OAuth state did not match".to_string());
+        }
+
+        log::info!("Exchanging OAuth code for tokens");
+        let tokens = self.backend.exchange_code(code, redirect_uri).await?;
+        log::info!("Successfully exchanged code for tokens");
+
+        self.save_access_token(ServerAccessToken::new(
+            server_id.clone(),
+            tokens.access_token,
+            tokens.refresh_token,
+            timestamp::now_secs() + TOKEN_LIFETIME_SECS,
+        ));
+        self.persist_tokens().map_err(|e| {
+            log::error!("Failed to persist tokens: {}", e);
+            e
+        })?;
+
+        // Get user profile using the new token
+        let profile = self.fetch_profile().await.map_err(|e| {
+            log::error!("Failed to get user profile after token exchange: {}", e);
+            e
+        })?;
+
+        let mut server = self.server(&server_id).unwrap_or(Server {
+            id: server_id.clone(),
+            profile: None,
+            available: false,
+        });
+        server.profile = Some(profile.clone());
+        server.available = true;
+        self.save_server(&server);
+        self.persist_servers().map_err(|e| {
+            log::error!("Failed to persist servers: {}", e);
+            e
+        })?;
+
+        Ok(profile)
+    }
+
+    /// Exchanges the stored refresh token for a new access token and persists it.
+    /// Returns the new access token so the caller can retry the failed request.
+    pub async fn refresh_access_token(&self) -> Result<String, String> {
+        log::info!("Refreshing access token");
+        let server_id = self.server_id();
+
+        let refresh_token = self
+            .current_token()
+            .map(|t| t.refresh_token)
+            .ok_or_else(|| "No refresh token found".to_string())?;
+
+        let access_token = self.backend.refresh(&refresh_token).await?;
+
+        {
+            let mut tokens = lock(&self.tokens);
+            let token = tokens
+                .get_mut(server_id)
+                .ok_or_else(|| "Access token was removed during refresh".to_string())?;
+            token.access_token = access_token.clone();
+            token.expires_at = timestamp::now_secs() + TOKEN_LIFETIME_SECS;
+        }
+
+        self.persist_tokens().map_err(|e| {
+            log::error!("Failed to persist refreshed token: {}", e);
+            e
+        })?;
+
+        log::info!("Access token refreshed successfully");
+        Ok(access_token)
+    }
+
+    /// Drops the token and profile of the default server
+    pub fn logout(&self) -> Result<(), String> {
+        let server_id = self.server_id().to_string();
+
+        self.remove_access_token(&server_id);
+        self.persist_tokens()?;
+
+        // Update server availability
+        if let Some(mut server) = self.server(&server_id) {
+            server.available = false;
+            server.profile = None;
+            self.save_server(&server);
+            self.persist_servers()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the session manager registered during setup
+pub fn manager(app: &AppHandle) -> State<'_, SessionManager> {
+    app.state::<SessionManager>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REDIRECT_URI: &str = "http://localhost:8765/auth/callback";
+
+    /// Answers like the gateway: codes become `access-<code>` tokens, which
+    /// the profile endpoint accepts until `revoked` is set
+    #[derive(Default)]
+    struct MockBackend {
+        exchanged: Mutex<Vec<String>>,
+        refreshed: Mutex<Vec<String>>,
+        revoked: Mutex<bool>,
+        refresh_fails: bool,
+    }
+
+    impl AuthBackend for MockBackend {
+        fn login_url<'a>(&'a self, redirect_uri: &'a str, state: &'a str) -> BoxFuture<'a, Result<String, String>> {
+            Box::pin(async move { Ok(format!("https://accounts.example.com/o/oauth2?redirect_uri={}&state={}", redirect_uri, state)) })
+        }
+
+        fn exchange_code<'a>(&'a self, code: &'a str, _redirect_uri: &'a str) -> BoxFuture<'a, Result<TokenPair, String>> {
+            Box::pin(async move {
+                lock(&self.exchanged).push(code.to_string());
+                Ok(TokenPair {
+                    access_token: format!("access-{}", code),
+                    refresh_token: format!("refresh-{}", code),
+                })
+            })
+        }
+
+        fn refresh<'a>(&'a self, refresh_token: &'a str) -> BoxFuture<'a, Result<String, String>> {
+            Box::pin(async move {
+                lock(&self.refreshed).push(refresh_token.to_string());
+                if self.refresh_fails {
+                    return Err("Token refresh failed with status: 401 Unauthorized".to_string());
+                }
+                *lock(&self.revoked) = false;
+                Ok("access-refreshed".to_string())
+            })
+        }
+
+        fn profile<'a>(&'a self, access_token: &'a str) -> BoxFuture<'a, Result<UserProfile, String>> {
+            Box::pin(async move {
+                if *lock(&self.revoked) || !access_token.starts_with("access-") {
+                    return Err("Unauthorized: Token expired or invalid".to_string());
+                }
+                Ok(UserProfile {
+                    id: 7,
+                    email: "writer@example.com".to_string(),
+                    name: "Writer".to_string(),
+                    profile_picture: None,
+                    auth_provider: "google".to_string(),
+                    is_google_api_connected: Some(false),
+                })
+            })
+        }
+    }
+
+    /// Shares the mock between the manager and the test
+    impl AuthBackend for std::sync::Arc<MockBackend> {
+        fn login_url<'a>(&'a self, redirect_uri: &'a str, state: &'a str) -> BoxFuture<'a, Result<String, String>> {
+            self.as_ref().login_url(redirect_uri, state)
+        }
+
+        fn exchange_code<'a>(&'a self, code: &'a str, redirect_uri: &'a str) -> BoxFuture<'a, Result<TokenPair, String>> {
+            self.as_ref().exchange_code(code, redirect_uri)
+        }
+
+        fn refresh<'a>(&'a self, refresh_token: &'a str) -> BoxFuture<'a, Result<String, String>> {
+            self.as_ref().refresh(refresh_token)
+        }
+
+        fn profile<'a>(&'a self, access_token: &'a str) -> BoxFuture<'a, Result<UserProfile, String>> {
+            self.as_ref().profile(access_token)
+        }
+    }
+
+    impl SessionStorage for std::sync::Arc<MemoryStorage> {
+        fn load_servers(&self) -> Result<Option<Vec<Server>>, String> {
+            self.as_ref().load_servers()
+        }
+
+        fn load_tokens(&self) -> Result<Option<Tokens>, String> {
+            self.as_ref().load_tokens()
+        }
+
+        fn save_servers(&self, snapshot: &dyn Fn() -> Vec<Server>) -> Result<(), String> {
+            self.as_ref().save_servers(snapshot)
+        }
+
+        fn save_tokens(&self, snapshot: &dyn Fn() -> Tokens) -> Result<(), String> {
+            self.as_ref().save_tokens(snapshot)
+        }
+    }
+
+    struct Harness {
+        session: SessionManager,
+        storage: std::sync::Arc<MemoryStorage>,
+        backend: std::sync::Arc<MockBackend>,
+    }
+
+    fn harness(backend: MockBackend) -> Harness {
+        let storage = std::sync::Arc::new(MemoryStorage::default());
+        let backend = std::sync::Arc::new(backend);
+        let session = SessionManager::new(AppConfig::default(), storage.clone(), backend.clone());
+        Harness { session, storage, backend }
+    }
+
+    /// The state parameter of a consent URL returned by the mock
+    fn state_of(url: &str) -> String {
+        url.split("state=").nth(1).expect("the URL carries a state").to_string()
+    }
+
+    async fn log_in(harness: &Harness) -> UserProfile {
+        let url = harness.session.begin_login(REDIRECT_URI).await.unwrap();
+        harness.session.finalize_login("code-1", Some(&state_of(&url)), REDIRECT_URI).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn login_stores_tokens_and_profile() {
+        let harness = harness(MockBackend::default());
+
+        let profile = log_in(&harness).await;
+        assert_eq!(profile.id, 7);
+        assert_eq!(harness.session.current_access_token().as_deref(), Some("access-code-1"));
+        assert_eq!(harness.session.current_account_key().as_deref(), Some("backend_v1-7"));
+
+        let tokens = harness.storage.load_tokens().unwrap().unwrap();
+        assert_eq!(tokens["backend_v1"].refresh_token, "refresh-code-1");
+        let servers = harness.storage.load_servers().unwrap().unwrap();
+        assert!(servers[0].available);
+        assert_eq!(servers[0].profile.as_ref().map(|p| p.id), Some(7));
+    }
+
+    #[tokio::test]
+    async fn login_with_a_different_state_is_rejected() {
+        let harness = harness(MockBackend::default());
+        harness.session.begin_login(REDIRECT_URI).await.unwrap();
+
+        let result = harness.session.finalize_login("code-1", Some("forged"), REDIRECT_URI).await;
+        assert!(result.is_err());
+        assert!(harness.session.current_access_token().is_none());
+        assert!(lock(&harness.backend.exchanged).is_empty());
+    }
+
+    #[tokio::test]
+    async fn login_callbacks_need_a_started_attempt_and_count_once() {
+        let harness = harness(MockBackend::default());
+        assert!(harness.session.finalize_login("code-1", None, REDIRECT_URI).await.is_err());
+
+        let url = harness.session.begin_login(REDIRECT_URI).await.unwrap();
+        let state = state_of(&url);
+        assert!(harness.session.finalize_login("code-1", None, REDIRECT_URI).await.is_err());
+        // The failed callback used up the attempt
+        assert!(harness.session.finalize_login("code-1", Some(&state), REDIRECT_URI).await.is_err());
+        assert!(lock(&harness.backend.exchanged).is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_replaces_the_access_token() {
+        let harness = harness(MockBackend::default());
+        log_in(&harness).await;
+        *lock(&harness.backend.revoked) = true;
+        assert!(harness.session.fetch_profile().await.unwrap_err().starts_with("Unauthorized"));
+
+        let access_token = harness.session.refresh_access_token().await.unwrap();
+        assert_eq!(access_token, "access-refreshed");
+        assert_eq!(*lock(&harness.backend.refreshed), vec!["refresh-code-1".to_string()]);
+        assert!(harness.session.fetch_profile().await.is_ok());
+
+        let tokens = harness.storage.load_tokens().unwrap().unwrap();
+        assert_eq!(tokens["backend_v1"].access_token, "access-refreshed");
+        assert_eq!(tokens["backend_v1"].refresh_token, "refresh-code-1");
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_the_stored_tokens() {
+        let harness = harness(MockBackend { refresh_fails: true, ..Default::default() });
+        log_in(&harness).await;
+
+        assert!(harness.session.refresh_access_token().await.is_err());
+        assert_eq!(harness.session.current_access_token().as_deref(), Some("access-code-1"));
+    }
+
+    #[tokio::test]
+    async fn refresh_without_a_login_fails() {
+        let harness = harness(MockBackend::default());
+        assert!(harness.session.refresh_access_token().await.is_err());
+        assert!(lock(&harness.backend.refreshed).is_empty());
+    }
+
+    #[tokio::test]
+    async fn logout_drops_the_token_and_profile() {
+        let harness = harness(MockBackend::default());
+        log_in(&harness).await;
+
+        harness.session.logout().unwrap();
+        assert!(harness.session.current_access_token().is_none());
+        assert!(harness.session.current_profile().is_none());
+        assert!(harness.storage.load_tokens().unwrap().unwrap().is_empty());
+        let servers = harness.storage.load_servers().unwrap().unwrap();
+        assert!(!servers[0].available);
+        assert!(servers[0].profile.is_none());
+    }
+
+    #[tokio::test]
+    async fn stored_sessions_are_loaded_on_startup() {
+        let harness = harness(MockBackend::default());
+        log_in(&harness).await;
+
+        let restarted = SessionManager::new(AppConfig::default(), harness.storage.clone(), harness.backend.clone());
+        restarted.load_servers().unwrap();
+        restarted.load_tokens().unwrap();
+        assert_eq!(restarted.current_access_token().as_deref(), Some("access-code-1"));
+        assert_eq!(restarted.current_account_key().as_deref(), Some("backend_v1-7"));
+    }
+}
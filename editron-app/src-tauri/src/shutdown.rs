@@ -0,0 +1,167 @@
+use crate::outbox::Outbox;
+use crate::locks::lock;
+use crate::session;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{oneshot, Notify};
+use tokio::time::Instant;
+
+/// Upper bound for draining pending work before the app exits anyway
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(8);
+/// Time given to the webview to hand over debounced saves after `shutdown_progress`
+const UI_FLUSH_GRACE: Duration = Duration::from_millis(300);
+
+/// Stops an OAuth callback server; shared with the server's request handler
+pub type CallbackServerShutdown = Arc<Mutex<Option<oneshot::Sender<()>>>>;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownPhase {
+    /// Pending saves, queued changes and stores are being written
+    Saving,
+    /// Everything was written or the deadline passed; the app exits next
+    Done,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ShutdownProgress {
+    pub phase: ShutdownPhase,
+    /// Queued document changes left for the next start
+    #[serde(rename = "queuedChanges")]
+    pub queued_changes: usize,
+}
+
+/// Coordinates exit: holds it while document saves, the outbox and the stores
+/// drain, stops the OAuth callback servers, then exits.
+#[derive(Default)]
+pub struct Shutdown {
+    started: AtomicBool,
+    finished: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    callback_servers: Mutex<HashMap<u16, CallbackServerShutdown>>,
+}
+
+/// Marks a save in progress; shutdown waits for it until the deadline
+pub struct WorkGuard<'a>(&'a Shutdown);
+
+impl Drop for WorkGuard<'_> {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&self) -> WorkGuard<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        WorkGuard(self)
+    }
+
+    async fn wait_until_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Whether the exit may proceed without holding it
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    pub fn register_callback_server(&self, port: u16, shutdown: CallbackServerShutdown) {
+        lock(&self.callback_servers).insert(port, shutdown);
+    }
+
+    /// Forgets a callback server, stopping it first if it is still running
+    pub fn release_callback_server(&self, port: u16) {
+        let server = lock(&self.callback_servers).remove(&port);
+        if let Some(server) = server {
+            stop_callback_server(port, &server);
+        }
+    }
+
+    fn stop_callback_servers(&self) {
+        let servers: Vec<_> = lock(&self.callback_servers).drain().collect();
+        for (port, server) in servers {
+            stop_callback_server(port, &server);
+        }
+    }
+}
+
+fn stop_callback_server(port: u16, server: &CallbackServerShutdown) {
+    if let Some(sender) = lock(server).take() {
+        log::info!("Stopping OAuth callback server on port {}", port);
+        let _ = sender.send(());
+    }
+}
+
+fn emit_progress(app: &AppHandle, phase: ShutdownPhase, queued_changes: usize) {
+    if let Err(e) = app.emit("shutdown_progress", ShutdownProgress { phase, queued_changes }) {
+        log::error!("Failed to emit shutdown_progress event: {}", e);
+    }
+}
+
+/// Starts draining pending work and exits with `code` when done. Does nothing
+/// when a shutdown is already under way.
+pub fn begin(app: &AppHandle, code: i32) {
+    if app.state::<Shutdown>().started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    log::info!("Shutting down: saving pending work");
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let shutdown = app.state::<Shutdown>();
+        let deadline = Instant::now() + SHUTDOWN_DEADLINE;
+        emit_progress(&app, ShutdownPhase::Saving, 0);
+
+        tokio::time::sleep(UI_FLUSH_GRACE).await;
+        if tokio::time::timeout_at(deadline, shutdown.wait_until_idle()).await.is_err() {
+            log::warn!("Exiting with {} saves still in progress", shutdown.in_flight.load(Ordering::SeqCst));
+        }
+
+        let queued_changes = match tokio::time::timeout_at(deadline, app.state::<Outbox>().flush(&app)).await {
+            Ok(Ok(remaining)) => remaining,
+            Ok(Err(e)) => {
+                log::warn!("Could not sync queued changes before exit: {}", e);
+                0
+            }
+            Err(_) => {
+                log::warn!("Syncing queued changes did not finish before the exit deadline");
+                0
+            }
+        };
+        if queued_changes > 0 {
+            log::info!("{} queued document changes will be synced on the next start", queued_changes);
+        }
+
+        let session = session::manager(&app);
+        if let Err(e) = session.persist_servers() {
+            log::error!("Failed to save servers on exit: {}", e);
+        }
+        if let Err(e) = session.persist_tokens() {
+            log::error!("Failed to save tokens on exit: {}", e);
+        }
+
+        shutdown.stop_callback_servers();
+
+        emit_progress(&app, ShutdownPhase::Done, queued_changes);
+        shutdown.finished.store(true, Ordering::SeqCst);
+        log::info!("Shutdown complete");
+        app.exit(code);
+    });
+}
@@ -0,0 +1,204 @@
+/// A single dispatched Server-Sent Event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+/// Incremental parser for the `text/event-stream` format as described in the
+/// HTML Living Standard. Bytes can be fed in arbitrary chunks; complete events
+/// are returned as soon as their terminating blank line has been seen.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event_type: String,
+    data: String,
+    has_data: bool,
+    /// The `id:` field of the event being read, kept across events
+    id_buffer: Option<String>,
+    /// The ID of the last dispatched event
+    last_event_id: Option<String>,
+    retry_ms: Option<u64>,
+    skip_leading_lf: bool,
+    bom_checked: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last event ID seen on the stream, sent as `Last-Event-ID` on reconnect
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// The reconnection delay requested by the server with a `retry:` field
+    pub fn retry_ms(&self) -> Option<u64> {
+        self.retry_ms
+    }
+
+    /// Ends the current connection. A partial line and an event that was not
+    /// terminated by a blank line are discarded, as the spec requires; the last
+    /// event ID and retry delay are kept for the reconnection.
+    pub fn end_stream(&mut self) {
+        self.buffer.clear();
+        self.event_type.clear();
+        self.data.clear();
+        self.has_data = false;
+        self.id_buffer = self.last_event_id.clone();
+        self.skip_leading_lf = false;
+        self.bom_checked = false;
+    }
+
+    /// Feeds a chunk of bytes and returns every event completed by it
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        if !self.bom_checked && self.buffer.len() >= 3 {
+            if self.buffer.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                self.buffer.drain(..3);
+            }
+            self.bom_checked = true;
+        }
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+
+        while i < self.buffer.len() {
+            let byte = self.buffer[i];
+
+            // A CR at the very end may be followed by an LF in the next chunk
+            if self.skip_leading_lf && i == start && byte == b'\n' {
+                self.skip_leading_lf = false;
+                start = i + 1;
+                i += 1;
+                continue;
+            }
+            self.skip_leading_lf = false;
+
+            if byte == b'\n' || byte == b'\r' {
+                let line = String::from_utf8_lossy(&self.buffer[start..i]).into_owned();
+                if byte == b'\r' {
+                    if i + 1 < self.buffer.len() {
+                        if self.buffer[i + 1] == b'\n' {
+                            i += 1;
+                        }
+                    } else {
+                        self.skip_leading_lf = true;
+                    }
+                }
+                if let Some(event) = self.process_line(&line) {
+                    events.push(event);
+                }
+                start = i + 1;
+            }
+            i += 1;
+        }
+
+        self.buffer.drain(..start);
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Lines starting with a colon are comments (often used as keep-alives)
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.find(':') {
+            Some(pos) => {
+                let value = &line[pos + 1..];
+                (&line[..pos], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event_type = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.id_buffer = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry_ms = value.parse().ok();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        self.last_event_id = self.id_buffer.clone();
+        let event_type = std::mem::take(&mut self.event_type);
+        let mut data = std::mem::take(&mut self.data);
+
+        if !self.has_data {
+            return None;
+        }
+        self.has_data = false;
+
+        if data.ends_with('\n') {
+            data.pop();
+        }
+
+        Some(SseEvent {
+            event: if event_type.is_empty() { "message".to_string() } else { event_type },
+            data,
+            id: self.last_event_id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.data.as_str()).collect()
+    }
+
+    #[test]
+    fn events_split_across_chunks() {
+        let mut parser = SseParser::new();
+        assert!(parser.feed(b"event: upd").is_empty());
+        assert!(parser.feed(b"ate\r").is_empty());
+        let events = parser.feed(b"\ndata: one\r\ndata: two\r\n\r\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "update");
+        assert_eq!(events[0].data, "one\ntwo");
+    }
+
+    #[test]
+    fn pending_event_is_discarded_at_end_of_stream() {
+        let mut parser = SseParser::new();
+        let events = parser.feed(b"id: 1\ndata: first\n\nid: 2\nevent: chunk\ndata: par");
+        assert_eq!(data(&events), ["first"]);
+        // The second event was never dispatched, so it is not resumed past
+        assert_eq!(parser.last_event_id(), Some("1"));
+
+        parser.end_stream();
+        let events = parser.feed(b"data: second\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "second");
+        assert_eq!(events[0].id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn retry_and_event_id_survive_the_end_of_stream() {
+        let mut parser = SseParser::new();
+        parser.feed(b"retry: 1500\nid: 7\ndata: x\n\n");
+        parser.end_stream();
+        assert_eq!(parser.last_event_id(), Some("7"));
+        assert_eq!(parser.retry_ms(), Some(1500));
+    }
+}
@@ -0,0 +1,497 @@
+use crate::config::SETTINGS_STORE;
+use crate::locks::lock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Migrates the data of one schema version to the next
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Describes a value kept in a store file under `key`. The value is wrapped in
+/// an envelope `{"schemaVersion": n, "data": ...}`; `migrations[i]` upgrades
+/// data from version `i` to `i + 1`, so `migrations.len()` is the current version.
+pub struct StoreSchema {
+    pub file: &'static str,
+    pub key: &'static str,
+    pub migrations: &'static [Migration],
+}
+
+impl StoreSchema {
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+}
+
+/// Version 0 stored the data directly under the key, without an envelope
+fn wrap_unversioned(data: Value) -> Result<Value, String> {
+    Ok(data)
+}
+
+pub const SERVERS: StoreSchema = StoreSchema {
+    file: "servers.json",
+    key: "servers",
+    migrations: &[wrap_unversioned],
+};
+
+pub const TOKENS: StoreSchema = StoreSchema {
+    file: "tokens.json",
+    key: "tokens",
+    migrations: &[wrap_unversioned],
+};
+
+/// Demo mode keeps its servers and tokens apart from the real account's
+pub const DEMO_SERVERS: StoreSchema = StoreSchema {
+    file: "demo-servers.json",
+    key: "servers",
+    migrations: &[wrap_unversioned],
+};
+
+pub const DEMO_TOKENS: StoreSchema = StoreSchema {
+    file: "demo-tokens.json",
+    key: "tokens",
+    migrations: &[wrap_unversioned],
+};
+
+/// The servers and tokens stores used in the given mode
+pub fn session_stores(demo: bool) -> [&'static StoreSchema; 2] {
+    if demo {
+        [&DEMO_SERVERS, &DEMO_TOKENS]
+    } else {
+        [&SERVERS, &TOKENS]
+    }
+}
+
+pub const LOG_LEVEL: StoreSchema = StoreSchema {
+    file: SETTINGS_STORE,
+    key: "logLevel",
+    migrations: &[wrap_unversioned],
+};
+
+pub const CAPTURE_BODIES: StoreSchema = StoreSchema {
+    file: SETTINGS_STORE,
+    key: "captureRequestBodies",
+    migrations: &[wrap_unversioned],
+};
+
+/// Payload of the `store_recovered` event
+#[derive(Serialize, Clone, Debug)]
+pub struct StoreRecovered {
+    pub file: String,
+    /// True when the last-known-good backup was restored, false when the store was reset
+    pub restored: bool,
+    /// Where the unreadable file was moved to
+    #[serde(rename = "corruptCopy")]
+    pub corrupt_copy: Option<String>,
+}
+
+lazy_static::lazy_static! {
+    /// Serializes store writes so a stale snapshot can never overwrite a newer one
+    static ref WRITE_LOCK: Mutex<()> = Mutex::new(());
+    /// Recoveries performed during this session, for the startup checks
+    static ref RECOVERED: Mutex<Vec<StoreRecovered>> = Mutex::new(Vec::new());
+    /// Stores written by a newer version, by file and key; saving them would lose that data
+    static ref UNSUPPORTED: Mutex<HashSet<(PathBuf, &'static str)>> = Mutex::new(HashSet::new());
+}
+
+/// Returns the store recoveries performed during this session
+pub fn recovered() -> Vec<StoreRecovered> {
+    lock(&RECOVERED).clone()
+}
+
+/// Location of a store file in the app data directory
+pub fn path(app: &AppHandle, schema: &StoreSchema) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(schema.file))
+        .map_err(|e| e.to_string())
+}
+
+/// The last-known-good copy of a store file
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Writes `bytes` to a temporary file, flushes it to disk and renames it over `path`
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    let tmp = path.with_file_name(name);
+
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+/// Reads the raw value stored under the schema's key. `Ok(None)` means the
+/// file or key does not exist; an error means the file could not be parsed.
+fn read_raw(path: &Path, schema: &StoreSchema) -> Result<Option<Value>, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut file: serde_json::Map<String, Value> = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    Ok(file.remove(schema.key))
+}
+
+/// Splits a stored value into its schema version and data
+pub fn open_envelope(raw: Value) -> (u32, Value) {
+    match raw {
+        Value::Object(mut map) if map.contains_key("schemaVersion") && map.contains_key("data") => {
+            let version = map.get("schemaVersion").and_then(Value::as_u64).unwrap_or(0) as u32;
+            (version, map.remove("data").unwrap_or(Value::Null))
+        }
+        legacy => (0, legacy),
+    }
+}
+
+/// Serializes the store file with `data` under the schema's key in the current
+/// schema version. Other keys already in the file are kept.
+fn encode(path: &Path, schema: &StoreSchema, data: Value) -> Result<Vec<u8>, String> {
+    let mut file: serde_json::Map<String, Value> = std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    file.insert(schema.key.to_string(), json!({ "schemaVersion": schema.version(), "data": data }));
+    serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())
+}
+
+/// Writes the store file and refreshes its last-known-good backup
+fn write_store(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    write_atomic(path, bytes).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    write_atomic(&backup_path(path), bytes).map_err(|e| format!("Failed to back up {:?}: {}", path, e))
+}
+
+/// Runs the migrations needed to bring `data` from `version` to the current version
+fn migrate(schema: &StoreSchema, version: u32, mut data: Value) -> Result<Value, String> {
+    for (from, migration) in schema.migrations.iter().enumerate().skip(version as usize) {
+        data = migration(data).map_err(|e| {
+            format!("Migrating {} from version {} to {} failed: {}", schema.file, from, from + 1, e)
+        })?;
+    }
+    Ok(data)
+}
+
+/// Copies the store file aside before it is rewritten by a migration
+fn backup_before_migration(path: &Path, schema: &StoreSchema, version: u32) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let target = path.with_file_name(format!("{}.v{}.bak", schema.file, version));
+    std::fs::copy(path, &target).map_err(|e| format!("Failed to back up {}: {}", schema.file, e))?;
+    log::info!("Backed up {} to {:?} before migrating", schema.file, target);
+    Ok(())
+}
+
+/// Why a store file could not be read
+enum ReadError {
+    /// The file is corrupt or its data no longer fits the expected type
+    Unreadable(String),
+    /// The file is fine but cannot be used, e.g. it was written by a newer version
+    Unsupported(String),
+}
+
+/// A value read from a store file, with the version it was stored in and its migrated data
+struct Decoded<T> {
+    value: T,
+    version: u32,
+    data: Value,
+}
+
+/// Migrates a stored value to the current version and deserializes it
+fn decode<T: DeserializeOwned>(schema: &StoreSchema, raw: Value) -> Result<Decoded<T>, ReadError> {
+    let (version, data) = open_envelope(raw);
+    if version > schema.version() {
+        return Err(ReadError::Unsupported(format!(
+            "{} was written by a newer version of Editron (schema {}, supported {})",
+            schema.file,
+            version,
+            schema.version()
+        )));
+    }
+
+    let data = migrate(schema, version, data).map_err(ReadError::Unreadable)?;
+    let value = serde_json::from_value(data.clone())
+        .map_err(|e| ReadError::Unreadable(format!("Failed to read {}: {}", schema.file, e)))?;
+    Ok(Decoded { value, version, data })
+}
+
+fn read<T: DeserializeOwned>(path: &Path, schema: &StoreSchema) -> Result<Option<Decoded<T>>, ReadError> {
+    match read_raw(path, schema).map_err(ReadError::Unreadable)? {
+        Some(raw) => decode(schema, raw).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Handles an unreadable store file: the corrupt file is moved aside and the
+/// last-known-good backup restored if it can be read; otherwise the store starts empty.
+fn recover<T: DeserializeOwned>(schema: &StoreSchema, path: &Path) -> (StoreRecovered, Option<Decoded<T>>) {
+    let corrupt = path.with_file_name(format!("{}.corrupt-{}", schema.file, crate::timestamp::now_millis()));
+    let corrupt_copy = match std::fs::rename(path, &corrupt) {
+        Ok(()) => Some(corrupt.to_string_lossy().into_owned()),
+        Err(e) => {
+            log::error!("Failed to move corrupt {} aside: {}", schema.file, e);
+            None
+        }
+    };
+
+    let backup = backup_path(path);
+    let restored = match read(&backup, schema) {
+        Ok(Some(decoded)) => match std::fs::read(&backup).and_then(|bytes| write_atomic(path, &bytes)) {
+            Ok(()) => Some(decoded),
+            Err(e) => {
+                log::error!("Failed to restore {} from backup: {}", schema.file, e);
+                None
+            }
+        },
+        Ok(None) => {
+            log::warn!("No backup of {} to restore", schema.file);
+            None
+        }
+        Err(ReadError::Unreadable(e) | ReadError::Unsupported(e)) => {
+            log::error!("Backup of {} is unusable too: {}", schema.file, e);
+            None
+        }
+    };
+
+    if restored.is_some() {
+        log::warn!("Restored {} from its last-known-good backup", schema.file);
+    } else {
+        log::error!("Could not recover {} - starting with an empty store", schema.file);
+    }
+
+    let event = StoreRecovered {
+        file: schema.file.to_string(),
+        restored: restored.is_some(),
+        corrupt_copy,
+    };
+    (event, restored)
+}
+
+/// Loads the value described by `schema`, migrating and rewriting it first when
+/// it was written by an older version. A file that cannot be parsed or no longer
+/// deserializes into `T` is recovered from its backup and reported through
+/// `on_recovered`. Returns `None` when nothing is stored.
+fn load_file<T: DeserializeOwned>(
+    path: &Path,
+    schema: &StoreSchema,
+    on_recovered: impl FnOnce(StoreRecovered),
+) -> Result<Option<T>, String> {
+    let decoded = match read(path, schema) {
+        Ok(decoded) => decoded,
+        Err(ReadError::Unsupported(e)) => {
+            lock(&UNSUPPORTED).insert((path.to_path_buf(), schema.key));
+            return Err(e);
+        }
+        Err(ReadError::Unreadable(e)) => {
+            log::error!("{} is unreadable: {}", schema.file, e);
+            let (event, decoded) = recover(schema, path);
+            on_recovered(event);
+            decoded
+        }
+    };
+    lock(&UNSUPPORTED).remove(&(path.to_path_buf(), schema.key));
+    let Some(decoded) = decoded else {
+        return Ok(None);
+    };
+
+    if decoded.version < schema.version() {
+        log::info!("Migrating {} from schema {} to {}", schema.file, decoded.version, schema.version());
+        backup_before_migration(path, schema, decoded.version)?;
+        let _guard = lock(&WRITE_LOCK);
+        write_store(path, &encode(path, schema, decoded.data)?)?;
+    }
+
+    // The file was read, so it becomes the last-known-good copy
+    if let Err(e) = std::fs::read(path).and_then(|bytes| write_atomic(&backup_path(path), &bytes)) {
+        log::warn!("Failed to refresh backup of {}: {}", schema.file, e);
+    }
+    Ok(Some(decoded.value))
+}
+
+/// Loads the value described by `schema` from the app data directory.
+/// Recoveries are recorded for the startup checks and emitted as `store_recovered`.
+pub fn load<T: DeserializeOwned>(app: &AppHandle, schema: &StoreSchema) -> Result<Option<T>, String> {
+    load_file(&path(app, schema)?, schema, |event| {
+        lock(&RECOVERED).push(event.clone());
+        if let Err(e) = app.emit("store_recovered", event) {
+            log::error!("Failed to emit store_recovered event: {}", e);
+        }
+    })
+}
+
+/// Stores the value returned by `snapshot` and saves the store file atomically.
+/// The snapshot is taken under the write lock, so concurrent saves are applied
+/// in order and the newest state always wins.
+pub fn save_with<T: Serialize>(app: &AppHandle, schema: &StoreSchema, snapshot: impl FnOnce() -> T) -> Result<(), String> {
+    save_file(&path(app, schema)?, schema, snapshot)
+}
+
+/// Saves to `path`, unless the store there was written by a newer version and
+/// refused by `load_file`; it is left untouched until a load succeeds.
+fn save_file<T: Serialize>(path: &Path, schema: &StoreSchema, snapshot: impl FnOnce() -> T) -> Result<(), String> {
+    if lock(&UNSUPPORTED).contains(&(path.to_path_buf(), schema.key)) {
+        return Err(format!("{} was written by a newer version of Editron and is not saved", schema.file));
+    }
+    let _guard = lock(&WRITE_LOCK);
+    let data = serde_json::to_value(snapshot()).map_err(|e| e.to_string())?;
+    write_store(path, &encode(path, schema, data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{Server, Tokens};
+
+    /// Store files as written before schema versions existed
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/stores");
+
+    /// A copy of a fixture in a directory of its own, removed on drop
+    struct Fixture {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl Fixture {
+        fn copy(file: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("editron-store-{}", rand::random::<u64>()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(file);
+            std::fs::copy(Path::new(FIXTURES).join(file), &path).unwrap();
+            Self { dir, path }
+        }
+
+        /// The raw value stored under the schema's key
+        fn stored(&self, schema: &StoreSchema) -> Value {
+            let file: Value = serde_json::from_slice(&std::fs::read(&self.path).unwrap()).unwrap();
+            file[schema.key].clone()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn no_recovery(event: StoreRecovered) {
+        panic!("{} was recovered unexpectedly", event.file);
+    }
+
+    #[test]
+    fn unversioned_tokens_are_migrated() {
+        let fixture = Fixture::copy("tokens.json");
+
+        let tokens: Tokens = load_file(&fixture.path, &TOKENS, no_recovery).unwrap().unwrap();
+        let token = &tokens["default-1"];
+        assert_eq!(token.server_id, "default-1");
+        assert_eq!(token.access_token, "fixture-access-token");
+        assert_eq!(token.refresh_token, "fixture-refresh-token");
+        assert_eq!(token.expires_at, 1735689600);
+
+        let stored = fixture.stored(&TOKENS);
+        assert_eq!(stored["schemaVersion"], json!(TOKENS.version()));
+        assert_eq!(stored["data"]["default-1"]["access_token"], "fixture-access-token");
+        assert!(fixture.dir.join("tokens.json.v0.bak").exists());
+    }
+
+    #[test]
+    fn unversioned_servers_are_migrated() {
+        let fixture = Fixture::copy("servers.json");
+
+        let servers: Vec<Server> = load_file(&fixture.path, &SERVERS, no_recovery).unwrap().unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].id, "default-1");
+        assert!(servers[0].available);
+        let profile = servers[0].profile.as_ref().unwrap();
+        assert_eq!(profile.id, 42);
+        assert_eq!(profile.email, "writer@example.com");
+        assert_eq!(profile.auth_provider, "google");
+        assert_eq!(profile.is_google_api_connected, Some(true));
+
+        let stored = fixture.stored(&SERVERS);
+        assert_eq!(stored["schemaVersion"], json!(SERVERS.version()));
+        assert_eq!(stored["data"][0]["profile"]["email"], "writer@example.com");
+    }
+
+    #[test]
+    fn unversioned_settings_are_migrated_key_by_key() {
+        let fixture = Fixture::copy(SETTINGS_STORE);
+
+        let level: String = load_file(&fixture.path, &LOG_LEVEL, no_recovery).unwrap().unwrap();
+        assert_eq!(level, "debug");
+        // Migrating one key leaves the other in place, still unversioned
+        assert_eq!(fixture.stored(&CAPTURE_BODIES), json!(true));
+
+        let capture: bool = load_file(&fixture.path, &CAPTURE_BODIES, no_recovery).unwrap().unwrap();
+        assert!(capture);
+
+        assert_eq!(fixture.stored(&LOG_LEVEL), json!({ "schemaVersion": LOG_LEVEL.version(), "data": "debug" }));
+        assert_eq!(fixture.stored(&CAPTURE_BODIES), json!({ "schemaVersion": CAPTURE_BODIES.version(), "data": true }));
+    }
+
+    #[test]
+    fn migrated_stores_load_unchanged() {
+        let fixture = Fixture::copy("tokens.json");
+        let first: Tokens = load_file(&fixture.path, &TOKENS, no_recovery).unwrap().unwrap();
+        let migrated = std::fs::read(&fixture.path).unwrap();
+
+        let second: Tokens = load_file(&fixture.path, &TOKENS, no_recovery).unwrap().unwrap();
+        assert_eq!(second["default-1"].access_token, first["default-1"].access_token);
+        assert_eq!(std::fs::read(&fixture.path).unwrap(), migrated);
+    }
+
+    #[test]
+    fn data_of_the_wrong_shape_is_restored_from_backup() {
+        let fixture = Fixture::copy("tokens.json");
+        let _: Tokens = load_file(&fixture.path, &TOKENS, no_recovery).unwrap().unwrap();
+
+        let wrong = json!({ "tokens": { "schemaVersion": TOKENS.version(), "data": { "default-1": { "server_id": 7 } } } });
+        std::fs::write(&fixture.path, serde_json::to_vec(&wrong).unwrap()).unwrap();
+
+        let mut recovered = None;
+        let tokens: Tokens = load_file(&fixture.path, &TOKENS, |event| recovered = Some(event)).unwrap().unwrap();
+        assert_eq!(tokens["default-1"].access_token, "fixture-access-token");
+
+        let recovered = recovered.expect("recovery is reported");
+        assert!(recovered.restored);
+        assert!(Path::new(&recovered.corrupt_copy.unwrap()).exists());
+    }
+
+    #[test]
+    fn unreadable_store_without_backup_starts_empty() {
+        let fixture = Fixture::copy("tokens.json");
+        std::fs::write(&fixture.path, b"{ not json").unwrap();
+
+        let mut recovered = None;
+        let tokens: Option<Tokens> = load_file(&fixture.path, &TOKENS, |event| recovered = Some(event)).unwrap();
+        assert!(tokens.is_none());
+        assert!(!recovered.expect("recovery is reported").restored);
+    }
+
+    #[test]
+    fn stores_from_newer_versions_are_left_alone() {
+        let fixture = Fixture::copy("tokens.json");
+        let newer = serde_json::to_vec(&json!({ "tokens": { "schemaVersion": TOKENS.version() + 1, "data": {} } })).unwrap();
+        std::fs::write(&fixture.path, &newer).unwrap();
+
+        assert!(load_file::<Tokens>(&fixture.path, &TOKENS, no_recovery).is_err());
+        assert_eq!(std::fs::read(&fixture.path).unwrap(), newer);
+
+        // Saving what this version knows would drop the newer data
+        assert!(save_file(&fixture.path, &TOKENS, Tokens::new).is_err());
+        assert_eq!(std::fs::read(&fixture.path).unwrap(), newer);
+    }
+}
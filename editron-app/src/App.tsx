@@ -73,6 +73,7 @@ function App() {
   const [projects, setProjects] = useState<Project[]>([]);
   const [showCreateProject, setShowCreateProject] = useState(false);
   const [showWelcome, setShowWelcome] = useState(false);
+  const [isShuttingDown, setIsShuttingDown] = useState(false);
 
   useEffect(() => {
     const checkInitialLogin = async () => {
@@ -120,10 +121,16 @@ function App() {
       setProfile(null);
     });
 
+    // The app stays open on exit until pending saves are written
+    const unlistenShutdown = listen<{ phase: 'saving' | 'done' }>("shutdown_progress", (e) => {
+      setIsShuttingDown(e.payload.phase === 'saving');
+    });
+
     return () => {
       unlistenSuccess.then(f => f());
       unlistenFailed.then(f => f());
       unlistenLogout.then(f => f());
+      unlistenShutdown.then(f => f());
     };
   }, []);
 
@@ -311,6 +318,14 @@ function App() {
 
   return (
     <Router>
+      {isShuttingDown && (
+        <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/40">
+          <div className="card-modern text-center p-8">
+            <div className="animate-spin rounded-full h-10 w-10 border-b-2 border-primary-500 mx-auto mb-4"></div>
+            <p className="text-neutral-700 font-medium">Saving your work…</p>
+          </div>
+        </div>
+      )}
       {profile && (
         <Layout profile={profile} onLogout={() => setLoggedIn(false)}>
           <Routes>
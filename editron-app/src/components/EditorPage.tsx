@@ -1,11 +1,13 @@
-import { useState, useEffect, useCallback } from 'react';
+import { useState, useEffect, useCallback, useRef } from 'react';
 import { useParams, useNavigate } from 'react-router-dom';
 import { ArrowLeft, Clock, Mail, Download } from 'lucide-react';
 import { Button } from '@/components/ui/button';
+import { ToastAction } from '@/components/ui/toast';
 import TiptapEditor from './TiptapEditor';
 import { AgentReviewModal } from './Diff/AgentReviewModal';
 import ComposeEmailModal from './Email/ComposeEmailModal';
-import { apiClient } from '../utils/api';
+import { listen } from '@tauri-apps/api/event';
+import { apiClient, OutboxEntry, RequestCancelledError, SyncConflict } from '../utils/api';
 import { useToast } from '../hooks/use-toast';
 
 // Global state for agent request function
@@ -30,6 +32,9 @@ export const addMessageToChat = (message: { role: 'user' | 'assistant'; content:
   }
 };
 
+// Delay between the last keystroke and the journal snapshot
+const JOURNAL_DEBOUNCE_MS = 500;
+
 interface Document {
   uuid: string;
   title: string;
@@ -67,8 +72,10 @@ const EditorPage = () => {
   const [isComposeModalOpen, setIsComposeModalOpen] = useState(false);
   
   // Download state
-  const [isDownloading, setIsDownloading] = useState(false);
+  const [downloadRequestId, setDownloadRequestId] = useState<string | null>(null);
   const { toast } = useToast();
+  const journalTimer = useRef<ReturnType<typeof setTimeout> | null>(null);
+  const pendingJournal = useRef<(() => void) | null>(null);
 
   useEffect(() => {
     if (uuid) {
@@ -77,6 +84,56 @@ const EditorPage = () => {
     }
   }, [uuid]);
 
+  // Queued edits that no longer apply cleanly are held until the user decides
+  useEffect(() => {
+    const unlistenConflict = listen<SyncConflict>('sync_conflict', (e) => {
+      if (e.payload.entry.documentUuid !== uuid) return;
+      toast({
+        title: 'Sync conflict',
+        description: e.payload.server
+          ? 'This document was changed elsewhere while you were offline. Your changes have not been synced.'
+          : 'This document was deleted on the server. Your changes have not been synced.',
+        variant: 'destructive',
+      });
+    });
+
+    // Changes the gateway kept rejecting stay queued until the user retries them
+    const unlistenFailed = listen<OutboxEntry>('sync_failed', (e) => {
+      if (e.payload.documentUuid !== uuid) return;
+      const entryId = e.payload.id;
+      toast({
+        title: 'Sync failed',
+        description: `Your changes could not be synced after ${e.payload.attempts} attempts: ${e.payload.lastError ?? 'unknown error'}`,
+        variant: 'destructive',
+        action: (
+          <ToastAction altText="Retry sync" onClick={() => apiClient.resolveSyncConflict(entryId, { action: 'retry' })}>
+            Retry
+          </ToastAction>
+        ),
+      });
+    });
+
+    // Queued edits merged with server changes replace the editor contents
+    const unlistenMerged = listen<Document>('document_merged', (e) => {
+      if (e.payload.uuid !== uuid) return;
+      setDocument(e.payload);
+    });
+
+    // On exit, write the pending snapshot right away instead of waiting for the debounce
+    const unlistenShutdown = listen<{ phase: 'saving' | 'done' }>('shutdown_progress', (e) => {
+      if (e.payload.phase !== 'saving' || !pendingJournal.current) return;
+      if (journalTimer.current) clearTimeout(journalTimer.current);
+      pendingJournal.current();
+    });
+
+    return () => {
+      unlistenConflict.then(f => f());
+      unlistenFailed.then(f => f());
+      unlistenMerged.then(f => f());
+      unlistenShutdown.then(f => f());
+    };
+  }, [uuid]);
+
   const fetchUserProfile = async () => {
     try {
       const { invoke } = await import('@tauri-apps/api/core');
@@ -110,11 +167,27 @@ const EditorPage = () => {
     // Update local state immediately for responsive UI
     setDocument(prev => prev ? { ...prev, content } : null);
 
+    // Journal a debounced snapshot so the edit survives a crash or a rejected save
+    if (journalTimer.current) clearTimeout(journalTimer.current);
+    pendingJournal.current = () => {
+      journalTimer.current = null;
+      pendingJournal.current = null;
+      apiClient
+        .journalDocument(document.uuid, content, document.updatedAt, projectUuid, document.title)
+        .catch(error => console.error('Error journaling document:', error));
+    };
+    journalTimer.current = setTimeout(pendingJournal.current, JOURNAL_DEBOUNCE_MS);
+
     // Show saving indicator
     setIsSaving(true);
     
     try {
-      await apiClient.updateDocument(document.uuid, { content }, projectUuid);
+      if (projectUuid) {
+        // Queued edits are stored locally first and synced in the background
+        await apiClient.queueDocumentChange(projectUuid, document.uuid, { kind: 'edit', content }, document.updatedAt);
+      } else {
+        await apiClient.updateDocument(document.uuid, { content }, projectUuid);
+      }
       setLastSaved(new Date());
     } catch (error) {
       console.error('Error saving document:', error);
@@ -208,6 +281,7 @@ const EditorPage = () => {
     if (!document) return;
     
     try {
+      await apiClient.recordAgentEdit(document.uuid, document.title, finalContent);
       await handleContentChange(finalContent);
       setIsAgentModalOpen(false);
       setAgentDiffHtml(null);
@@ -224,26 +298,25 @@ const EditorPage = () => {
 
   const handleDownloadPdf = async () => {
     if (!document) return;
-    
-    setIsDownloading(true);
+    if (downloadRequestId) {
+      await apiClient.cancelRequest(downloadRequestId);
+      return;
+    }
+
+    const requestId = crypto.randomUUID();
+    setDownloadRequestId(requestId);
     try {
-      const blob = await apiClient.downloadPdf(document.uuid);
-      
-      // Create a download link
-      const url = window.URL.createObjectURL(blob);
-      const link = window.document.createElement('a');
-      link.href = url;
-      link.download = `${document.title}.pdf`;
-      window.document.body.appendChild(link);
-      link.click();
-      window.document.body.removeChild(link);
-      window.URL.revokeObjectURL(url);
-      
+      const path = await apiClient.downloadPdf(document.uuid, document.title, requestId);
+
       toast({
         title: 'Download Successful',
-        description: 'Your document has been downloaded. Please check your Downloads folder.',
+        description: `Your document has been saved to ${path}`,
       });
     } catch (error) {
+      if (error instanceof RequestCancelledError) {
+        toast({ title: 'Download Cancelled' });
+        return;
+      }
       console.error('Download failed:', error);
       toast({
         title: 'Download Failed',
@@ -251,7 +324,7 @@ const EditorPage = () => {
         variant: 'destructive',
       });
     } finally {
-      setIsDownloading(false);
+      setDownloadRequestId(null);
     }
   };
 
@@ -352,15 +425,14 @@ const EditorPage = () => {
           <div className="flex items-center gap-3">
             <Button
               onClick={handleDownloadPdf}
-              disabled={isDownloading}
               variant="outline"
               size="sm"
               className="btn-secondary gap-2"
             >
-              {isDownloading ? (
+              {downloadRequestId ? (
                 <>
                   <div className="w-3 h-3 border border-primary-500 border-t-transparent rounded-full animate-spin"></div>
-                  Downloading...
+                  Cancel Download
                 </>
               ) : (
                 <>
@@ -4,7 +4,7 @@ import { Button } from '@/components/ui/button';
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card';
 import { Upload, FileText, Calendar, MessageSquare, MoreVertical, Trash2, Download } from 'lucide-react';
 import { DropdownMenu, DropdownMenuContent, DropdownMenuItem, DropdownMenuTrigger } from './ui/dropdown-menu';
-import { apiClient } from '../utils/api';
+import { apiClient, RequestCancelledError } from '../utils/api';
 import { ProjectModal } from './CreateProjectModal';
 import { DeleteConfirmationDialog } from './DeleteConfirmationDialog';
 import { useToast } from '../hooks/use-toast';
@@ -34,7 +34,7 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
   const [isLoading, setIsLoading] = useState(true);
   const [isUploading, setIsUploading] = useState(false);
   const [error, setError] = useState<string | null>(null);
-  const [downloadingDoc, setDownloadingDoc] = useState<string | null>(null);
+  const [download, setDownload] = useState<{ documentUuid: string; requestId: string } | null>(null);
   const { toast } = useToast();
 
   useEffect(() => {
@@ -84,6 +84,15 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
 
     setIsUploading(true);
     try {
+      // Convert locally first: invalid files are rejected before the upload
+      const preview = await apiClient.previewDocx(file);
+      if (preview.unsupported.length > 0) {
+        toast({
+          title: 'Some formatting was not imported',
+          description: preview.unsupported.join(', '),
+        });
+      }
+
       const uploadedDocument = await apiClient.uploadDocument(file, projectUuid);
       
       // Add the new document to the list
@@ -151,7 +160,12 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
     if (!projectUuid) return;
     
     try {
-      await apiClient.deleteDocument(documentUuid, projectUuid);
+      const doc = documents.find(d => d.uuid === documentUuid);
+      if (doc) {
+        await apiClient.queueDocumentChange(projectUuid, documentUuid, { kind: 'delete' }, doc.updatedAt);
+      } else {
+        await apiClient.deleteDocument(documentUuid, projectUuid);
+      }
       // Remove the document from the local state
       setDocuments(prev => prev.filter(doc => doc.uuid !== documentUuid));
     } catch (error) {
@@ -161,25 +175,25 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
   };
 
   const handleDocumentDownload = async (documentUuid: string, documentTitle: string) => {
-    setDownloadingDoc(documentUuid);
+    if (download?.documentUuid === documentUuid) {
+      await apiClient.cancelRequest(download.requestId);
+      return;
+    }
+
+    const requestId = crypto.randomUUID();
+    setDownload({ documentUuid, requestId });
     try {
-      const blob = await apiClient.downloadPdf(documentUuid);
-      
-      // Create a download link
-      const url = window.URL.createObjectURL(blob);
-      const link = window.document.createElement('a');
-      link.href = url;
-      link.download = `${documentTitle}.pdf`;
-      window.document.body.appendChild(link);
-      link.click();
-      window.document.body.removeChild(link);
-      window.URL.revokeObjectURL(url);
-      
+      const path = await apiClient.downloadPdf(documentUuid, documentTitle, requestId);
+
       toast({
         title: 'Download Successful',
-        description: 'Your document has been downloaded. Please check your Downloads folder.',
+        description: `Your document has been saved to ${path}`,
       });
     } catch (error) {
+      if (error instanceof RequestCancelledError) {
+        toast({ title: 'Download Cancelled' });
+        return;
+      }
       console.error('Download failed:', error);
       toast({
         title: 'Download Failed',
@@ -187,7 +201,7 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
         variant: 'destructive',
       });
     } finally {
-      setDownloadingDoc(null);
+      setDownload(prev => (prev?.requestId === requestId ? null : prev));
     }
   };
 
@@ -353,12 +367,11 @@ export const ProjectDashboard = ({ refreshProjects }: { refreshProjects?: () =>
                         e.stopPropagation();
                         handleDocumentDownload(doc.uuid, doc.title);
                       }}
-                      disabled={downloadingDoc === doc.uuid}
                     >
-                      {downloadingDoc === doc.uuid ? (
+                      {download?.documentUuid === doc.uuid ? (
                         <>
                           <div className="w-3 h-3 border border-primary-500 border-t-transparent rounded-full animate-spin mr-2"></div>
-                          Downloading...
+                          Cancel Download
                         </>
                       ) : (
                         <>
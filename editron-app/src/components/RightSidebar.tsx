@@ -1,6 +1,6 @@
 import React, { useState, useRef, useEffect, useCallback } from 'react';
-import { MessageSquare, Send, ChevronLeft, ChevronDown, Bot } from 'lucide-react';
-import { apiClient } from '../utils/api';
+import { MessageSquare, Send, Square, ChevronLeft, ChevronDown, Bot } from 'lucide-react';
+import { apiClient, type ChatStreamEvent } from '../utils/api';
 import { Button } from '@/components/ui/button';
 import { DropdownMenu, DropdownMenuContent, DropdownMenuItem, DropdownMenuTrigger } from '@/components/ui/dropdown-menu';
 import { Badge } from '@/components/ui/badge';
@@ -20,11 +20,34 @@ interface ChatMessage {
   mode?: 'chat' | 'agent';
 }
 
+// The stream outlives a webview reload in Rust, so its id is kept here to reattach to it
+const CHAT_STREAM_KEY = 'editron.activeChatStream';
+
+// Appends chunks to the last message until the stream ends; resolves on done or cancel
+const followChatStream = (
+  setMessages: React.Dispatch<React.SetStateAction<ChatMessage[]>>,
+  start: (onEvent: (event: ChatStreamEvent) => void) => Promise<unknown>
+) => new Promise<void>((resolve, reject) => {
+  start((event) => {
+    if (event.type === 'chunk') {
+      setMessages(prev => prev.map((msg, index) =>
+        index === prev.length - 1 ? { ...msg, content: msg.content + event.content } : msg
+      ));
+    } else if (event.type === 'error') {
+      console.error('Chat error:', event.message);
+      reject(new Error(event.message));
+    } else {
+      resolve();
+    }
+  }).catch(reject);
+});
+
 export const RightSidebar: React.FC<RightSidebarProps> = ({ isCollapsed, onToggleCollapse, onAgentRequest, documentUuid, projectUuid }) => {
   const [messages, setMessages] = useState<ChatMessage[]>([]);
   const [input, setInput] = useState('');
   const [isLoading, setIsLoading] = useState(false);
   const [isAgentMode, setIsAgentMode] = useState(false);
+  const [activeRequestId, setActiveRequestId] = useState<string | null>(null);
   const scrollAreaRef = useRef<HTMLDivElement>(null);
 
   // Check if we're on an editor page
@@ -53,10 +76,36 @@ export const RightSidebar: React.FC<RightSidebarProps> = ({ isCollapsed, onToggl
     }
   }, []);
 
-  // Fetch chat history on component mount
+  // Picks up a chat stream that was still running when the webview reloaded
+  const reattachChatStream = useCallback(async () => {
+    const requestId = sessionStorage.getItem(CHAT_STREAM_KEY);
+    if (!requestId) return;
+
+    try {
+      await followChatStream(setMessages, async (onEvent) => {
+        const snapshot = await apiClient.attachChatStream(requestId, onEvent);
+        if (snapshot.finished) {
+          // The response has already been saved to the history fetched above
+          onEvent({ type: 'done', content: snapshot.content });
+          return;
+        }
+        setActiveRequestId(requestId);
+        setIsLoading(true);
+        setMessages(prev => [...prev, { role: 'assistant', content: snapshot.content, mode: 'chat' }]);
+      });
+    } catch (error) {
+      console.error('Failed to reattach to chat stream:', error);
+    } finally {
+      sessionStorage.removeItem(CHAT_STREAM_KEY);
+      setActiveRequestId(null);
+      setIsLoading(false);
+    }
+  }, []);
+
+  // Fetch chat history on component mount, then resume any stream in progress
   useEffect(() => {
-    fetchHistory();
-  }, [fetchHistory]);
+    fetchHistory().then(reattachChatStream);
+  }, [fetchHistory, reattachChatStream]);
 
   // Set global add message function
   useEffect(() => {
@@ -126,42 +175,22 @@ export const RightSidebar: React.FC<RightSidebarProps> = ({ isCollapsed, onToggl
     // Regular chat mode
     console.log('Chat mode: processing chat query');
     setIsLoading(true);
+    const requestId = crypto.randomUUID();
+    sessionStorage.setItem(CHAT_STREAM_KEY, requestId);
+    setActiveRequestId(requestId);
 
     const assistantMessage: ChatMessage = { role: 'assistant', content: '', mode: isAgentMode ? 'agent' : 'chat' };
     setMessages(prev => [...prev, assistantMessage]);
 
     try {
-      const reader = await apiClient.chatQuery(promptText, documentUuid, projectUuid, isAgentMode ? 'agent' : 'chat');
-      
-      const decoder = new TextDecoder();
-      let done = false;
-
-      while (!done) {
-        const { value, done: readerDone } = await reader.read();
-        done = readerDone;
-        
-        if (value) {
-          const chunk = decoder.decode(value, { stream: true });
-          const lines = chunk.split('\n').filter(line => line.trim());
-          
-          for (const line of lines) {
-            if (line.startsWith('data: ')) {
-              try {
-                const data = JSON.parse(line.substring(6));
-                if (data.type === 'chunk' && data.content) {
-                  setMessages(prev => prev.map((msg, index) =>
-                    index === prev.length - 1 ? { ...msg, content: msg.content + data.content } : msg
-                  ));
-                } else if (data.type === 'error') {
-                  console.error('Chat error:', data.message);
-                }
-              } catch(e) {
-                // Ignore parse errors on partial chunks
-              }
-            }
-          }
-        }
-      }
+      await followChatStream(setMessages, (onEvent) => apiClient.chatQuery(
+        requestId,
+        promptText,
+        onEvent,
+        documentUuid,
+        projectUuid,
+        isAgentMode ? 'agent' : 'chat'
+      ));
     } catch (error) {
       console.error('Chat request failed:', error);
       setMessages(prev => prev.slice(0, -1));
@@ -171,10 +200,21 @@ export const RightSidebar: React.FC<RightSidebarProps> = ({ isCollapsed, onToggl
         mode: isAgentMode ? 'agent' : 'chat'
       }]);
     } finally {
+      sessionStorage.removeItem(CHAT_STREAM_KEY);
+      setActiveRequestId(null);
       setIsLoading(false);
     }
   };
 
+  const handleCancel = async () => {
+    if (!activeRequestId) return;
+    try {
+      await apiClient.cancelChat(activeRequestId);
+    } catch (error) {
+      console.error('Failed to cancel chat:', error);
+    }
+  };
+
   // Collapsed state
   if (isCollapsed) {
     return (
@@ -330,15 +370,27 @@ export const RightSidebar: React.FC<RightSidebarProps> = ({ isCollapsed, onToggl
               </div>
             )}
             
-            {/* Send Button - bottom-right */}
-            <Button
-              type="submit"
-              disabled={isLoading || !input.trim()}
-              size="sm"
-              className="absolute bottom-3 right-3 w-8 h-8 p-0 rounded-full bg-gradient-to-br from-primary-500 to-primary-600 hover:from-primary-600 hover:to-primary-700 text-white shadow-sm"
-            >
-              <Send size={14} />
-            </Button>
+            {/* Send Button - bottom-right, replaced by a stop button while a response streams */}
+            {activeRequestId ? (
+              <Button
+                type="button"
+                onClick={handleCancel}
+                size="sm"
+                title="Stop generating"
+                className="absolute bottom-3 right-3 w-8 h-8 p-0 rounded-full bg-neutral-700 hover:bg-neutral-800 text-white shadow-sm"
+              >
+                <Square size={12} />
+              </Button>
+            ) : (
+              <Button
+                type="submit"
+                disabled={isLoading || !input.trim()}
+                size="sm"
+                className="absolute bottom-3 right-3 w-8 h-8 p-0 rounded-full bg-gradient-to-br from-primary-500 to-primary-600 hover:from-primary-600 hover:to-primary-700 text-white shadow-sm"
+              >
+                <Send size={14} />
+              </Button>
+            )}
           </div>
         </form>
       </div>
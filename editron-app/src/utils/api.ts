@@ -1,78 +1,156 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, Channel } from '@tauri-apps/api/core';
+
+export type ChatStreamEvent =
+  | { type: 'chunk'; content: string }
+  | { type: 'done'; content: string }
+  | { type: 'error'; message: string }
+  | { type: 'cancelled'; content: string };
+
+export type ChatStreamSnapshot = {
+  requestId: string;
+  content: string;
+  finished: boolean;
+};
 
-const getApiBaseUrl = (): string => {
-  if (import.meta.env.VITE_API_BASE_URL) {
-    return import.meta.env.VITE_API_BASE_URL;
-  }
-  
-  // Fallback to default
-  return 'http://localhost:5000';
+export type BackendCapabilities = {
+  source: 'unknown' | 'document' | 'probe';
+  clientVersion: string;
+  apiVersion: string;
+  serverVersion: string | null;
+  supportedApiVersions: string[];
+  minClientVersion: string | null;
+  features: {
+    agentEdit: boolean | null;
+    gmail: boolean | null;
+    sseChat: boolean | null;
+    uploads: boolean | null;
+  };
+  updateRequired: string | null;
+  checkedAt: number | null;
 };
 
-const API_BASE_URL = getApiBaseUrl();
+export type DocumentChange =
+  | { kind: 'edit'; content: string }
+  | { kind: 'rename'; title: string }
+  | { kind: 'delete' };
+
+export type OutboxEntry = {
+  id: number;
+  documentUuid: string;
+  projectUuid: string;
+  change: DocumentChange;
+  baseVersion: string;
+  baseContent: string | null;
+  state: 'pending' | 'conflict' | 'failed';
+  attempts: number;
+  lastError: string | null;
+  createdAt: number;
+};
 
-class ApiClient {
-  private baseUrl: string;
+export type SyncConflict = {
+  entry: OutboxEntry;
+  server: { uuid: string; title: string; content: string; updatedAt: string } | null;
+};
+
+export type MergeResult = {
+  html: string;
+  conflicts: { id: number; base: string; local: string; remote: string }[];
+};
 
-  constructor(baseUrl: string = API_BASE_URL) {
-    this.baseUrl = baseUrl;
+export type RecoverableDocument = {
+  documentUuid: string;
+  projectUuid: string | null;
+  title: string | null;
+  content: string;
+  baseVersion: string;
+  savedAt: number;
+  preview: string;
+  serverVersion: string | null;
+  diff: DiffBlock[];
+};
+
+export type DocumentVersion = {
+  id: number;
+  documentUuid: string;
+  hash: string;
+  title: string;
+  source: 'save' | 'agent';
+  size: number;
+  createdAt: number;
+};
+
+export type DiffBlock = { kind: 'unchanged' | 'added' | 'removed'; html: string };
+
+export type GitMirrorConfig = {
+  projectUuid: string;
+  path: string;
+  createdAt: number;
+};
+
+export type DocxImport = {
+  title: string;
+  html: string;
+  unsupported: string[];
+};
+
+export type MarkdownImport = {
+  title: string;
+  html: string;
+  frontMatter: {
+    title?: string;
+    project?: string;
+    projectUuid?: string;
+    documentUuid?: string;
+    created?: string;
+    updated?: string;
+    [key: string]: unknown;
+  };
+};
+
+export type MarkdownFolderImport = {
+  documents: any[];
+  failed: { file: string; error: string }[];
+  warnings: { file: string; message: string }[];
+};
+
+type RequestOutcome<T> = { status: 'completed'; data: T } | { status: 'cancelled' };
+
+export class RequestCancelledError extends Error {
+  constructor() {
+    super('Request cancelled');
+    this.name = 'RequestCancelledError';
   }
+}
 
-  private async getAuthHeaders(): Promise<Record<string, string>> {
-    try {
-      // Get token from Tauri backend
-      console.log('[API] Requesting access token from Tauri...');
-      const token = await invoke<string>('get_access_token');
-      console.log('[API] ✅ Access token received:', token ? `${token.substring(0, 20)}...` : 'null');
-      return {
-        'Authorization': `Bearer ${token}`,
-        'Content-Type': 'application/json',
-      };
-    } catch (error) {
-      console.error('[API] ❌ Failed to get access token:', error);
-      throw new Error('Authentication required');
-    }
+const unwrapOutcome = <T>(outcome: RequestOutcome<T>): T => {
+  if (outcome.status === 'cancelled') {
+    throw new RequestCancelledError();
   }
+  return outcome.data;
+};
 
+// Every backend call goes through Rust, which knows the gateway URL and attaches the JWT
+class ApiClient {
   private async request<T>(
     endpoint: string,
     options: RequestInit = {}
   ): Promise<T> {
-    const url = `${this.baseUrl}${endpoint}`;
-    
-    try {
-      console.log(`[API] Making request to: ${url}`);
-      const headers = await this.getAuthHeaders();
-      
-      const response = await fetch(url, {
-        ...options,
-        headers: {
-          ...headers,
-          ...options.headers,
-        },
-      });
+    // JSON requests go through the Rust proxy, which attaches the JWT itself
+    const [path, queryString] = endpoint.split('?');
+    const query = queryString
+      ? Object.fromEntries(new URLSearchParams(queryString).entries())
+      : undefined;
+    const method = options.method ?? 'GET';
+    const body = typeof options.body === 'string' ? JSON.parse(options.body) : undefined;
 
-      console.log(`[API] Response status: ${response.status} for ${url}`);
-
-      if (!response.ok) {
-        const errorText = await response.text();
-        console.error(`[API] ❌ Request failed: ${response.status} ${errorText}`);
-        throw new Error(`API request failed: ${response.status} ${errorText}`);
-      }
-
-      const contentType = response.headers.get('content-type');
-      if (contentType && contentType.includes('application/json')) {
-        const jsonData = await response.json();
-        console.log(`[API] ✅ JSON response received for ${endpoint}`);
-        return jsonData;
-      } else {
-        const textData = await response.text();
-        console.log(`[API] ✅ Text response received for ${endpoint}`);
-        return textData as T;
-      }
+    try {
+      console.log(`[API] Making request to: ${method} ${path}`);
+      const data = await invoke<T>('backend_request', { method, path, query, body });
+      console.log(`[API] ✅ Response received for ${endpoint}`);
+      return data;
     } catch (error) {
-      console.error(`[API] ❌ Request failed for ${url}:`, error);
-      throw error;
+      console.error(`[API] ❌ Request failed for ${endpoint}:`, error);
+      throw error instanceof Error ? error : new Error(String(error));
     }
   }
 
@@ -169,48 +247,136 @@ class ApiClient {
     });
   }
 
-  async agentEdit(documentUuid: string, promptText: string, projectUuid?: string) {
-    if (projectUuid) {
-      return this.request(`/api/v1/documents/agent-edit?projectUuid=${projectUuid}`, {
-        method: 'POST',
-        body: JSON.stringify({ documentUuid, promptText }),
-      });
+  async agentEdit(documentUuid: string, promptText: string, projectUuid?: string, requestId: string = crypto.randomUUID()) {
+    if (!projectUuid) {
+      throw new Error('A project is required for agent edits');
     }
-    return this.request('/api/v1/documents/agent-edit', {
-      method: 'POST',
-      body: JSON.stringify({ documentUuid, promptText }),
-    });
+    const outcome = await invoke<RequestOutcome<unknown>>('agent_edit', { requestId, projectUuid, documentUuid, promptText });
+    return unwrapOutcome(outcome);
   }
 
-  async uploadDocument(file: File, projectUuid?: string) {
-    try {
-      const headers = await this.getAuthHeaders();
-      const formData = new FormData();
-      formData.append('file', file);
-
-      const uploadEndpoint = projectUuid 
-        ? `/api/v1/documents/upload-and-preview?projectUuid=${projectUuid}`
-        : '/api/v1/documents/upload-and-preview';
-      
-      const response = await fetch(`${this.baseUrl}${uploadEndpoint}`, {
-        method: 'POST',
-        headers: {
-          // Remove Content-Type to let browser set it with boundary for FormData
-          'Authorization': headers['Authorization'],
-        },
-        body: formData,
-      });
+  async uploadDocument(file: File, projectUuid: string, requestId: string = crypto.randomUUID()) {
+    const contents = Array.from(new Uint8Array(await file.arrayBuffer()));
+    const outcome = await invoke<RequestOutcome<unknown>>('upload_document', { requestId, projectUuid, fileName: file.name, contents });
+    return unwrapOutcome(outcome);
+  }
 
-      if (!response.ok) {
-        const errorText = await response.text();
-        throw new Error(`Upload failed: ${response.status} ${errorText}`);
-      }
+  // DOCX files converted locally, without the gateway
+  async previewDocx(file: File): Promise<DocxImport> {
+    const contents = Array.from(new Uint8Array(await file.arrayBuffer()));
+    return invoke<DocxImport>('preview_docx', { fileName: file.name, contents });
+  }
 
-      return response.json();
-    } catch (error) {
-      console.error('Upload failed:', error);
-      throw error;
-    }
+  async importDocx(file: File, projectUuid: string, documentUuid: string, baseVersion: string): Promise<OutboxEntry> {
+    const contents = Array.from(new Uint8Array(await file.arrayBuffer()));
+    return invoke<OutboxEntry>('import_docx', { projectUuid, documentUuid, baseVersion, fileName: file.name, contents });
+  }
+
+  // Exports are saved to the Downloads folder; Rust picks the path and returns it
+  async exportDocx(projectUuid: string, documentUuid: string, requestId: string = crypto.randomUUID()) {
+    const outcome = await invoke<RequestOutcome<string>>('export_docx', { requestId, projectUuid, documentUuid });
+    return unwrapOutcome(outcome);
+  }
+
+  // Markdown with YAML front matter
+  async importMarkdown(file: File): Promise<MarkdownImport> {
+    return invoke<MarkdownImport>('import_markdown', { fileName: file.name, contents: await file.text() });
+  }
+
+  async exportMarkdown(projectUuid: string, documentUuid: string, requestId: string = crypto.randomUUID()) {
+    const outcome = await invoke<RequestOutcome<string>>('export_markdown', { requestId, projectUuid, documentUuid });
+    return unwrapOutcome(outcome);
+  }
+
+  async importMarkdownFolder(projectUuid: string, folder: string, requestId: string = crypto.randomUUID()) {
+    const outcome = await invoke<RequestOutcome<MarkdownFolderImport>>('import_markdown_folder', { requestId, projectUuid, folder });
+    return unwrapOutcome(outcome);
+  }
+
+  async cancelRequest(requestId: string) {
+    return invoke<boolean>('cancel_request', { requestId });
+  }
+
+  // Local library: projects and documents readable without the gateway
+  async getCachedProjects() {
+    return invoke('list_cached_projects');
+  }
+
+  async getCachedDocuments(projectUuid: string) {
+    return invoke('list_cached_documents', { projectUuid });
+  }
+
+  async getCachedDocument(documentUuid: string) {
+    return invoke('get_cached_document', { documentUuid });
+  }
+
+  // Offline edit queue: changes are stored locally and synced in the background
+  async queueDocumentChange(
+    projectUuid: string,
+    documentUuid: string,
+    change: DocumentChange,
+    baseVersion: string
+  ): Promise<OutboxEntry> {
+    return invoke<OutboxEntry>('queue_document_change', { projectUuid, documentUuid, change, baseVersion });
+  }
+
+  async getOutbox(): Promise<OutboxEntry[]> {
+    return invoke<OutboxEntry[]>('get_outbox');
+  }
+
+  async resolveSyncConflict(entryId: number, resolution: { action: 'discard' } | { action: 'overwrite'; content?: string } | { action: 'retry' }) {
+    return invoke('resolve_sync_conflict', { entryId, resolution });
+  }
+
+  async mergeDocumentVersions(base: string, local: string, remote: string): Promise<MergeResult> {
+    return invoke<MergeResult>('merge_document_versions', { base, local, remote });
+  }
+
+  // Autosave journal: editor snapshots kept on disk until the gateway confirms a save
+  async journalDocument(documentUuid: string, content: string, baseVersion: string, projectUuid?: string, title?: string) {
+    return invoke('journal_document', { projectUuid, documentUuid, title, content, baseVersion });
+  }
+
+  async recoverDocuments(): Promise<RecoverableDocument[]> {
+    return invoke<RecoverableDocument[]>('recover_documents');
+  }
+
+  async discardRecoveredDocument(documentUuid: string) {
+    return invoke('discard_recovered_document', { documentUuid });
+  }
+
+  // Local version history
+  async listDocumentVersions(documentUuid: string): Promise<DocumentVersion[]> {
+    return invoke<DocumentVersion[]>('list_document_versions', { documentUuid });
+  }
+
+  async getDocumentVersion(documentUuid: string, versionId: number): Promise<string> {
+    return invoke<string>('get_document_version', { documentUuid, versionId });
+  }
+
+  async diffDocumentVersions(documentUuid: string, fromVersion: number, toVersion: number): Promise<DiffBlock[]> {
+    return invoke<DiffBlock[]>('diff_document_versions', { documentUuid, fromVersion, toVersion });
+  }
+
+  async recordAgentEdit(documentUuid: string, title: string, content: string) {
+    return invoke('record_agent_edit', { documentUuid, title, content });
+  }
+
+  async restoreDocumentVersion(projectUuid: string, documentUuid: string, versionId: number, baseVersion: string): Promise<OutboxEntry> {
+    return invoke<OutboxEntry>('restore_document_version', { projectUuid, documentUuid, versionId, baseVersion });
+  }
+
+  // Git mirror: opt-in local repository per project
+  async getGitMirror(projectUuid: string): Promise<GitMirrorConfig | null> {
+    return invoke<GitMirrorConfig | null>('get_git_mirror', { projectUuid });
+  }
+
+  async enableGitMirror(projectUuid: string, path: string): Promise<GitMirrorConfig> {
+    return invoke<GitMirrorConfig>('enable_git_mirror', { projectUuid, path });
+  }
+
+  async disableGitMirror(projectUuid: string) {
+    return invoke('disable_git_mirror', { projectUuid });
   }
 
   // Chat API methods
@@ -218,48 +384,71 @@ class ApiClient {
     return this.request('/api/v1/chat/history');
   }
 
-  async chatQuery(promptText: string, documentUuid?: string, projectUuid?: string, mode: 'chat' | 'agent' = 'chat'): Promise<ReadableStreamDefaultReader<Uint8Array>> {
-    try {
-      console.log(`[API] Starting chat query with documentUuid: ${documentUuid || 'none'}, projectUuid: ${projectUuid || 'none'}, mode: ${mode}`);
-      const headers = await this.getAuthHeaders();
-      
-      const requestBody = { 
-        promptText, 
-        mode,
-        ...(documentUuid && { documentUuid }),
-        ...(projectUuid && { projectUuid })
-      };
-      
-      console.log('[API] Chat request body:', requestBody);
-      
-      const response = await fetch(`${this.baseUrl}/api/v1/chat/query`, {
-        method: 'POST',
-        headers: {
-          ...headers,
-          'Accept': 'text/event-stream',
-        },
-        body: JSON.stringify(requestBody),
-      });
+  async chatQuery(
+    requestId: string,
+    promptText: string,
+    onEvent: (event: ChatStreamEvent) => void,
+    documentUuid?: string,
+    projectUuid?: string,
+    mode: 'chat' | 'agent' = 'chat'
+  ): Promise<void> {
+    // The SSE stream is read in Rust and forwarded over a channel
+    console.log(`[API] Starting chat query with documentUuid: ${documentUuid || 'none'}, projectUuid: ${projectUuid || 'none'}, mode: ${mode}`);
+    const channel = new Channel<ChatStreamEvent>();
+    channel.onmessage = onEvent;
+    await invoke('chat_query', { requestId, promptText, documentUuid, projectUuid, mode, onEvent: channel });
+  }
 
-      console.log(`[API] Chat response status: ${response.status}`);
+  async cancelChat(requestId: string) {
+    return invoke('cancel_chat', { requestId });
+  }
 
-      if (!response.ok) {
-        const errorText = await response.text();
-        console.error(`[API] ❌ Chat request failed: ${response.status} ${errorText}`);
-        throw new Error(`Chat request failed: ${response.status} ${errorText}`);
-      }
+  async getChatStreams(): Promise<ChatStreamSnapshot[]> {
+    return invoke<ChatStreamSnapshot[]>('get_chat_streams');
+  }
 
-      const reader = response.body?.getReader();
-      if (!reader) {
-        throw new Error('No response body reader available');
-      }
+  // Streams keep running in Rust across a webview reload; this routes their remaining events to a new channel
+  async attachChatStream(requestId: string, onEvent: (event: ChatStreamEvent) => void): Promise<ChatStreamSnapshot> {
+    const channel = new Channel<ChatStreamEvent>();
+    channel.onmessage = onEvent;
+    return invoke<ChatStreamSnapshot>('attach_chat_stream', { requestId, onEvent: channel });
+  }
 
-      console.log('[API] ✅ Chat stream reader created');
-      return reader;
-    } catch (error) {
-      console.error('[API] ❌ Chat query failed:', error);
-      throw error;
-    }
+  async getBackendCapabilities(refresh = false): Promise<BackendCapabilities> {
+    return invoke<BackendCapabilities>('get_backend_capabilities', { refresh });
+  }
+
+  // Diagnostics
+  async getRequestLog() {
+    return invoke('get_request_log');
+  }
+
+  async exportRequestLog() {
+    return invoke('export_request_log');
+  }
+
+  async setRequestBodyCapture(enabled: boolean) {
+    return invoke('set_request_body_capture', { enabled });
+  }
+
+  async getLogLevel() {
+    return invoke<string>('get_log_level');
+  }
+
+  async setLogLevel(level: 'error' | 'warn' | 'info' | 'debug' | 'trace') {
+    return invoke('set_log_level', { level });
+  }
+
+  async exportDiagnostics() {
+    return invoke<string>('export_diagnostics');
+  }
+
+  async runDiagnostics() {
+    return invoke('run_diagnostics');
+  }
+
+  async getStartupReport() {
+    return invoke('get_startup_report');
   }
 
   // Google API methods
@@ -274,27 +463,10 @@ class ApiClient {
     });
   }
 
-  async downloadPdf(documentUuid: string): Promise<Blob> {
-    try {
-      const headers = await this.getAuthHeaders();
-      
-      const response = await fetch(`${this.baseUrl}/api/v1/google-api/download-pdf/${documentUuid}`, {
-        method: 'GET',
-        headers: {
-          'Authorization': headers['Authorization'],
-        },
-      });
-
-      if (!response.ok) {
-        const errorText = await response.text();
-        throw new Error(`Download failed: ${response.status} ${errorText}`);
-      }
-
-      return response.blob();
-    } catch (error) {
-      console.error('Download failed:', error);
-      throw error;
-    }
+  // The PDF is fetched and written to the Downloads folder in Rust, so neither the JWT nor the file path passes through the webview
+  async downloadPdf(documentUuid: string, title: string, requestId: string = crypto.randomUUID()): Promise<string> {
+    const outcome = await invoke<RequestOutcome<string>>('download_pdf', { requestId, documentUuid, title });
+    return unwrapOutcome(outcome);
   }
 }
 
@@ -11,17 +11,6 @@ declare global {
 let mockAccessToken: string | null = null;
 
 export const mockTauriCommands = {
-  get_access_token: async (): Promise<string> => {
-    if (mockAccessToken) {
-      return mockAccessToken;
-    }
-    
-    // For development, create a mock token
-    // In production, this would come from the Tauri backend after OAuth
-    mockAccessToken = 'mock-jwt-token-for-development';
-    return mockAccessToken;
-  },
-
   set_access_token: async (token: string): Promise<void> => {
     mockAccessToken = token;
   },
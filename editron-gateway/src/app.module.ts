@@ -12,6 +12,7 @@ import { RedisModule } from './redis/redis.module';
 import { ProjectModule } from './project/project.module';
 import { GoogleApiModule } from './google-api/google-api.module';
 import { WaitlistModule } from './waitlist/waitlist.module';
+import { CapabilitiesModule } from './capabilities/capabilities.module';
 import { User } from './entities/user.entity';
 import { Document } from './entities/document.entity';
 import { UserFile } from './entities/user-file.entity';
@@ -46,6 +47,7 @@ import { WaitlistEntry } from './entities/waitlist-entry.entity';
     AuthModule,
     UserModule,
     WaitlistModule,
+    CapabilitiesModule,
 
     AiGatewayModule,
     IndexingModule,
@@ -0,0 +1,22 @@
+import { Controller, Get, VERSION_NEUTRAL } from '@nestjs/common';
+
+// Bump when a client release can no longer talk to this gateway
+const MIN_CLIENT_VERSION = '0.1.0';
+
+@Controller({ path: 'capabilities', version: VERSION_NEUTRAL })
+export class CapabilitiesController {
+  @Get()
+  getCapabilities() {
+    return {
+      serverVersion: process.env.npm_package_version ?? '0.0.1',
+      apiVersions: ['v1'],
+      minClientVersion: MIN_CLIENT_VERSION,
+      features: {
+        agentEdit: true,
+        gmail: true,
+        sseChat: true,
+        uploads: true,
+      },
+    };
+  }
+}